@@ -5,26 +5,64 @@ use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
 
 use std::error::Error;
 
+pub mod liz_cases;
 pub mod liz_codes;
+pub mod liz_diffs;
+pub mod liz_distances;
+pub mod liz_edits;
+pub mod liz_encodes;
+pub mod liz_envs;
+pub mod liz_exits;
+pub mod liz_files;
 pub mod liz_fires;
 pub mod liz_forms;
 pub mod liz_group;
+pub mod liz_hashes;
 pub mod liz_logic;
+pub mod liz_logs;
+pub mod liz_packs;
 pub mod liz_parse;
+pub mod liz_paths;
+pub mod liz_procs;
+pub mod liz_renders;
+pub mod liz_rexes;
+pub mod liz_syncs;
+pub mod liz_temps;
+pub mod liz_tomls;
+pub mod liz_watches;
+pub mod liz_yamls;
 
 mod utils;
 
 mod wiz_all;
 mod wiz_codes;
+mod wiz_diffs;
+mod wiz_distances;
+mod wiz_edits;
+mod wiz_encodes;
+mod wiz_envs;
+mod wiz_exits;
+mod wiz_files;
 mod wiz_fires;
 mod wiz_forms;
 mod wiz_group;
+mod wiz_hashes;
 mod wiz_logic;
+mod wiz_logs;
+mod wiz_packs;
 mod wiz_parse;
 mod wiz_paths;
+mod wiz_procs;
+mod wiz_renders;
+mod wiz_rexes;
+mod wiz_syncs;
+mod wiz_temps;
 mod wiz_texts;
 mod wiz_times;
+mod wiz_tomls;
+mod wiz_watches;
 mod wiz_winds;
+mod wiz_yamls;
 
 pub type LizError = Box<dyn Error + Send + Sync>;
 
@@ -32,13 +70,18 @@ pub fn run(path: &str, args: &Option<Vec<String>>) -> Result<Vec<String>, LizErr
   rux_dbg_call!(path, args);
   let (rise_path, handler) = rise(path, args).map_err(|err| rux_dbg_bleb!(err))?;
   rux_dbg_step!(rise_path);
-  rux_dbg_reav!(race(&rise_path, &handler).map_err(|err| rux_dbg_bleb!(err)));
+  let result = race(&rise_path, &handler).map_err(|err| rux_dbg_bleb!(err));
+  let result = liz_exits::treat_exit(&handler, result);
+  rux_dbg_step!(result);
+  let finished = finish(&handler).map_err(|err| rux_dbg_bleb!(err));
+  rux_dbg_reav!(result.and_then(|values| finished.map(|_| values)));
 }
 
 pub fn rise(path: &str, args: &Option<Vec<String>>) -> Result<(String, Lua), LizError> {
   rux_dbg_call!(path, args);
   rux_dbg_info!("Rising a new lane", path, args);
   let handler = Lua::new();
+  liz_exits::watch_signals(&handler);
   let mut rise_path: Option<String> = None;
   let mut rise_error: Option<LizError> = None;
   handler.context(|lane| match wiz_all::inject_all(lane, path, args) {
@@ -65,6 +108,12 @@ pub fn race(path: &str, handler: &Lua) -> Result<Vec<String>, LizError> {
   rux_dbg_reav!(result.unwrap());
 }
 
+pub fn finish(handler: &Lua) -> Result<(), LizError> {
+  rux_dbg_call!();
+  rux_dbg_info!("Finishing the lane");
+  rux_dbg_reav!(handler.context(|lane| liz_exits::run_exit_handlers(lane)));
+}
+
 pub fn race_in(lane: Context, path: &str) -> Result<Vec<String>, LizError> {
   rux_dbg_call!(path);
   let globals = lane.globals();
@@ -0,0 +1,142 @@
+use rubx::{rux_dbg_call, rux_dbg_reav};
+
+pub fn words(text: &str) -> Vec<String> {
+  rux_dbg_call!(text);
+  let mut result: Vec<String> = Vec::new();
+  let mut word = String::new();
+  let mut prior: Option<char> = None;
+  let chars: Vec<char> = text.chars().collect();
+  for (index, ch) in chars.iter().enumerate() {
+    if !ch.is_alphanumeric() {
+      if !word.is_empty() {
+        result.push(std::mem::take(&mut word));
+      }
+      prior = None;
+      continue;
+    }
+    if ch.is_uppercase() {
+      if let Some(prior) = prior {
+        let next_lower = chars
+          .get(index + 1)
+          .map(|next| next.is_lowercase())
+          .unwrap_or(false);
+        let split =
+          prior.is_lowercase() || prior.is_numeric() || (prior.is_uppercase() && next_lower);
+        if split && !word.is_empty() {
+          result.push(std::mem::take(&mut word));
+        }
+      }
+    }
+    word.push(*ch);
+    prior = Some(*ch);
+  }
+  if !word.is_empty() {
+    result.push(word);
+  }
+  rux_dbg_reav!(result);
+}
+
+pub fn to_snake(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, "_", |_, word| word.to_lowercase()));
+}
+
+pub fn to_camel(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, "", |index, word| {
+    if index == 0 {
+      word.to_lowercase()
+    } else {
+      get_capital(word)
+    }
+  }));
+}
+
+pub fn to_pascal(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, "", |_, word| get_capital(word)));
+}
+
+pub fn to_kebab(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, "-", |_, word| word.to_lowercase()));
+}
+
+pub fn to_screaming_snake(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, "_", |_, word| word.to_uppercase()));
+}
+
+pub fn to_title(text: &str) -> String {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(join_words(text, " ", |_, word| get_capital(word)));
+}
+
+fn join_words(text: &str, separator: &str, convert: impl Fn(usize, &str) -> String) -> String {
+  words(text)
+    .iter()
+    .enumerate()
+    .map(|(index, word)| convert(index, word))
+    .collect::<Vec<String>>()
+    .join(separator)
+}
+
+fn get_capital(word: &str) -> String {
+  let mut chars = word.chars();
+  match chars.next() {
+    Some(first) => first
+      .to_uppercase()
+      .chain(chars.as_str().to_lowercase().chars())
+      .collect(),
+    None => String::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn words_split_on_separators_and_case() {
+    assert_eq!(
+      words("hello_world-foo bar"),
+      vec!["hello", "world", "foo", "bar"]
+    );
+    assert_eq!(words("helloWorld"), vec!["hello", "World"]);
+    assert_eq!(
+      words("  __leading..trailing__  "),
+      vec!["leading", "trailing"]
+    );
+    assert!(words("").is_empty());
+    assert!(words("-_ .").is_empty());
+  }
+
+  #[test]
+  fn words_keep_acronyms_and_digits() {
+    assert_eq!(
+      words("parseHTTPResponse2Fast"),
+      vec!["parse", "HTTP", "Response2", "Fast"]
+    );
+    assert_eq!(words("XMLHttpRequest"), vec!["XML", "Http", "Request"]);
+    assert_eq!(words("ID"), vec!["ID"]);
+    assert_eq!(words("version2Beta"), vec!["version2", "Beta"]);
+  }
+
+  #[test]
+  fn words_split_unicode_letters() {
+    assert_eq!(words("ação_Rápida"), vec!["ação", "Rápida"]);
+    assert_eq!(words("éclairÉtoile"), vec!["éclair", "Étoile"]);
+  }
+
+  #[test]
+  fn cases_convert_between_styles() {
+    let text = "parseHTTP response_code";
+    assert_eq!(to_snake(text), "parse_http_response_code");
+    assert_eq!(to_camel(text), "parseHttpResponseCode");
+    assert_eq!(to_pascal(text), "ParseHttpResponseCode");
+    assert_eq!(to_kebab(text), "parse-http-response-code");
+    assert_eq!(to_screaming_snake(text), "PARSE_HTTP_RESPONSE_CODE");
+    assert_eq!(to_title(text), "Parse Http Response Code");
+    assert_eq!(to_snake(""), "");
+  }
+}
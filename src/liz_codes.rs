@@ -1,4 +1,4 @@
-use rlua::{UserData, UserDataMethods};
+use rlua::{Table, UserData, UserDataMethods};
 use rubx::rux_winds;
 use rubx::rux_paths;
 use rubx::rux_fires;
@@ -7,6 +7,8 @@ use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
 
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::liz_distances;
+use crate::liz_files;
 use crate::liz_forms::{self, Forms};
 use crate::liz_group::{self, GroupPair};
 use crate::liz_parse::{self, BlockBy};
@@ -226,6 +228,17 @@ impl UserData for Forms {
             Ok(liz_forms::kit_find_all_like(&slf.desk, &term))
         });
 
+        methods.add_method(
+            "find_all_similar",
+            |_, slf, (term, threshold): (String, Option<f64>)| {
+                Ok(liz_forms::kit_find_all_similar(
+                    &slf.desk,
+                    &term,
+                    threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD),
+                ))
+            },
+        );
+
         methods.add_method("first_some", |_, slf, ()| {
             Ok(liz_forms::kit_first_some(&slf.desk))
         });
@@ -254,8 +267,11 @@ impl UserData for Forms {
 
         methods.add_method("build", |_, slf, ()| Ok(liz_forms::kit_build(&slf.desk)));
 
-        methods.add_method("write", |_, slf, path: String| {
-            utils::treat_error(liz_forms::kit_write(&slf.desk, &path))
+        methods.add_method("write", |_, slf, (path, opts): (String, Option<Table>)| {
+            utils::treat_error(
+                liz_files::is_atomic(opts, true)
+                    .and_then(|atomic| liz_forms::kit_write(&slf.desk, &path, atomic)),
+            )
         });
 
         // Gather Methods
@@ -0,0 +1,531 @@
+use rlua::{Context, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+use similar::TextDiff;
+
+use crate::liz_files;
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone)]
+pub struct DiffSpec {
+  pub context: usize,
+  pub old_name: String,
+  pub new_name: String,
+}
+
+pub fn diff_spec(
+  opts: Option<Table>,
+  old_name: &str,
+  new_name: &str,
+) -> Result<DiffSpec, LizError> {
+  rux_dbg_call!(old_name, new_name);
+  let mut result = DiffSpec {
+    context: 3,
+    old_name: old_name.into(),
+    new_name: new_name.into(),
+  };
+  if let Some(opts) = opts {
+    if let Some(context) = opts.get("context").map_err(|err| rux_dbg_erro!(err))? {
+      result.context = context;
+    }
+    if let Some(old_name) = opts.get("old_name").map_err(|err| rux_dbg_erro!(err))? {
+      result.old_name = old_name;
+    }
+    if let Some(new_name) = opts.get("new_name").map_err(|err| rux_dbg_erro!(err))? {
+      result.new_name = new_name;
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn diff(old_text: &str, new_text: &str, spec: &DiffSpec) -> String {
+  rux_dbg_call!(spec);
+  let result = TextDiff::from_lines(old_text, new_text)
+    .unified_diff()
+    .context_radius(spec.context)
+    .header(&spec.old_name, &spec.new_name)
+    .to_string();
+  rux_dbg_reav!(result);
+}
+
+pub fn diff_files(
+  lane: Context,
+  old_path: &str,
+  new_path: &str,
+  spec: &DiffSpec,
+) -> Result<String, LizError> {
+  rux_dbg_call!(old_path, new_path);
+  let old_full = utils::get_stacked_path(lane, old_path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(old_full);
+  let new_full = utils::get_stacked_path(lane, new_path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(new_full);
+  let old_text =
+    get_file_text(&old_full, is_dev_null(&spec.old_name)).map_err(|err| rux_dbg_bleb!(err))?;
+  let new_text =
+    get_file_text(&new_full, is_dev_null(&spec.new_name)).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(diff(&old_text, &new_text, spec)));
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchSpec {
+  pub fuzz: usize,
+  pub dry_run: bool,
+}
+
+pub fn patch_spec(opts: Option<Table>) -> Result<PatchSpec, LizError> {
+  rux_dbg_call!();
+  let mut result = PatchSpec {
+    fuzz: 2,
+    dry_run: false,
+  };
+  if let Some(opts) = opts {
+    if let Some(fuzz) = opts.get("fuzz").map_err(|err| rux_dbg_erro!(err))? {
+      result.fuzz = fuzz;
+    }
+    if let Some(dry_run) = opts.get("dry_run").map_err(|err| rux_dbg_erro!(err))? {
+      result.dry_run = dry_run;
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn patch<'a>(
+  lane: Context<'a>,
+  path: &str,
+  diff_text: &str,
+  spec: &PatchSpec,
+) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(path, spec);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let (names, hunks) = get_hunks(diff_text).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(names);
+  if names.len() > 1 {
+    return Err(rux_dbg_erro!(format!(
+      "The diff names {} files, patch applies only one: {}",
+      names.len(),
+      names.join(", ")
+    )));
+  }
+  let creates = names.first().map(|name| is_dev_null(name)).unwrap_or(false);
+  let source = get_file_text(&path, creates).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut lines: Vec<String> = source.split_inclusive('\n').map(String::from).collect();
+  let done = apply_hunks(&mut lines, &hunks, spec.fuzz);
+  if done.applied > 0 && !spec.dry_run {
+    liz_files::write_atomic(&path, lines.concat().as_bytes())
+      .map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("ok", done.rejected.is_empty())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("applied", done.applied)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("fuzzed", done.fuzzed)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("skipped", done.skipped)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("rejected", done.rejected)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+#[derive(Debug, Clone)]
+struct Hunk {
+  old_start: usize,
+  old_lines: Vec<String>,
+  new_lines: Vec<String>,
+  leading: usize,
+  trailing: usize,
+  text: String,
+}
+
+// The names are the old sides of the file headers, a new file has /dev/null.
+fn get_hunks(diff_text: &str) -> Result<(Vec<String>, Vec<Hunk>), LizError> {
+  rux_dbg_call!();
+  let mut names: Vec<String> = Vec::new();
+  let mut result: Vec<Hunk> = Vec::new();
+  let mut current: Option<Hunk> = None;
+  let mut old_left = 0;
+  let mut new_left = 0;
+  let mut last_side = ' ';
+  for (index, line) in diff_text.split_inclusive('\n').enumerate() {
+    if line.starts_with("@@") {
+      if let Some(hunk) = current.take() {
+        result.push(get_hunk_edges(hunk));
+      }
+      let (old_start, old_count, new_count) = get_hunk_header(line).ok_or_else(|| {
+        rux_dbg_erro!(format!(
+          "Invalid hunk header on line {}: {}",
+          index + 1,
+          line.trim_end()
+        ))
+      })?;
+      old_left = old_count;
+      new_left = new_count;
+      current = Some(Hunk {
+        old_start,
+        old_lines: Vec::new(),
+        new_lines: Vec::new(),
+        leading: 0,
+        trailing: 0,
+        text: line.into(),
+      });
+      continue;
+    }
+    let outside = current.is_none() || (old_left == 0 && new_left == 0);
+    if outside && line.starts_with("--- ") {
+      if let Some(hunk) = current.take() {
+        result.push(get_hunk_edges(hunk));
+      }
+      names.push(get_header_name(&line[4..]));
+      continue;
+    }
+    let hunk = match current.as_mut() {
+      Some(hunk) => hunk,
+      None => continue,
+    };
+    if line.starts_with('\\') {
+      if last_side != '+' {
+        put_no_newline(&mut hunk.old_lines);
+      }
+      if last_side != '-' {
+        put_no_newline(&mut hunk.new_lines);
+      }
+      hunk.text.push_str(line);
+      continue;
+    }
+    if old_left == 0 && new_left == 0 {
+      result.push(get_hunk_edges(current.take().unwrap()));
+      continue;
+    }
+    let (side, body) = match line.chars().next() {
+      Some(side @ (' ' | '-' | '+')) => (side, &line[1..]),
+      Some('\n') | Some('\r') => (' ', line),
+      _ => {
+        return Err(rux_dbg_erro!(format!(
+          "Invalid hunk line {}: {}",
+          index + 1,
+          line.trim_end()
+        )))
+      }
+    };
+    if side != '+' {
+      hunk.old_lines.push(body.into());
+      old_left = old_left.saturating_sub(1);
+    }
+    if side != '-' {
+      hunk.new_lines.push(body.into());
+      new_left = new_left.saturating_sub(1);
+    }
+    hunk.text.push_str(line);
+    last_side = side;
+  }
+  if let Some(hunk) = current.take() {
+    result.push(get_hunk_edges(hunk));
+  }
+  rux_dbg_reav!(Ok((names, result)));
+}
+
+fn get_header_name(header: &str) -> String {
+  header.split('\t').next().unwrap_or("").trim().into()
+}
+
+fn is_dev_null(name: &str) -> bool {
+  name == "/dev/null"
+}
+
+fn get_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+  let mut parts = header.trim_start_matches('@').trim().split(' ');
+  let old = parts.next()?.strip_prefix('-')?;
+  let new = parts.next()?.strip_prefix('+')?;
+  let (old_start, old_count) = get_hunk_range(old)?;
+  let (_, new_count) = get_hunk_range(new)?;
+  // An empty old range points to the line before the change.
+  let old_start = if old_count == 0 {
+    old_start + 1
+  } else {
+    old_start
+  };
+  Some((old_start, old_count, new_count))
+}
+
+fn get_hunk_range(range: &str) -> Option<(usize, usize)> {
+  let mut parts = range.split(',');
+  let start = parts.next()?.parse::<usize>().ok()?;
+  let count = match parts.next() {
+    Some(count) => count.parse::<usize>().ok()?,
+    None => 1,
+  };
+  Some((start, count))
+}
+
+fn get_hunk_edges(mut hunk: Hunk) -> Hunk {
+  let shared = hunk
+    .old_lines
+    .iter()
+    .zip(hunk.new_lines.iter())
+    .take_while(|(old, new)| old == new)
+    .count();
+  hunk.leading = shared;
+  let shared = hunk
+    .old_lines
+    .iter()
+    .rev()
+    .zip(hunk.new_lines.iter().rev())
+    .take_while(|(old, new)| old == new)
+    .count();
+  hunk.trailing = shared;
+  hunk
+}
+
+fn put_no_newline(lines: &mut [String]) {
+  if let Some(last) = lines.last_mut() {
+    if last.ends_with('\n') {
+      last.pop();
+      if last.ends_with('\r') {
+        last.pop();
+      }
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct PatchDone {
+  applied: usize,
+  fuzzed: usize,
+  skipped: usize,
+  rejected: Vec<String>,
+}
+
+fn apply_hunks(lines: &mut Vec<String>, hunks: &[Hunk], fuzz: usize) -> PatchDone {
+  let mut result = PatchDone::default();
+  let mut offset: isize = 0;
+  let mut floor = 0;
+  for hunk in hunks {
+    if let Some(start) = find_applied(lines, hunk, offset, floor) {
+      rux_dbg_tell!(hunk.old_start, start, "skipped");
+      result.skipped += 1;
+      offset = start as isize - (hunk.old_start as isize - 1);
+      floor = start + hunk.new_lines.len();
+      continue;
+    }
+    match apply_hunk(lines, hunk, offset, floor, fuzz) {
+      Some((start, fuzz)) => {
+        rux_dbg_tell!(hunk.old_start, start, fuzz);
+        result.applied += 1;
+        if fuzz > 0 {
+          result.fuzzed += 1;
+        }
+        offset = start as isize - (hunk.old_start as isize - 1) + hunk.new_lines.len() as isize
+          - hunk.old_lines.len() as isize;
+        floor = start + hunk.new_lines.len() - fuzz.min(hunk.new_lines.len());
+      }
+      None => {
+        rux_dbg_tell!(hunk.old_start, "rejected");
+        result.rejected.push(hunk.text.clone());
+      }
+    }
+  }
+  result
+}
+
+// A hunk is taken as already applied when its new side is found and its old side
+// is either missing or found at the same place, as on a hunk that only appends.
+fn find_applied(lines: &[String], hunk: &Hunk, offset: isize, floor: usize) -> Option<usize> {
+  if hunk.old_lines == hunk.new_lines {
+    return None;
+  }
+  let expected = (hunk.old_start as isize - 1 + offset).max(0) as usize;
+  let new_at = find_hunk(lines, &hunk.new_lines, expected, floor)?;
+  match find_hunk(lines, &hunk.old_lines, expected, floor) {
+    Some(old_at) if old_at != new_at => None,
+    _ => Some(new_at),
+  }
+}
+
+fn apply_hunk(
+  lines: &mut Vec<String>,
+  hunk: &Hunk,
+  offset: isize,
+  floor: usize,
+  fuzz: usize,
+) -> Option<(usize, usize)> {
+  for level in 0..=fuzz {
+    let front = level.min(hunk.leading);
+    let back = level.min(hunk.trailing);
+    if front + back > hunk.old_lines.len() || (level > 0 && front + back == 0) {
+      continue;
+    }
+    let old_lines = &hunk.old_lines[front..hunk.old_lines.len() - back];
+    let new_lines = &hunk.new_lines[front..hunk.new_lines.len() - back];
+    let expected = (hunk.old_start as isize - 1 + offset + front as isize).max(0) as usize;
+    if let Some(start) = find_hunk(lines, old_lines, expected, floor) {
+      lines.splice(start..start + old_lines.len(), new_lines.iter().cloned());
+      return Some((start - front.min(start), level));
+    }
+  }
+  None
+}
+
+fn find_hunk(
+  lines: &[String],
+  old_lines: &[String],
+  expected: usize,
+  floor: usize,
+) -> Option<usize> {
+  if old_lines.len() > lines.len() {
+    return None;
+  }
+  let last = lines.len() - old_lines.len();
+  let matches = |start: usize| lines[start..start + old_lines.len()] == *old_lines;
+  let expected = expected.min(last);
+  for distance in 0..=last {
+    let after = expected + distance;
+    if after <= last && after >= floor && matches(after) {
+      return Some(after);
+    }
+    if distance <= expected {
+      let before = expected - distance;
+      if before >= floor && distance > 0 && matches(before) {
+        return Some(before);
+      }
+    }
+    if after > last && distance > expected {
+      break;
+    }
+  }
+  None
+}
+
+fn get_file_text(path: &str, can_miss: bool) -> Result<String, LizError> {
+  if can_miss && !std::path::Path::new(path).exists() {
+    return Ok(String::new());
+  }
+  std::fs::read_to_string(path).map_err(|err| rux_dbg_erro!(err, path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn get_spec(context: usize) -> DiffSpec {
+    DiffSpec {
+      context,
+      old_name: "a/file".into(),
+      new_name: "b/file".into(),
+    }
+  }
+
+  fn get_numbered(count: usize) -> String {
+    (1..=count).map(|number| format!("{}\n", number)).collect()
+  }
+
+  fn get_patched(source: &str, diff_text: &str, fuzz: usize) -> (String, PatchDone) {
+    let (_, hunks) = get_hunks(diff_text).unwrap();
+    let mut lines: Vec<String> = source.split_inclusive('\n').map(String::from).collect();
+    let done = apply_hunks(&mut lines, &hunks, fuzz);
+    (lines.concat(), done)
+  }
+
+  #[test]
+  fn diff_makes_unified_hunks() {
+    let result = diff("a\nb\n", "a\nc\n", &get_spec(3));
+    assert_eq!(
+      result,
+      "--- a/file\n+++ b/file\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+    );
+    assert_eq!(diff("same\n", "same\n", &get_spec(3)), "");
+  }
+
+  #[test]
+  fn patch_applies_its_diff_and_skips_it_after() {
+    let old = get_numbered(20);
+    let new = old
+      .replace("\n3\n", "\nthree\n")
+      .replace("\n12\n", "\ntwelve\n")
+      + "21\n";
+    let diff_text = diff(&old, &new, &get_spec(3));
+    let (patched, done) = get_patched(&old, &diff_text, 0);
+    assert_eq!(patched, new);
+    assert_eq!((done.applied, done.fuzzed, done.skipped), (3, 0, 0));
+    assert!(done.rejected.is_empty());
+    let (again, done) = get_patched(&patched, &diff_text, 0);
+    assert_eq!(again, new);
+    assert_eq!((done.applied, done.skipped), (0, 3));
+  }
+
+  #[test]
+  fn patch_follows_moved_lines() {
+    let old = get_numbered(10);
+    let new = old.replace("5\n", "five\n");
+    let diff_text = diff(&old, &new, &get_spec(3));
+    let source = format!("a\nb\nc\n{}", old);
+    let (patched, done) = get_patched(&source, &diff_text, 0);
+    assert_eq!(patched, format!("a\nb\nc\n{}", new));
+    assert_eq!((done.applied, done.fuzzed), (1, 0));
+  }
+
+  #[test]
+  fn patch_fuzzes_changed_context_or_rejects() {
+    let old = get_numbered(10);
+    let new = old.replace("5\n", "five\n");
+    let diff_text = diff(&old, &new, &get_spec(3));
+    let source = old.replace("2\n", "two\n");
+    let (patched, done) = get_patched(&source, &diff_text, 0);
+    assert_eq!(patched, source);
+    assert_eq!(done.applied, 0);
+    assert_eq!(done.rejected.len(), 1);
+    assert!(done.rejected[0].starts_with("@@ -2,7 +2,7 @@\n"));
+    let (patched, done) = get_patched(&source, &diff_text, 2);
+    assert_eq!(patched, source.replace("5\n", "five\n"));
+    assert_eq!((done.applied, done.fuzzed), (1, 1));
+  }
+
+  #[test]
+  fn patch_keeps_missing_final_newlines() {
+    let diff_text = diff("a\nb", "a\nc", &get_spec(3));
+    assert!(diff_text.contains("\\ No newline at end of file"));
+    assert_eq!(get_patched("a\nb", &diff_text, 0).0, "a\nc");
+    let diff_text = diff("a\nb", "a\nb\n", &get_spec(3));
+    assert_eq!(get_patched("a\nb", &diff_text, 0).0, "a\nb\n");
+  }
+
+  #[test]
+  fn patch_creates_new_files() {
+    let spec = DiffSpec {
+      context: 3,
+      old_name: "/dev/null".into(),
+      new_name: "b/file".into(),
+    };
+    let diff_text = diff("", "x\ny\n", &spec);
+    let (names, _) = get_hunks(&diff_text).unwrap();
+    assert_eq!(names, vec!["/dev/null"]);
+    assert!(is_dev_null(&names[0]));
+    assert_eq!(get_patched("", &diff_text, 0).0, "x\ny\n");
+  }
+
+  #[test]
+  fn hunks_parse_headers_and_names() {
+    assert_eq!(get_hunk_header("@@ -3,4 +3,5 @@ fn main"), Some((3, 4, 5)));
+    assert_eq!(get_hunk_header("@@ -7 +7 @@"), Some((7, 1, 1)));
+    assert_eq!(get_hunk_header("@@ -0,0 +1,2 @@"), Some((1, 0, 2)));
+    assert_eq!(get_hunk_header("@@ -x +1 @@"), None);
+    assert!(get_hunks("@@ -x +1 @@\n").is_err());
+    assert!(get_hunks("@@ -1 +1 @@\n?a\n").is_err());
+    let two = format!(
+      "{}{}",
+      diff("a\n", "b\n", &get_spec(3)),
+      diff("c\n", "d\n", &get_spec(3))
+    );
+    let (names, hunks) = get_hunks(&two).unwrap();
+    assert_eq!(names, vec!["a/file", "a/file"]);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(get_header_name("a/file\t2024-01-01 10:00"), "a/file");
+  }
+}
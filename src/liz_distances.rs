@@ -0,0 +1,242 @@
+use rlua::{Context, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
+
+use crate::LizError;
+
+pub const SIMILAR_THRESHOLD: f64 = 0.85;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distance {
+  Edits(usize),
+  Ratio(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+  Levenshtein,
+  Damerau,
+  Jaro,
+  JaroWinkler,
+}
+
+impl Metric {
+  pub fn new(algo: &str) -> Result<Metric, LizError> {
+    match algo.trim().to_lowercase().replace('-', "_").as_str() {
+      "levenshtein" => Ok(Metric::Levenshtein),
+      "damerau" | "damerau_levenshtein" => Ok(Metric::Damerau),
+      "jaro" => Ok(Metric::Jaro),
+      "jaro_winkler" => Ok(Metric::JaroWinkler),
+      _ => Err(rux_dbg_erro!(format!(
+        "Unknown distance algorithm: {}",
+        algo
+      ))),
+    }
+  }
+}
+
+pub fn text_distance(text: &str, with: &str, algo: &str) -> Result<Distance, LizError> {
+  rux_dbg_call!(text, with, algo);
+  let result = match Metric::new(algo).map_err(|err| rux_dbg_bleb!(err))? {
+    Metric::Levenshtein => Distance::Edits(strsim::levenshtein(text, with)),
+    Metric::Damerau => Distance::Edits(strsim::damerau_levenshtein(text, with)),
+    Metric::Jaro => Distance::Ratio(1.0 - strsim::jaro(text, with)),
+    Metric::JaroWinkler => Distance::Ratio(1.0 - strsim::jaro_winkler(text, with)),
+  };
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn text_similarity(text: &str, with: &str, algo: &str) -> Result<f64, LizError> {
+  rux_dbg_call!(text, with, algo);
+  let metric = Metric::new(algo).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(get_similarity(metric, text, with)));
+}
+
+pub fn is_similar(text: &str, with: &str, threshold: f64) -> bool {
+  rux_dbg_call!(text, with, threshold);
+  rux_dbg_reav!(get_likely_score(&get_likely(text), &get_likely(with)) >= threshold);
+}
+
+pub fn fuzzy_find<'a>(
+  lane: Context<'a>,
+  query: &str,
+  candidates: Vec<String>,
+  limit: Option<usize>,
+  threshold: f64,
+) -> Result<Vec<Table<'a>>, LizError> {
+  rux_dbg_call!(query, candidates.len(), limit, threshold);
+  let query = get_likely(query);
+  let mut scored: Vec<(usize, String, f64)> = candidates
+    .into_iter()
+    .enumerate()
+    .map(|(index, candidate)| {
+      let score = get_fuzzy_score(&query, &get_likely(&candidate));
+      (index, candidate, score)
+    })
+    .filter(|(_, _, score)| *score >= threshold)
+    .collect();
+  scored.sort_by(|left, right| right.2.total_cmp(&left.2).then(left.0.cmp(&right.0)));
+  if let Some(limit) = limit {
+    if limit > 0 {
+      scored.truncate(limit);
+    }
+  }
+  rux_dbg_step!(scored);
+  let mut result = Vec::with_capacity(scored.len());
+  for (index, text, score) in scored {
+    let found = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+    found
+      .set("index", index + 1)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    found.set("text", text).map_err(|err| rux_dbg_erro!(err))?;
+    found
+      .set("score", score)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result.push(found);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_similarity(metric: Metric, text: &str, with: &str) -> f64 {
+  match metric {
+    Metric::Levenshtein => strsim::normalized_levenshtein(text, with),
+    Metric::Damerau => strsim::normalized_damerau_levenshtein(text, with),
+    Metric::Jaro => strsim::jaro(text, with),
+    Metric::JaroWinkler => strsim::jaro_winkler(text, with),
+  }
+}
+
+fn get_likely_score(text: &str, with: &str) -> f64 {
+  get_similarity(Metric::JaroWinkler, text, with)
+}
+
+// A short query is far from a long candidate on the whole text distance, so the
+// candidates that have the query as a prefix, a part or a subsequence go first,
+// the shorter ones before, and the others only by their likely score.
+fn get_fuzzy_score(query: &str, candidate: &str) -> f64 {
+  let likely = get_likely_score(query, candidate);
+  let query_len = query.chars().count();
+  let candidate_len = candidate.chars().count();
+  if query_len == 0 || query_len > candidate_len {
+    return likely;
+  }
+  let ratio = query_len as f64 / candidate_len as f64;
+  let bonus = if candidate.starts_with(query) {
+    0.95 + 0.05 * ratio
+  } else if candidate.contains(query) {
+    0.9 + 0.05 * ratio
+  } else if is_subsequence(query, candidate) {
+    0.85 + 0.05 * ratio
+  } else {
+    0.0
+  };
+  likely.max(bonus)
+}
+
+fn is_subsequence(query: &str, candidate: &str) -> bool {
+  let mut candidate = candidate.chars();
+  query
+    .chars()
+    .all(|wanted| candidate.any(|found| found == wanted))
+}
+
+fn get_likely(text: &str) -> String {
+  text
+    .trim()
+    .to_lowercase()
+    .chars()
+    .map(get_unaccented)
+    .collect()
+}
+
+fn get_unaccented(ch: char) -> char {
+  match ch {
+    'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+    'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+    'ď' | 'đ' => 'd',
+    'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+    'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+    'ĥ' | 'ħ' => 'h',
+    'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+    'ĵ' => 'j',
+    'ķ' => 'k',
+    'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+    'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+    'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+    'ŕ' | 'ŗ' | 'ř' => 'r',
+    'ś' | 'ŝ' | 'ş' | 'š' => 's',
+    'ţ' | 'ť' | 'ŧ' => 't',
+    'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+    'ŵ' => 'w',
+    'ý' | 'ÿ' | 'ŷ' => 'y',
+    'ź' | 'ż' | 'ž' => 'z',
+    _ => ch,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn distances_count_edits_and_ratios() {
+    assert_eq!(
+      text_distance("kitten", "sitting", "levenshtein").unwrap(),
+      Distance::Edits(3)
+    );
+    assert_eq!(
+      text_distance("ab", "ba", "levenshtein").unwrap(),
+      Distance::Edits(2)
+    );
+    assert_eq!(
+      text_distance("ab", "ba", "damerau").unwrap(),
+      Distance::Edits(1)
+    );
+    assert_eq!(
+      text_distance("same", "same", "jaro-winkler").unwrap(),
+      Distance::Ratio(0.0)
+    );
+    assert!(text_distance("a", "b", "soundex").is_err());
+  }
+
+  #[test]
+  fn similarity_ignores_case_and_accents() {
+    assert!(is_similar("Café", "cafe", SIMILAR_THRESHOLD));
+    assert!(is_similar("  Ação ", "acao", SIMILAR_THRESHOLD));
+    assert!(!is_similar("apple", "orange", SIMILAR_THRESHOLD));
+    assert_eq!(get_likely("  ÁRVORE Ñu "), "arvore nu");
+  }
+
+  #[test]
+  fn fuzzy_score_ranks_prefix_part_and_subsequence() {
+    let prefix = get_fuzzy_score("con", "config");
+    let part = get_fuzzy_score("fig", "config");
+    let subsequence = get_fuzzy_score("cfg", "config");
+    let unrelated = get_fuzzy_score("xyz", "config");
+    assert!((prefix - 0.975).abs() < 1e-9);
+    assert!((part - 0.925).abs() < 1e-9);
+    assert!((subsequence - 0.875).abs() < 1e-9);
+    assert!(prefix > part && part > subsequence && subsequence > unrelated);
+    assert!(unrelated < 0.5);
+  }
+
+  #[test]
+  fn fuzzy_score_prefers_shorter_candidates() {
+    assert!(get_fuzzy_score("con", "config") > get_fuzzy_score("con", "configuration"));
+    assert_eq!(
+      get_fuzzy_score("", "config"),
+      get_likely_score("", "config")
+    );
+    assert_eq!(
+      get_fuzzy_score("configuration", "config"),
+      get_likely_score("configuration", "config")
+    );
+  }
+
+  #[test]
+  fn subsequence_keeps_the_order() {
+    assert!(is_subsequence("cfg", "config"));
+    assert!(!is_subsequence("gfc", "config"));
+    assert!(is_subsequence("", "config"));
+  }
+}
@@ -0,0 +1,415 @@
+use regex::Regex;
+use rlua::{Context, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use crate::liz_files;
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone)]
+pub enum EditOp {
+  Replace {
+    pattern: Regex,
+    with: String,
+    limit: usize,
+  },
+  InsertBefore {
+    pattern: Regex,
+    line: String,
+    limit: usize,
+  },
+  InsertAfter {
+    pattern: Regex,
+    line: String,
+    limit: usize,
+  },
+  Delete {
+    pattern: Regex,
+    limit: usize,
+  },
+  ReplaceBlock {
+    start: String,
+    finish: String,
+    with: String,
+  },
+  EnsureLine {
+    line: String,
+    after: Option<Regex>,
+  },
+}
+
+pub fn edit_ops(ops: Vec<Table>) -> Result<Vec<EditOp>, LizError> {
+  rux_dbg_call!(ops.len());
+  let mut result = Vec::with_capacity(ops.len());
+  for (index, op) in ops.into_iter().enumerate() {
+    let kind: String = op
+      .get("op")
+      .map_err(|err| rux_dbg_erro!(format!("On the edit op {}: {}", index + 1, err)))?;
+    rux_dbg_tell!(index, kind);
+    let limit = op
+      .get::<_, Option<usize>>("limit")
+      .map_err(|err| rux_dbg_erro!(err))?
+      .unwrap_or(0);
+    let edit = match kind.as_str() {
+      "replace" => EditOp::Replace {
+        pattern: get_op_regex(&op, "pattern").map_err(|err| rux_dbg_bleb!(err))?,
+        with: get_op_text(&op, "with").map_err(|err| rux_dbg_bleb!(err))?,
+        limit,
+      },
+      "insert_before" => EditOp::InsertBefore {
+        pattern: get_op_regex(&op, "pattern").map_err(|err| rux_dbg_bleb!(err))?,
+        line: get_op_text(&op, "line").map_err(|err| rux_dbg_bleb!(err))?,
+        limit,
+      },
+      "insert_after" => EditOp::InsertAfter {
+        pattern: get_op_regex(&op, "pattern").map_err(|err| rux_dbg_bleb!(err))?,
+        line: get_op_text(&op, "line").map_err(|err| rux_dbg_bleb!(err))?,
+        limit,
+      },
+      "delete" => EditOp::Delete {
+        pattern: get_op_regex(&op, "pattern").map_err(|err| rux_dbg_bleb!(err))?,
+        limit,
+      },
+      "replace_block" => EditOp::ReplaceBlock {
+        start: get_op_text(&op, "start").map_err(|err| rux_dbg_bleb!(err))?,
+        finish: get_op_text(&op, "finish").map_err(|err| rux_dbg_bleb!(err))?,
+        with: get_op_text(&op, "with").map_err(|err| rux_dbg_bleb!(err))?,
+      },
+      "ensure_line" => EditOp::EnsureLine {
+        line: get_op_text(&op, "line").map_err(|err| rux_dbg_bleb!(err))?,
+        after: match op
+          .get::<_, Option<String>>("after")
+          .map_err(|err| rux_dbg_erro!(err))?
+        {
+          Some(after) => Some(Regex::new(&after).map_err(|err| rux_dbg_erro!(err, after))?),
+          None => None,
+        },
+      },
+      _ => {
+        return Err(rux_dbg_erro!(format!(
+          "Unknown edit op {} on the position {}",
+          kind,
+          index + 1
+        )))
+      }
+    };
+    result.push(edit);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn edit_file(lane: Context, path: &str, ops: &[EditOp]) -> Result<usize, LizError> {
+  rux_dbg_call!(path);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let source = std::fs::read_to_string(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let (edited, changes) = edit_text(&source, ops);
+  rux_dbg_step!(changes);
+  if changes > 0 && edited != source {
+    liz_files::write_atomic(&path, edited.as_bytes()).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  rux_dbg_reav!(Ok(changes));
+}
+
+pub fn edit_text(source: &str, ops: &[EditOp]) -> (String, usize) {
+  let newline = if source.contains("\r\n") {
+    "\r\n"
+  } else {
+    "\n"
+  };
+  let mut text = source.to_string();
+  let mut changes = 0;
+  for op in ops {
+    let (edited, changed) = match op {
+      EditOp::Replace {
+        pattern,
+        with,
+        limit,
+      } => {
+        let found = pattern.find_iter(&text).count();
+        let found = if *limit > 0 { found.min(*limit) } else { found };
+        (
+          pattern.replacen(&text, *limit, with.as_str()).to_string(),
+          found,
+        )
+      }
+      EditOp::InsertBefore {
+        pattern,
+        line,
+        limit,
+      } => put_edit_lines(&text, |lines| {
+        insert_near(lines, pattern, line, *limit, newline, false)
+      }),
+      EditOp::InsertAfter {
+        pattern,
+        line,
+        limit,
+      } => put_edit_lines(&text, |lines| {
+        insert_near(lines, pattern, line, *limit, newline, true)
+      }),
+      EditOp::Delete { pattern, limit } => put_edit_lines(&text, |lines| {
+        let mut deleted = 0;
+        lines.retain(|actual| {
+          if (*limit == 0 || deleted < *limit) && pattern.is_match(get_line_body(actual)) {
+            deleted += 1;
+            false
+          } else {
+            true
+          }
+        });
+        deleted
+      }),
+      EditOp::ReplaceBlock {
+        start,
+        finish,
+        with,
+      } => put_edit_lines(&text, |lines| {
+        replace_block(lines, start, finish, with, newline)
+      }),
+      EditOp::EnsureLine { line, after } => put_edit_lines(&text, |lines| {
+        if lines.iter().any(|actual| get_line_body(actual) == line) {
+          return 0;
+        }
+        let position = match after {
+          Some(after) => lines
+            .iter()
+            .position(|actual| after.is_match(get_line_body(actual)))
+            .map(|index| index + 1)
+            .unwrap_or(lines.len()),
+          None => lines.len(),
+        };
+        put_line_at(lines, position, line, newline);
+        1
+      }),
+    };
+    text = edited;
+    changes += changed;
+  }
+  (text, changes)
+}
+
+fn put_edit_lines(text: &str, edit: impl FnOnce(&mut Vec<String>) -> usize) -> (String, usize) {
+  let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+  let changed = edit(&mut lines);
+  (lines.concat(), changed)
+}
+
+fn insert_near(
+  lines: &mut Vec<String>,
+  pattern: &Regex,
+  line: &str,
+  limit: usize,
+  newline: &str,
+  after: bool,
+) -> usize {
+  let mut inserted = 0;
+  let mut index = 0;
+  while index < lines.len() {
+    if limit > 0 && inserted >= limit {
+      break;
+    }
+    if pattern.is_match(get_line_body(&lines[index])) {
+      let position = if after { index + 1 } else { index };
+      put_line_at(lines, position, line, newline);
+      inserted += 1;
+      index += 2;
+    } else {
+      index += 1;
+    }
+  }
+  inserted
+}
+
+fn replace_block(
+  lines: &mut Vec<String>,
+  start: &str,
+  finish: &str,
+  with: &str,
+  newline: &str,
+) -> usize {
+  let opening = match lines.iter().position(|line| line.contains(start)) {
+    Some(opening) => opening,
+    None => return 0,
+  };
+  let closing = match lines
+    .iter()
+    .skip(opening + 1)
+    .position(|line| line.contains(finish))
+  {
+    Some(closing) => opening + 1 + closing,
+    None => return 0,
+  };
+  let mut block: Vec<String> = Vec::new();
+  if !with.is_empty() {
+    for line in with.split_inclusive('\n') {
+      block.push(format!(
+        "{}{}",
+        line.trim_end_matches(['\r', '\n']),
+        newline
+      ));
+    }
+  }
+  if lines[opening + 1..closing] == block[..] {
+    return 0;
+  }
+  lines.splice(opening + 1..closing, block);
+  1
+}
+
+fn put_line_at(lines: &mut Vec<String>, position: usize, line: &str, newline: &str) {
+  let mut ending = newline;
+  if position > 0 && position == lines.len() && !lines[position - 1].ends_with('\n') {
+    lines[position - 1].push_str(newline);
+    ending = "";
+  }
+  lines.insert(position, format!("{}{}", line, ending));
+}
+
+fn get_line_body(line: &str) -> &str {
+  line.trim_end_matches(['\r', '\n'])
+}
+
+fn get_op_regex(op: &Table, name: &str) -> Result<Regex, LizError> {
+  let pattern = get_op_text(op, name).map_err(|err| rux_dbg_bleb!(err))?;
+  Regex::new(&pattern).map_err(|err| rux_dbg_erro!(err, pattern))
+}
+
+fn get_op_text(op: &Table, name: &str) -> Result<String, LizError> {
+  match op
+    .get::<_, Option<String>>(name)
+    .map_err(|err| rux_dbg_erro!(err))?
+  {
+    Some(text) => Ok(text),
+    None => Err(rux_dbg_erro!(format!(
+      "The edit op needs the {} field",
+      name
+    ))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_regex(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap()
+  }
+
+  fn get_edited(source: &str, op: EditOp) -> (String, usize) {
+    edit_text(source, &[op])
+  }
+
+  #[test]
+  fn replace_counts_up_to_the_limit() {
+    let op = EditOp::Replace {
+      pattern: get_regex("a(\\d)"),
+      with: "b$1".into(),
+      limit: 2,
+    };
+    assert_eq!(get_edited("a1 a2 a3\n", op), ("b1 b2 a3\n".into(), 2));
+    let op = EditOp::Replace {
+      pattern: get_regex("z"),
+      with: "y".into(),
+      limit: 0,
+    };
+    assert_eq!(get_edited("abc", op), ("abc".into(), 0));
+  }
+
+  #[test]
+  fn inserts_go_around_the_matched_lines() {
+    let after = EditOp::InsertAfter {
+      pattern: get_regex("^x$"),
+      line: "new".into(),
+      limit: 0,
+    };
+    assert_eq!(
+      get_edited("x\ny\nx\n", after),
+      ("x\nnew\ny\nx\nnew\n".into(), 2)
+    );
+    let before = EditOp::InsertBefore {
+      pattern: get_regex("^x$"),
+      line: "new".into(),
+      limit: 1,
+    };
+    assert_eq!(
+      get_edited("x\ny\nx\n", before),
+      ("new\nx\ny\nx\n".into(), 1)
+    );
+    let after = EditOp::InsertAfter {
+      pattern: get_regex("^a$"),
+      line: "n".into(),
+      limit: 0,
+    };
+    assert_eq!(
+      get_edited("a\r\nb\r\n", after.clone()),
+      ("a\r\nn\r\nb\r\n".into(), 1)
+    );
+    assert_eq!(get_edited("b\na", after), ("b\na\nn".into(), 1));
+  }
+
+  #[test]
+  fn delete_drops_the_matched_lines() {
+    let op = EditOp::Delete {
+      pattern: get_regex("^#"),
+      limit: 0,
+    };
+    assert_eq!(
+      get_edited("keep\n#drop\nkeep\n#drop", op),
+      ("keep\nkeep\n".into(), 2)
+    );
+  }
+
+  #[test]
+  fn block_replaces_between_its_markers_once() {
+    let op = EditOp::ReplaceBlock {
+      start: "# BEGIN".into(),
+      finish: "# END".into(),
+      with: "one\ntwo".into(),
+    };
+    let source = "start\n# BEGIN\nold\n# END\nend\n";
+    let expected = "start\n# BEGIN\none\ntwo\n# END\nend\n";
+    assert_eq!(get_edited(source, op.clone()), (expected.into(), 1));
+    assert_eq!(get_edited(expected, op.clone()), (expected.into(), 0));
+    assert_eq!(
+      get_edited("# BEGIN\nopen\n", op),
+      ("# BEGIN\nopen\n".into(), 0)
+    );
+  }
+
+  #[test]
+  fn ensure_line_adds_only_missing_lines() {
+    let op = EditOp::EnsureLine {
+      line: "c".into(),
+      after: None,
+    };
+    assert_eq!(get_edited("a\nb", op.clone()), ("a\nb\nc".into(), 1));
+    assert_eq!(get_edited("a\nc\nb\n", op), ("a\nc\nb\n".into(), 0));
+    let op = EditOp::EnsureLine {
+      line: "x".into(),
+      after: Some(get_regex("^a$")),
+    };
+    assert_eq!(get_edited("a\nb\n", op), ("a\nx\nb\n".into(), 1));
+  }
+
+  #[test]
+  fn ops_are_read_from_tables() {
+    Lua::new().context(|lane| {
+      let get_ops = |source: &str| {
+        let ops: Vec<Table> = lane.load(source).eval().unwrap();
+        edit_ops(ops)
+      };
+      let ops = get_ops(
+        "{ { op = 'replace', pattern = 'a', with = 'b', limit = 1 }, \
+        { op = 'ensure_line', line = 'x', after = '^y' } }",
+      )
+      .unwrap();
+      assert!(matches!(ops[0], EditOp::Replace { limit: 1, .. }));
+      assert!(matches!(ops[1], EditOp::EnsureLine { after: Some(_), .. }));
+      assert!(get_ops("{ { op = 'rename' } }").is_err());
+      assert!(get_ops("{ { op = 'delete' } }").is_err());
+      assert!(get_ops("{ { op = 'delete', pattern = '(' } }").is_err());
+    });
+  }
+}
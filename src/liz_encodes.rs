@@ -0,0 +1,365 @@
+use data_encoding::{Encoding, BASE32, BASE32_NOPAD, BASE64, BASE64URL, BASE64URL_NOPAD};
+use data_encoding::{BASE64_NOPAD, HEXLOWER, HEXLOWER_PERMISSIVE, HEXUPPER};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use rlua::Table;
+use rubx::rux_dbg_erro;
+use rubx::{rux_dbg_call, rux_dbg_reav};
+
+use crate::LizError;
+
+const PERCENT_SET: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+#[derive(Debug, Clone)]
+pub struct EncodeSpec {
+  pub padding: bool,
+  pub upper: bool,
+  pub form: bool,
+}
+
+pub fn encode_spec(opts: Option<Table>) -> Result<EncodeSpec, LizError> {
+  rux_dbg_call!();
+  let mut result = EncodeSpec {
+    padding: true,
+    upper: false,
+    form: false,
+  };
+  if let Some(opts) = opts {
+    if let Some(padding) = opts.get("padding").map_err(|err| rux_dbg_erro!(err))? {
+      result.padding = padding;
+    }
+    if let Some(upper) = opts.get("upper").map_err(|err| rux_dbg_erro!(err))? {
+      result.upper = upper;
+    }
+    if let Some(form) = opts.get("form").map_err(|err| rux_dbg_erro!(err))? {
+      result.form = form;
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn encode_base64(data: &[u8], spec: &EncodeSpec) -> String {
+  rux_dbg_call!(data.len());
+  let encoding = if spec.padding { &BASE64 } else { &BASE64_NOPAD };
+  rux_dbg_reav!(encoding.encode(data));
+}
+
+pub fn decode_base64(text: &str) -> Result<Vec<u8>, LizError> {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(decode_padded(text, "base64", &BASE64, &BASE64_NOPAD));
+}
+
+pub fn encode_base64_url(data: &[u8], spec: &EncodeSpec) -> String {
+  rux_dbg_call!(data.len());
+  let encoding = if spec.padding {
+    &BASE64URL
+  } else {
+    &BASE64URL_NOPAD
+  };
+  rux_dbg_reav!(encoding.encode(data));
+}
+
+pub fn decode_base64_url(text: &str) -> Result<Vec<u8>, LizError> {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(decode_padded(
+    text,
+    "base64 url",
+    &BASE64URL,
+    &BASE64URL_NOPAD
+  ));
+}
+
+pub fn encode_base32(data: &[u8], spec: &EncodeSpec) -> String {
+  rux_dbg_call!(data.len());
+  let encoding = if spec.padding { &BASE32 } else { &BASE32_NOPAD };
+  rux_dbg_reav!(encoding.encode(data));
+}
+
+pub fn decode_base32(text: &str) -> Result<Vec<u8>, LizError> {
+  rux_dbg_call!(text);
+  let text = text.to_uppercase();
+  rux_dbg_reav!(decode_padded(&text, "base32", &BASE32, &BASE32_NOPAD));
+}
+
+pub fn encode_hex(data: &[u8], spec: &EncodeSpec) -> String {
+  rux_dbg_call!(data.len());
+  let encoding = if spec.upper { &HEXUPPER } else { &HEXLOWER };
+  rux_dbg_reav!(encoding.encode(data));
+}
+
+pub fn decode_hex(text: &str) -> Result<Vec<u8>, LizError> {
+  rux_dbg_call!(text);
+  let text = get_unspaced(text);
+  rux_dbg_reav!(HEXLOWER_PERMISSIVE
+    .decode(text.as_bytes())
+    .map_err(|err| rux_dbg_erro!(format!("Could not decode the hex text: {}", err))));
+}
+
+pub fn encode_percent(data: &[u8], spec: &EncodeSpec) -> String {
+  rux_dbg_call!(data.len());
+  let result = percent_encoding::percent_encode(data, PERCENT_SET).to_string();
+  if spec.form {
+    rux_dbg_reav!(result.replace("%20", "+"));
+  }
+  rux_dbg_reav!(result);
+}
+
+pub fn decode_percent(text: &str, spec: &EncodeSpec) -> Result<Vec<u8>, LizError> {
+  rux_dbg_call!(text);
+  let bytes = text.as_bytes();
+  let mut result = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    match bytes[index] {
+      b'%' => {
+        let escape = bytes
+          .get(index + 1..index + 3)
+          .filter(|escape| escape.iter().all(|byte| byte.is_ascii_hexdigit()))
+          .and_then(|escape| std::str::from_utf8(escape).ok())
+          .and_then(|escape| u8::from_str_radix(escape, 16).ok());
+        match escape {
+          Some(byte) => result.push(byte),
+          None => {
+            return Err(rux_dbg_erro!(format!(
+              "Could not decode the percent text: invalid escape at {}",
+              index + 1
+            )))
+          }
+        }
+        index += 3;
+      }
+      b'+' if spec.form => {
+        result.push(b' ');
+        index += 1;
+      }
+      byte => {
+        result.push(byte);
+        index += 1;
+      }
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn encode_html(text: &str) -> String {
+  rux_dbg_call!(text);
+  let mut result = String::with_capacity(text.len());
+  for ch in text.chars() {
+    match ch {
+      '&' => result.push_str("&amp;"),
+      '<' => result.push_str("&lt;"),
+      '>' => result.push_str("&gt;"),
+      '"' => result.push_str("&quot;"),
+      '\'' => result.push_str("&#39;"),
+      _ => result.push(ch),
+    }
+  }
+  rux_dbg_reav!(result);
+}
+
+pub fn decode_html(text: &str) -> Result<String, LizError> {
+  rux_dbg_call!(text);
+  let mut result = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(found) = rest.find('&') {
+    result.push_str(&rest[..found]);
+    let after = &rest[found + 1..];
+    let name_len = after
+      .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '#'))
+      .unwrap_or(after.len());
+    if name_len == 0 || !after[name_len..].starts_with(';') {
+      result.push('&');
+      rest = after;
+      continue;
+    }
+    let name = &after[..name_len];
+    if let Some(number) = name.strip_prefix('#') {
+      let position = text.len() - rest.len() + found + 1;
+      let decoded = get_html_number(number).ok_or_else(|| {
+        rux_dbg_erro!(format!(
+          "Could not decode the html text: invalid entity &{}; at {}",
+          name, position
+        ))
+      })?;
+      result.push(decoded);
+    } else if let Some(decoded) = get_html_entity(name) {
+      result.push(decoded);
+    } else {
+      // Only the common named entities are known, the others are kept as is.
+      result.push('&');
+      result.push_str(name);
+      result.push(';');
+    }
+    rest = &after[name_len + 1..];
+  }
+  result.push_str(rest);
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn encode_json_string(text: &str) -> Result<String, LizError> {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(serde_json::to_string(text).map_err(|err| rux_dbg_erro!(err)));
+}
+
+pub fn decode_json_string(text: &str) -> Result<String, LizError> {
+  rux_dbg_call!(text);
+  let text = text.trim();
+  let quoted = if text.starts_with('"') {
+    text.to_string()
+  } else {
+    format!("\"{}\"", text)
+  };
+  rux_dbg_reav!(serde_json::from_str::<String>(&quoted)
+    .map_err(|err| rux_dbg_erro!(format!("Could not decode the json string: {}", err))));
+}
+
+fn decode_padded(
+  text: &str,
+  name: &str,
+  padded: &Encoding,
+  unpadded: &Encoding,
+) -> Result<Vec<u8>, LizError> {
+  let text = get_unspaced(text);
+  let encoding = if text.ends_with('=') {
+    padded
+  } else {
+    unpadded
+  };
+  encoding
+    .decode(text.as_bytes())
+    .map_err(|err| rux_dbg_erro!(format!("Could not decode the {} text: {}", name, err)))
+}
+
+fn get_unspaced(text: &str) -> String {
+  text.chars().filter(|ch| !ch.is_whitespace()).collect()
+}
+
+fn get_html_number(number: &str) -> Option<char> {
+  let code = match number.strip_prefix(['x', 'X']) {
+    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+    None => number.parse::<u32>().ok()?,
+  };
+  char::from_u32(code)
+}
+
+fn get_html_entity(name: &str) -> Option<char> {
+  let result = match name {
+    "amp" => '&',
+    "lt" => '<',
+    "gt" => '>',
+    "quot" => '"',
+    "apos" => '\'',
+    "nbsp" => '\u{a0}',
+    "copy" => '\u{a9}',
+    "reg" => '\u{ae}',
+    "trade" => '\u{2122}',
+    "hellip" => '\u{2026}',
+    "mdash" => '\u{2014}',
+    "ndash" => '\u{2013}',
+    "laquo" => '\u{ab}',
+    "raquo" => '\u{bb}',
+    "lsquo" => '\u{2018}',
+    "rsquo" => '\u{2019}',
+    "ldquo" => '\u{201c}',
+    "rdquo" => '\u{201d}',
+    "middot" => '\u{b7}',
+    "deg" => '\u{b0}',
+    "euro" => '\u{20ac}',
+    _ => return None,
+  };
+  Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn get_spec(padding: bool, upper: bool, form: bool) -> EncodeSpec {
+    EncodeSpec {
+      padding,
+      upper,
+      form,
+    }
+  }
+
+  #[test]
+  fn base_encodings_round_trip() {
+    let spec = get_spec(true, false, false);
+    let unpadded = get_spec(false, false, false);
+    assert_eq!(encode_base64(b"hello", &spec), "aGVsbG8=");
+    assert_eq!(encode_base64(b"hello", &unpadded), "aGVsbG8");
+    assert_eq!(decode_base64("aGVs\nbG8=").unwrap(), b"hello");
+    assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+    assert!(decode_base64("a$b=").is_err());
+    assert_eq!(encode_base64_url(&[0xfb, 0xff], &spec), "-_8=");
+    assert_eq!(decode_base64_url("-_8").unwrap(), vec![0xfb, 0xff]);
+    assert_eq!(encode_base32(b"hello", &spec), "NBSWY3DP");
+    assert_eq!(decode_base32("nbswy3dp").unwrap(), b"hello");
+    assert_eq!(encode_hex(&[0x01, 0xab], &spec), "01ab");
+    assert_eq!(
+      encode_hex(&[0x01, 0xab], &get_spec(true, true, false)),
+      "01AB"
+    );
+    assert_eq!(decode_hex("01 AB").unwrap(), vec![0x01, 0xab]);
+    assert!(decode_hex("0g").is_err());
+  }
+
+  #[test]
+  fn percent_encodes_and_decodes_forms() {
+    let spec = get_spec(true, false, false);
+    let form = get_spec(true, false, true);
+    assert_eq!(
+      encode_percent("a b/ç~".as_bytes(), &spec),
+      "a%20b%2F%C3%A7~"
+    );
+    assert_eq!(encode_percent("a b/ç~".as_bytes(), &form), "a+b%2F%C3%A7~");
+    assert_eq!(
+      decode_percent("a+b%2f%C3%A7", &spec).unwrap(),
+      "a+b/ç".as_bytes()
+    );
+    assert_eq!(
+      decode_percent("a+b%2f%C3%A7", &form).unwrap(),
+      "a b/ç".as_bytes()
+    );
+    assert!(decode_percent("100%", &spec).is_err());
+    assert!(decode_percent("%2", &spec).is_err());
+    assert!(decode_percent("%zz", &spec).is_err());
+  }
+
+  #[test]
+  fn html_escapes_round_trip() {
+    let text = "<a href=\"x\">Tom & 'Jerry'</a>";
+    let encoded = encode_html(text);
+    assert_eq!(
+      encoded,
+      "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;"
+    );
+    assert_eq!(decode_html(&encoded).unwrap(), text);
+  }
+
+  #[test]
+  fn html_decodes_numbers_and_keeps_unknown_entities() {
+    assert_eq!(
+      decode_html("&#65;&#x42;&#X43; &copy; &euro;").unwrap(),
+      "ABC \u{a9} \u{20ac}"
+    );
+    assert_eq!(
+      decode_html("&unknown; a & b &; &amp").unwrap(),
+      "&unknown; a & b &; &amp"
+    );
+    assert!(decode_html("&#xZZ;").is_err());
+    assert!(decode_html("&#xD800;").is_err());
+    assert!(decode_html("&#1114112;").is_err());
+  }
+
+  #[test]
+  fn json_strings_quote_and_unquote() {
+    assert_eq!(encode_json_string("a\"b\n").unwrap(), "\"a\\\"b\\n\"");
+    assert_eq!(decode_json_string("\"a\\\"b\\n\"").unwrap(), "a\"b\n");
+    assert_eq!(decode_json_string("a\\u00e7").unwrap(), "a\u{e7}");
+    assert!(decode_json_string("a\\q").is_err());
+  }
+}
@@ -0,0 +1,392 @@
+use rlua::{Context, Table, Value as LuaValue};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::utils;
+use crate::LizError;
+
+pub type LaneEnvs = HashMap<String, Option<String>>;
+
+pub fn env_get(lane: Context, name: &str) -> Result<Option<String>, LizError> {
+  rux_dbg_call!(name);
+  let lane_envs = get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(lane_envs_var(&lane_envs, name)));
+}
+
+pub fn env_set(lane: Context, name: &str, value: &str) -> Result<(), LizError> {
+  rux_dbg_call!(name, value);
+  check_env_name(name).map_err(|err| rux_dbg_bleb!(err))?;
+  let table = get_lane_envs_table(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  table.set(name, value).map_err(|err| rux_dbg_erro!(err))?;
+  Ok(())
+}
+
+pub fn env_unset(lane: Context, name: &str) -> Result<(), LizError> {
+  rux_dbg_call!(name);
+  check_env_name(name).map_err(|err| rux_dbg_bleb!(err))?;
+  let table = get_lane_envs_table(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  table.set(name, false).map_err(|err| rux_dbg_erro!(err))?;
+  Ok(())
+}
+
+pub fn env_all(lane: Context) -> Result<HashMap<String, String>, LizError> {
+  rux_dbg_call!();
+  let lane_envs = get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lane_envs);
+  let mut result: HashMap<String, String> = std::env::vars().collect();
+  for (name, value) in lane_envs {
+    match value {
+      Some(value) => result.insert(name, value),
+      None => result.remove(&name),
+    };
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn env_load(
+  lane: Context,
+  path: &str,
+  overwrite: bool,
+) -> Result<HashMap<String, String>, LizError> {
+  rux_dbg_call!(path, overwrite);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let source = std::fs::read_to_string(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let lane_envs = get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let loaded = env_parse(&source, |name| lane_envs_var(&lane_envs, name))
+    .map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(loaded);
+  let table = get_lane_envs_table(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut result = HashMap::new();
+  for (name, value) in loaded {
+    if !overwrite && lane_envs_var(&lane_envs, &name).is_some() {
+      rux_dbg_tell!(name);
+      continue;
+    }
+    table
+      .set(name.as_str(), value.as_str())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result.insert(name, value);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn env_parse<F: Fn(&str) -> Option<String>>(
+  source: &str,
+  lookup: F,
+) -> Result<Vec<(String, String)>, LizError> {
+  rux_dbg_call!(source);
+  let mut result: Vec<(String, String)> = Vec::new();
+  let chars: Vec<char> = source.chars().collect();
+  let mut index = 0;
+  let mut line = 1;
+  while index < chars.len() {
+    while index < chars.len() && chars[index].is_whitespace() {
+      if chars[index] == '\n' {
+        line += 1;
+      }
+      index += 1;
+    }
+    if index >= chars.len() {
+      break;
+    }
+    if chars[index] == '#' {
+      while index < chars.len() && chars[index] != '\n' {
+        index += 1;
+      }
+      continue;
+    }
+    let start = index;
+    while index < chars.len() && chars[index] != '=' && chars[index] != '\n' {
+      index += 1;
+    }
+    if index >= chars.len() || chars[index] != '=' {
+      return Err(rux_dbg_erro!(format!(
+        "Could not find the equals sign on the env line {}",
+        line
+      )));
+    }
+    let mut name: String = chars[start..index].iter().collect::<String>().trim().into();
+    if let Some(exported) = name.strip_prefix("export ") {
+      name = exported.trim().into();
+    }
+    check_env_name(&name).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(name);
+    index += 1;
+    while index < chars.len() && (chars[index] == ' ' || chars[index] == '\t') {
+      index += 1;
+    }
+    let lookup_on = |name: &str| -> Option<String> {
+      match result.iter().rev().find(|(loaded, _)| loaded == name) {
+        Some((_, value)) => Some(value.clone()),
+        None => lookup(name),
+      }
+    };
+    let mut value = String::new();
+    if index < chars.len() && (chars[index] == '"' || chars[index] == '\'') {
+      let quote = chars[index];
+      index += 1;
+      let mut raw = String::new();
+      let mut closed = false;
+      while index < chars.len() {
+        let actual = chars[index];
+        if actual == '\n' {
+          line += 1;
+        }
+        if actual == quote {
+          closed = true;
+          index += 1;
+          break;
+        }
+        if quote == '"' && actual == '\\' && index + 1 < chars.len() {
+          index += 1;
+          match chars[index] {
+            'n' => raw.push('\n'),
+            'r' => raw.push('\r'),
+            't' => raw.push('\t'),
+            '\\' => raw.push_str("\\\\"),
+            '$' => raw.push_str("\\$"),
+            other => raw.push(other),
+          }
+        } else {
+          raw.push(actual);
+        }
+        index += 1;
+      }
+      if !closed {
+        return Err(rux_dbg_erro!(format!(
+          "Could not find the closing quote on the env line {}",
+          line
+        )));
+      }
+      if quote == '"' {
+        value.push_str(&env_interpolate(&raw, true, &lookup_on));
+      } else {
+        value.push_str(&raw);
+      }
+      while index < chars.len() && chars[index] != '\n' {
+        index += 1;
+      }
+    } else {
+      let mut raw = String::new();
+      while index < chars.len() && chars[index] != '\n' {
+        if chars[index] == '#' && raw.ends_with(|ch: char| ch.is_whitespace()) {
+          while index < chars.len() && chars[index] != '\n' {
+            index += 1;
+          }
+          break;
+        }
+        raw.push(chars[index]);
+        index += 1;
+      }
+      value.push_str(&env_interpolate(raw.trim(), false, &lookup_on));
+    }
+    rux_dbg_tell!(value);
+    result.push((name, value));
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn env_interpolate<F: Fn(&str) -> Option<String>>(
+  source: &str,
+  escaped: bool,
+  lookup: &F,
+) -> String {
+  rux_dbg_call!(source, escaped);
+  let chars: Vec<char> = source.chars().collect();
+  let mut result = String::new();
+  let mut index = 0;
+  while index < chars.len() {
+    let actual = chars[index];
+    if actual == '\\' && index + 1 < chars.len() {
+      let next = chars[index + 1];
+      if next == '$' || (escaped && next == '\\') {
+        result.push(next);
+        index += 2;
+        continue;
+      }
+    }
+    if actual != '$' || index + 1 >= chars.len() {
+      result.push(actual);
+      index += 1;
+      continue;
+    }
+    if chars[index + 1] == '{' {
+      if let Some(close) = chars[index + 2..].iter().position(|ch| *ch == '}') {
+        let inner: String = chars[index + 2..index + 2 + close].iter().collect();
+        let (name, default) = match inner.find(":-") {
+          Some(split) => (&inner[..split], Some(&inner[split + 2..])),
+          None => (inner.as_str(), None),
+        };
+        match lookup(name) {
+          Some(found) if !found.is_empty() || default.is_none() => result.push_str(&found),
+          _ => result.push_str(default.unwrap_or("")),
+        }
+        index += close + 3;
+        continue;
+      }
+    } else if chars[index + 1] == '_' || chars[index + 1].is_ascii_alphabetic() {
+      let mut end = index + 1;
+      while end < chars.len() && (chars[end] == '_' || chars[end].is_ascii_alphanumeric()) {
+        end += 1;
+      }
+      let name: String = chars[index + 1..end].iter().collect();
+      result.push_str(&lookup(&name).unwrap_or_default());
+      index = end;
+      continue;
+    }
+    result.push(actual);
+    index += 1;
+  }
+  rux_dbg_reav!(result);
+}
+
+fn check_env_name(name: &str) -> Result<(), LizError> {
+  if name.is_empty() || name.contains('=') || name.contains('\0') {
+    return Err(rux_dbg_erro!(format!(
+      "Invalid environment variable name: '{}'",
+      name
+    )));
+  }
+  Ok(())
+}
+
+pub fn lane_envs_var(lane_envs: &LaneEnvs, name: &str) -> Option<String> {
+  match lane_envs.get(name) {
+    Some(value) => value.clone(),
+    None => std::env::var(name).ok(),
+  }
+}
+
+pub fn get_lane_envs(lane: Context) -> Result<LaneEnvs, LizError> {
+  rux_dbg_call!();
+  let table = get_lane_envs_table(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut result = LaneEnvs::new();
+  for pair in table.pairs::<String, LuaValue>() {
+    let (name, value) = pair.map_err(|err| rux_dbg_erro!(err))?;
+    match value {
+      LuaValue::String(value) => {
+        let value = value.to_str().map_err(|err| rux_dbg_erro!(err))?;
+        result.insert(name, Some(value.into()));
+      }
+      _ => {
+        result.insert(name, None);
+      }
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn put_lane_envs(lane: Context, lane_envs: &LaneEnvs) -> Result<(), LizError> {
+  rux_dbg_call!(lane_envs);
+  let table = get_lane_envs_table(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  for (name, value) in lane_envs {
+    match value {
+      Some(value) => table.set(name.as_str(), value.as_str()),
+      None => table.set(name.as_str(), false),
+    }
+    .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  Ok(())
+}
+
+pub fn apply_lane_envs(command: &mut Command, lane_envs: &LaneEnvs) {
+  rux_dbg_call!(lane_envs);
+  for (name, value) in lane_envs {
+    match value {
+      Some(value) => command.env(name, value),
+      None => command.env_remove(name),
+    };
+  }
+}
+
+fn get_lane_envs_table<'a>(lane: Context<'a>) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!();
+  let found: Option<Table> = lane
+    .named_registry_value("liz_lane_envs")
+    .map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(found) = found {
+    rux_dbg_reav!(Ok(found));
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  lane
+    .set_named_registry_value("liz_lane_envs", result.clone())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn get_parsed(source: &str) -> Vec<(String, String)> {
+    env_parse(source, |name| match name {
+      "HOME" => Some("/home/liz".into()),
+      "A" => Some("outer".into()),
+      "EMPTY" => Some(String::new()),
+      _ => None,
+    })
+    .unwrap()
+  }
+
+  fn get_pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn parse_reads_plain_and_quoted_values() {
+    let source = "# comment\nexport A=1\n  B = \"two words\" # trailing\nC='single $A'\n\
+      F=plain # comment\nG=a#b\n";
+    assert_eq!(
+      get_parsed(source),
+      get_pairs(&[
+        ("A", "1"),
+        ("B", "two words"),
+        ("C", "single $A"),
+        ("F", "plain"),
+        ("G", "a#b"),
+      ])
+    );
+  }
+
+  #[test]
+  fn parse_interpolates_loaded_and_outer_values() {
+    let source = "A=1\nD=\"line\\nnext $A ${HOME}\"\nE=${MISSING:-fallback}\n\
+      H=\"\\$A\"\nI=${EMPTY:-default}\nJ=$UNKNOWN.$A\n";
+    assert_eq!(
+      get_parsed(source),
+      get_pairs(&[
+        ("A", "1"),
+        ("D", "line\nnext 1 /home/liz"),
+        ("E", "fallback"),
+        ("H", "$A"),
+        ("I", "default"),
+        ("J", ".1"),
+      ])
+    );
+    assert_eq!(get_parsed("X=$A\n"), get_pairs(&[("X", "outer")]));
+  }
+
+  #[test]
+  fn parse_keeps_multiline_quotes() {
+    assert_eq!(
+      get_parsed("M=\"a\nb\"\nN=2\n"),
+      get_pairs(&[("M", "a\nb"), ("N", "2")])
+    );
+  }
+
+  #[test]
+  fn parse_rejects_broken_lines() {
+    let lookup = |_: &str| None;
+    let err = env_parse("A=1\nNOEQUALS\n", lookup).unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+    assert!(env_parse("A=\"open\n", lookup).is_err());
+    assert!(env_parse("=value\n", lookup).is_err());
+  }
+}
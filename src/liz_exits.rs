@@ -0,0 +1,542 @@
+use rlua::{Context, Function, HookTriggers, Lua, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+use crate::LizError;
+
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+static ABORT_SIGNAL: AtomicI32 = AtomicI32::new(0);
+static DEFERRED_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+// Pids of the children that must get the signal if liz is forced to quit, the
+// process groups are stored as negative numbers like libc::kill expects them.
+static TRACKED_CHILDREN: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+
+pub fn exit(lane: Context, code: i32) -> Result<(), LizError> {
+  rux_dbg_call!(code);
+  lane
+    .set_named_registry_value("liz_exit_code", code)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  Err(rux_dbg_erro!(format!("Liz exit with the code {}", code)))
+}
+
+pub fn get_exit_code(handler: &Lua) -> Option<i32> {
+  rux_dbg_call!();
+  rux_dbg_reav!(handler.context(get_lane_exit));
+}
+
+pub fn get_exit_status<T>(handler: &Lua, result: &Result<T, LizError>) -> i32 {
+  rux_dbg_call!();
+  if let Some(code) = get_exit_code(handler) {
+    rux_dbg_reav!(code);
+  }
+  let signal = ABORT_SIGNAL.load(Ordering::Acquire);
+  if signal != 0 {
+    rux_dbg_reav!(128 + signal);
+  }
+  rux_dbg_reav!(if result.is_err() { 1 } else { 0 });
+}
+
+pub fn is_exit_quiet(handler: &Lua) -> bool {
+  rux_dbg_call!();
+  rux_dbg_reav!(get_exit_code(handler).is_some() || ABORT_SIGNAL.load(Ordering::Acquire) != 0);
+}
+
+// The exit of a nested run only ends that run, with the code 0 it is a success.
+pub fn treat_exit<T: Default>(
+  handler: &Lua,
+  result: Result<T, LizError>,
+) -> Result<T, LizError> {
+  rux_dbg_call!();
+  if result.is_err() && get_exit_code(handler) == Some(0) {
+    rux_dbg_reav!(Ok(T::default()));
+  }
+  rux_dbg_reav!(result);
+}
+
+// The children on the terminal group already got an interactive signal, but not
+// the ones on their own group or when the signal was sent only to liz.
+pub fn abort_children() {
+  rux_dbg_call!();
+  let signal = ABORT_SIGNAL.load(Ordering::Acquire);
+  if signal == 0 {
+    return;
+  }
+  if let Ok(mut tracked) = TRACKED_CHILDREN.lock() {
+    send_tracked(&mut tracked, signal);
+  }
+}
+
+// The error of an exit must not be swallowed by the catches of the script, so
+// they are wrapped to raise it again while the exit is pending.
+const GUARD_CATCHES: &str = r#"
+local is_exit_pending, raw_pcall, raw_xpcall, raw_resume = ...
+local function checked(ok, ...)
+  if not ok and is_exit_pending() then
+    error((...), 0)
+  end
+  return ok, ...
+end
+pcall = function(...) return checked(raw_pcall(...)) end
+xpcall = function(...) return checked(raw_xpcall(...)) end
+coroutine.resume = function(...) return checked(raw_resume(...)) end
+"#;
+
+pub fn guard_catches(lane: Context) -> Result<(), LizError> {
+  rux_dbg_call!();
+  let globals = lane.globals();
+  let is_exit_pending = lane
+    .create_function(|lane, ()| Ok(is_exit_pending(lane)))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let raw_pcall: Function = globals.get("pcall").map_err(|err| rux_dbg_erro!(err))?;
+  let raw_xpcall: Function = globals.get("xpcall").map_err(|err| rux_dbg_erro!(err))?;
+  let coroutine: Table = globals.get("coroutine").map_err(|err| rux_dbg_erro!(err))?;
+  let raw_resume: Function = coroutine.get("resume").map_err(|err| rux_dbg_erro!(err))?;
+  lane
+    .load(GUARD_CATCHES)
+    .call::<_, ()>((is_exit_pending, raw_pcall, raw_xpcall, raw_resume))
+    .map_err(|err| rux_dbg_erro!(err))
+}
+
+pub fn is_exit_pending(lane: Context) -> bool {
+  get_lane_exit(lane).is_some() && !is_exit_running(lane)
+}
+
+pub fn is_signal_pending() -> bool {
+  PENDING_SIGNAL.load(Ordering::Acquire) != 0 || DEFERRED_SIGNAL.load(Ordering::Acquire) != 0
+}
+
+pub fn take_signals(lane: Context) -> Result<(), LizError> {
+  rux_dbg_call!();
+  check_signals(lane, true).map_err(|err| rux_dbg_erro!(err))
+}
+
+// Used where there is no lane to call the custom handlers, so any signal aborts.
+pub fn take_abort() -> Result<(), LizError> {
+  rux_dbg_call!();
+  let mut signal = PENDING_SIGNAL.swap(0, Ordering::AcqRel);
+  if signal == 0 {
+    signal = DEFERRED_SIGNAL.swap(0, Ordering::AcqRel);
+  }
+  if signal == 0 {
+    return Ok(());
+  }
+  ABORT_SIGNAL.store(signal, Ordering::Release);
+  Err(rux_dbg_erro!(format!(
+    "Liz interrupted by the signal {}",
+    signal_name(signal)
+  )))
+}
+
+pub fn track_child(pid: u32, group: bool) {
+  rux_dbg_call!(pid, group);
+  let target = if group { -(pid as i32) } else { pid as i32 };
+  if let Ok(mut tracked) = TRACKED_CHILDREN.lock() {
+    tracked.push(target);
+  }
+}
+
+pub fn untrack_child(pid: u32) {
+  rux_dbg_call!(pid);
+  let pid = pid as i32;
+  if let Ok(mut tracked) = TRACKED_CHILDREN.lock() {
+    tracked.retain(|target| *target != pid && *target != -pid);
+  }
+}
+
+pub fn on_exit<'a>(lane: Context<'a>, handler: Function<'a>) -> Result<(), LizError> {
+  rux_dbg_call!();
+  let handlers = get_lane_table(lane, "liz_exit_handlers").map_err(|err| rux_dbg_bleb!(err))?;
+  let next = handlers.raw_len() + 1;
+  handlers
+    .set(next, handler)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  Ok(())
+}
+
+pub fn on_signal<'a>(
+  lane: Context<'a>,
+  name: &str,
+  handler: Function<'a>,
+) -> Result<(), LizError> {
+  rux_dbg_call!(name);
+  let number = signal_number(name).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(number);
+  if !is_signal_catchable(number) {
+    return Err(rux_dbg_erro!(format!(
+      "The signal {} can not be caught",
+      signal_name(number)
+    )));
+  }
+  let handlers =
+    get_lane_table(lane, "liz_signal_handlers").map_err(|err| rux_dbg_bleb!(err))?;
+  handlers
+    .set(number, handler)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  trap_signal(number);
+  Ok(())
+}
+
+pub fn run_exit_handlers(lane: Context) -> Result<(), LizError> {
+  rux_dbg_call!();
+  set_exit_running(lane, true).map_err(|err| rux_dbg_bleb!(err))?;
+  let handlers = get_lane_table(lane, "liz_exit_handlers").map_err(|err| rux_dbg_bleb!(err))?;
+  let mut first_error: Option<LizError> = None;
+  let mut index = handlers.raw_len();
+  while index > 0 {
+    rux_dbg_tell!(index);
+    let handler: Function = handlers.get(index).map_err(|err| rux_dbg_erro!(err))?;
+    handlers
+      .set(index, rlua::Nil)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    if let Err(err) = handler.call::<_, ()>(()) {
+      let quiet = get_lane_exit(lane).is_some() || ABORT_SIGNAL.load(Ordering::Acquire) != 0;
+      if first_error.is_none() && !quiet {
+        first_error = Some(rux_dbg_erro!(err));
+      }
+    }
+    index -= 1;
+  }
+  set_exit_running(lane, false).map_err(|err| rux_dbg_bleb!(err))?;
+  match first_error {
+    Some(err) => Err(err),
+    None => Ok(()),
+  }
+}
+
+pub fn trap_signals() {
+  rux_dbg_call!();
+  if cfg!(unix) {
+    if let Ok(number) = signal_number("INT") {
+      trap_signal(number);
+    }
+    if let Ok(number) = signal_number("TERM") {
+      trap_signal(number);
+    }
+  }
+}
+
+// Every lane gets its own hook. The hook on calls is needed to reach the entry
+// of a native function, so the fast path only checks the pending signals.
+pub fn watch_signals(handler: &Lua) {
+  rux_dbg_call!();
+  let triggers = HookTriggers {
+    on_calls: true,
+    every_nth_instruction: Some(1000),
+    ..Default::default()
+  };
+  handler.set_hook(triggers, |lane, debug| {
+    if !is_signal_pending() {
+      return Ok(());
+    }
+    let on_native = debug.source().what == Some(b"C".as_ref());
+    check_signals(lane, on_native)
+  });
+}
+
+// Lua handlers are only called when the hook fires on the entry of a native
+// function, because on the other events rlua shifts the stack of the running
+// Lua function and any of its locals captured by the handler would be broken.
+fn check_signals(lane: Context, on_native: bool) -> rlua::Result<()> {
+  if !is_signal_pending() || CHECKING.with(|checking| checking.replace(true)) {
+    return Ok(());
+  }
+  let result = take_signal(lane, on_native);
+  CHECKING.with(|checking| checking.set(false));
+  result
+}
+
+// The calls of rlua into the lane fire the hook too, so they must not check again.
+thread_local! {
+  static CHECKING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn take_signal(lane: Context, on_native: bool) -> rlua::Result<()> {
+  if is_exit_running(lane) {
+    return Ok(());
+  }
+  let mut signal = PENDING_SIGNAL.swap(0, Ordering::AcqRel);
+  if signal == 0 {
+    if !on_native {
+      return Ok(());
+    }
+    signal = DEFERRED_SIGNAL.swap(0, Ordering::AcqRel);
+    if signal == 0 {
+      return Ok(());
+    }
+  }
+  let custom: Option<Function> = match get_lane_table(lane, "liz_signal_handlers") {
+    Ok(handlers) => handlers.get(signal)?,
+    Err(_) => None,
+  };
+  match custom {
+    Some(_) if !on_native => {
+      DEFERRED_SIGNAL.store(signal, Ordering::Release);
+      Ok(())
+    }
+    Some(custom) => custom.call::<_, ()>(signal_name(signal)),
+    None => {
+      ABORT_SIGNAL.store(signal, Ordering::Release);
+      Err(rlua::Error::external(format!(
+        "Liz interrupted by the signal {}",
+        signal_name(signal)
+      )))
+    }
+  }
+}
+
+fn get_lane_exit(lane: Context) -> Option<i32> {
+  lane
+    .named_registry_value::<_, Option<i32>>("liz_exit_code")
+    .ok()
+    .flatten()
+}
+
+fn is_exit_running(lane: Context) -> bool {
+  lane
+    .named_registry_value::<_, Option<bool>>("liz_exit_running")
+    .ok()
+    .flatten()
+    .unwrap_or(false)
+}
+
+fn set_exit_running(lane: Context, running: bool) -> Result<(), LizError> {
+  lane
+    .set_named_registry_value("liz_exit_running", running)
+    .map_err(|err| rux_dbg_erro!(err))
+}
+
+fn get_lane_table<'a>(lane: Context<'a>, name: &str) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(name);
+  let found: Option<Table> = lane
+    .named_registry_value(name)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(found) = found {
+    rux_dbg_reav!(Ok(found));
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  lane
+    .set_named_registry_value(name, result.clone())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+#[cfg(unix)]
+const SIGNAL_NAMES: [(&str, libc::c_int); 9] = [
+  ("HUP", libc::SIGHUP),
+  ("INT", libc::SIGINT),
+  ("QUIT", libc::SIGQUIT),
+  ("KILL", libc::SIGKILL),
+  ("USR1", libc::SIGUSR1),
+  ("USR2", libc::SIGUSR2),
+  ("TERM", libc::SIGTERM),
+  ("CONT", libc::SIGCONT),
+  ("STOP", libc::SIGSTOP),
+];
+
+#[cfg(unix)]
+pub fn signal_number(signal: &str) -> Result<i32, LizError> {
+  rux_dbg_call!(signal);
+  let name = signal.trim().to_uppercase();
+  let name = name.strip_prefix("SIG").unwrap_or(&name);
+  if let Some((_, number)) = SIGNAL_NAMES.iter().find(|(known, _)| *known == name) {
+    rux_dbg_reav!(Ok(*number));
+  }
+  rux_dbg_reav!(name
+    .parse::<i32>()
+    .map_err(|_| rux_dbg_erro!(format!("Unknown signal: {}", signal))));
+}
+
+#[cfg(unix)]
+fn is_signal_catchable(number: i32) -> bool {
+  number != libc::SIGKILL && number != libc::SIGSTOP
+}
+
+#[cfg(not(unix))]
+fn is_signal_catchable(_: i32) -> bool {
+  true
+}
+
+#[cfg(not(unix))]
+pub fn signal_number(signal: &str) -> Result<i32, LizError> {
+  rux_dbg_call!(signal);
+  Err(rux_dbg_erro!(format!(
+    "The signal {} is only supported on unix",
+    signal
+  )))
+}
+
+#[cfg(unix)]
+pub fn signal_name(number: i32) -> String {
+  match SIGNAL_NAMES.iter().find(|(_, known)| *known == number) {
+    Some((name, _)) => format!("SIG{}", name),
+    None => format!("{}", number),
+  }
+}
+
+#[cfg(not(unix))]
+pub fn signal_name(number: i32) -> String {
+  format!("{}", number)
+}
+
+#[cfg(unix)]
+extern "C" fn signal_trap(number: libc::c_int) {
+  if PENDING_SIGNAL.swap(number, Ordering::AcqRel) != 0 {
+    // The trap must never block, if the list is being changed right now the
+    // children are left to the signal of the terminal group.
+    if let Ok(mut tracked) = TRACKED_CHILDREN.try_lock() {
+      send_tracked(&mut tracked, number);
+    }
+    unsafe { libc::_exit(128 + number) };
+  }
+}
+
+#[cfg(unix)]
+fn send_tracked(tracked: &mut Vec<i32>, number: i32) {
+  for target in tracked.drain(..) {
+    unsafe { libc::kill(target, number) };
+  }
+}
+
+#[cfg(not(unix))]
+fn send_tracked(tracked: &mut Vec<i32>, _: i32) {
+  tracked.clear();
+}
+
+#[cfg(unix)]
+fn trap_signal(number: i32) {
+  rux_dbg_call!(number);
+  let trap = signal_trap as extern "C" fn(libc::c_int);
+  unsafe { libc::signal(number, trap as libc::sighandler_t) };
+}
+
+#[cfg(not(unix))]
+fn trap_signal(number: i32) {
+  rux_dbg_call!(number);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_tracked(target: i32) -> bool {
+    TRACKED_CHILDREN.lock().unwrap().contains(&target)
+  }
+
+  #[test]
+  fn exit_sets_the_code_and_fails() {
+    let handler = Lua::new();
+    assert_eq!(get_exit_code(&handler), None);
+    assert!(!is_exit_quiet(&handler));
+    assert_eq!(get_exit_status(&handler, &Ok(())), 0);
+    handler.context(|lane| {
+      assert!(exit(lane, 3).is_err());
+      assert!(is_exit_pending(lane));
+    });
+    assert_eq!(get_exit_code(&handler), Some(3));
+    assert!(is_exit_quiet(&handler));
+    assert_eq!(get_exit_status(&handler, &Ok(())), 3);
+    assert!(treat_exit::<u8>(&handler, Err("exit".into())).is_err());
+  }
+
+  #[test]
+  fn treat_exit_takes_code_zero_as_success() {
+    let handler = Lua::new();
+    assert!(treat_exit::<u8>(&handler, Err("fail".into())).is_err());
+    assert_eq!(
+      get_exit_status(&handler, &Err::<(), LizError>("fail".into())),
+      1
+    );
+    handler.context(|lane| {
+      assert!(exit(lane, 0).is_err());
+    });
+    assert_eq!(treat_exit::<u8>(&handler, Err("exit".into())).unwrap(), 0);
+  }
+
+  #[test]
+  fn guard_catches_raises_pending_exit() {
+    Lua::new().context(|lane| {
+      guard_catches(lane).unwrap();
+      let caught: bool = lane.load("return not pcall(error, 'boom')").eval().unwrap();
+      assert!(caught);
+      assert!(exit(lane, 2).is_err());
+      assert!(lane.load("pcall(error, 'boom')").exec().is_err());
+      assert!(lane.load("xpcall(error, tostring, 'boom')").exec().is_err());
+    });
+  }
+
+  #[test]
+  fn exit_handlers_run_in_reverse_and_once() {
+    Lua::new().context(|lane| {
+      lane.load("calls = {}").exec().unwrap();
+      for name in ["first", "second", "third"] {
+        let handler: Function = lane
+          .load(&format!(
+            "return function() calls[#calls + 1] = '{}'; error('{}') end",
+            name, name
+          ))
+          .eval()
+          .unwrap();
+        on_exit(lane, handler).unwrap();
+      }
+      let err = run_exit_handlers(lane).unwrap_err();
+      assert!(format!("{}", err).contains("third"));
+      let calls: Vec<String> = lane.load("calls").eval().unwrap();
+      assert_eq!(calls, vec!["third", "second", "first"]);
+      assert!(!is_exit_running(lane));
+      run_exit_handlers(lane).unwrap();
+      let calls: Vec<String> = lane.load("calls").eval().unwrap();
+      assert_eq!(calls.len(), 3);
+    });
+  }
+
+  #[test]
+  fn lane_table_is_created_once() {
+    Lua::new().context(|lane| {
+      let table = get_lane_table(lane, "liz_test_table").unwrap();
+      table.set("key", "value").unwrap();
+      let again = get_lane_table(lane, "liz_test_table").unwrap();
+      assert_eq!(again.get::<_, String>("key").unwrap(), "value");
+    });
+  }
+
+  #[test]
+  fn children_are_tracked_and_untracked() {
+    track_child(999_901, false);
+    track_child(999_902, true);
+    assert!(is_tracked(999_901));
+    assert!(is_tracked(-999_902));
+    untrack_child(999_901);
+    untrack_child(999_902);
+    assert!(!is_tracked(999_901));
+    assert!(!is_tracked(-999_902));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn signal_names_and_numbers() {
+    assert_eq!(signal_number("term").unwrap(), libc::SIGTERM);
+    assert_eq!(signal_number(" SIGINT ").unwrap(), libc::SIGINT);
+    assert_eq!(signal_number("9").unwrap(), 9);
+    assert!(signal_number("NOPE").is_err());
+    assert_eq!(signal_name(libc::SIGHUP), "SIGHUP");
+    assert_eq!(signal_name(64), "64");
+    assert!(is_signal_catchable(libc::SIGTERM));
+    assert!(!is_signal_catchable(libc::SIGKILL));
+    assert!(!is_signal_catchable(libc::SIGSTOP));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn on_signal_refuses_uncatchable() {
+    Lua::new().context(|lane| {
+      let handler: Function = lane.load("return function() end").eval().unwrap();
+      assert!(on_signal(lane, "KILL", handler.clone()).is_err());
+      assert!(on_signal(lane, "NOPE", handler).is_err());
+    });
+  }
+}
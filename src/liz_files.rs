@@ -0,0 +1,535 @@
+use once_cell::sync::Lazy;
+use rlua::{Context, Function, MultiValue, Table};
+use rubx::rux_texts;
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::liz_exits;
+use crate::liz_hashes;
+use crate::liz_procs;
+use crate::LizError;
+
+static ATOMIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+static HELD_LOCKS: Lazy<Mutex<HashSet<(usize, String)>>> =
+  Lazy::new(|| Mutex::new(HashSet::new()));
+
+static SINGLE_LOCKS: Lazy<Mutex<HashMap<String, (String, File)>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn is_atomic(opts: Option<Table>, default: bool) -> Result<bool, LizError> {
+  rux_dbg_call!(default);
+  if let Some(opts) = opts {
+    if let Some(atomic) = opts.get("atomic").map_err(|err| rux_dbg_erro!(err))? {
+      rux_dbg_reav!(Ok(atomic));
+    }
+  }
+  rux_dbg_reav!(Ok(default));
+}
+
+pub fn write(path: &str, contents: String, atomic: bool) -> Result<(), LizError> {
+  rux_dbg_call!(path, atomic);
+  if atomic {
+    rux_dbg_reav!(write_atomic(path, contents.as_bytes()));
+  }
+  rux_dbg_reav!(rux_texts::write(path, contents));
+}
+
+pub fn write_atomic(path: &str, contents: &[u8]) -> Result<(), LizError> {
+  rux_dbg_call!(path);
+  let target = get_atomic_target(path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(target);
+  let target = target.as_path();
+  let parent = match target.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+    _ => std::env::current_dir().map_err(|err| rux_dbg_erro!(err))?,
+  };
+  let name = target
+    .file_name()
+    .ok_or_else(|| rux_dbg_erro!(format!("The path has no file name: {}", path)))?
+    .to_string_lossy()
+    .to_string();
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|time| time.subsec_nanos())
+    .unwrap_or_default();
+  let temp = parent.join(format!(
+    ".{}.liz_tmp_{}_{}_{}",
+    name,
+    std::process::id(),
+    ATOMIC_COUNT.fetch_add(1, Ordering::SeqCst),
+    nanos
+  ));
+  rux_dbg_step!(temp);
+  let written = put_atomic_temp(&temp, target, contents);
+  if written.is_err() {
+    let _ = std::fs::remove_file(&temp);
+  }
+  written.map_err(|err| rux_dbg_bleb!(err))?;
+  #[cfg(unix)]
+  {
+    if let Ok(dir) = File::open(&parent) {
+      let _ = dir.sync_all();
+    }
+  }
+  Ok(())
+}
+
+// The rename would replace a symlink with a regular file, so the write goes to
+// the file the link points to, even when that one does not exist yet.
+fn get_atomic_target(path: &str) -> Result<PathBuf, LizError> {
+  let is_link = std::fs::symlink_metadata(path)
+    .map(|metadata| metadata.file_type().is_symlink())
+    .unwrap_or(false);
+  if !is_link {
+    return Ok(PathBuf::from(path));
+  }
+  if let Ok(canonical) = std::fs::canonicalize(path) {
+    return Ok(canonical);
+  }
+  let link = std::fs::read_link(path).map_err(|err| rux_dbg_erro!(err, path))?;
+  match Path::new(path).parent() {
+    Some(parent) if link.is_relative() => Ok(parent.join(link)),
+    _ => Ok(link),
+  }
+}
+
+pub fn append(path: &str, contents: String) -> Result<(), LizError> {
+  rux_dbg_call!(path);
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  file.lock().map_err(|err| rux_dbg_erro!(err, path))?;
+  file
+    .write_all(contents.as_bytes())
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  file.unlock().map_err(|err| rux_dbg_erro!(err, path))?;
+  Ok(())
+}
+
+pub fn lock<'a>(
+  lane: Context<'a>,
+  path: &str,
+  timeout: Option<u64>,
+  function: Function<'a>,
+) -> Result<MultiValue<'a>, LizError> {
+  rux_dbg_call!(path, timeout);
+  let lock_path = format!("{}.lock", path);
+  rux_dbg_step!(lock_path);
+  let mut file = open_lock_file(&lock_path).map_err(|err| rux_dbg_bleb!(err))?;
+  // Each open of the lock file takes its own lock, so a nested lock on the
+  // same path by the same lane would wait on itself forever.
+  let held = (
+    liz_procs::get_lane_id(lane).map_err(|err| rux_dbg_bleb!(err))?,
+    std::fs::canonicalize(&lock_path)
+      .map(|canonical| canonical.display().to_string())
+      .unwrap_or_else(|_| lock_path.clone()),
+  );
+  if !HELD_LOCKS
+    .lock()
+    .map_err(|err| rux_dbg_erro!(err))?
+    .insert(held.clone())
+  {
+    return Err(rux_dbg_erro!(format!(
+      "The lock on {} is already held by this lane",
+      path
+    )));
+  }
+  let _held = HeldLock(held);
+  let started = Instant::now();
+  loop {
+    match file.try_lock() {
+      Ok(()) => {
+        if is_lock_file(&file, &lock_path) {
+          break;
+        }
+        let _ = file.unlock();
+        file = open_lock_file(&lock_path).map_err(|err| rux_dbg_bleb!(err))?;
+      }
+      Err(TryLockError::WouldBlock) => {
+        if let Some(timeout) = timeout {
+          if started.elapsed() >= Duration::from_millis(timeout) {
+            return Err(rux_dbg_erro!(format!(
+              "Timeout waiting for the lock on {}",
+              path
+            )));
+          }
+        }
+        if liz_exits::is_signal_pending() {
+          liz_exits::take_signals(lane).map_err(|err| rux_dbg_bleb!(err))?;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+      }
+      Err(TryLockError::Error(err)) => return Err(rux_dbg_erro!(err, lock_path)),
+    }
+  }
+  let result = function.call::<_, MultiValue>(());
+  let _ = std::fs::remove_file(&lock_path);
+  let _ = file.unlock();
+  rux_dbg_reav!(result.map_err(|err| rux_dbg_erro!(err)));
+}
+
+fn open_lock_file(lock_path: &str) -> Result<File, LizError> {
+  OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(lock_path)
+    .map_err(|err| rux_dbg_erro!(err, lock_path))
+}
+
+// The lock file is removed by its holder before the unlock, so a waiter that
+// got the lock on a removed file must open the path again and lock the new one.
+#[cfg(unix)]
+fn is_lock_file(file: &File, lock_path: &str) -> bool {
+  use std::os::unix::fs::MetadataExt;
+  match (file.metadata(), std::fs::metadata(lock_path)) {
+    (Ok(locked), Ok(actual)) => locked.dev() == actual.dev() && locked.ino() == actual.ino(),
+    _ => false,
+  }
+}
+
+#[cfg(not(unix))]
+fn is_lock_file(_: &File, _: &str) -> bool {
+  true
+}
+
+struct HeldLock((usize, String));
+
+impl Drop for HeldLock {
+  fn drop(&mut self) {
+    if let Ok(mut held_locks) = HELD_LOCKS.lock() {
+      held_locks.remove(&self.0);
+    }
+  }
+}
+
+pub fn single_instance(
+  lane: Context,
+  name: Option<String>,
+  timeout: Option<u64>,
+) -> Result<String, LizError> {
+  rux_dbg_call!(name, timeout);
+  let key = match name {
+    Some(name) => name,
+    None => get_single_key(lane).map_err(|err| rux_dbg_bleb!(err))?,
+  };
+  rux_dbg_step!(key);
+  rux_dbg_reav!(single_lock(Some(lane), &key, timeout));
+}
+
+pub fn single_lock(
+  lane: Option<Context>,
+  key: &str,
+  timeout: Option<u64>,
+) -> Result<String, LizError> {
+  rux_dbg_call!(key, timeout);
+  let mut single_locks = SINGLE_LOCKS.lock().map_err(|err| rux_dbg_erro!(err))?;
+  if let Some((lock_path, _)) = single_locks.get(key) {
+    rux_dbg_reav!(Ok(lock_path.clone()));
+  }
+  let lock_path = get_single_path(key).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lock_path);
+  let mut file = open_single_file(&lock_path).map_err(|err| rux_dbg_bleb!(err))?;
+  let started = Instant::now();
+  loop {
+    match file.try_lock() {
+      Ok(()) => {
+        if is_lock_file(&file, &lock_path) {
+          break;
+        }
+        let _ = file.unlock();
+        file = open_single_file(&lock_path).map_err(|err| rux_dbg_bleb!(err))?;
+      }
+      Err(TryLockError::WouldBlock) => {
+        let holder = get_lock_holder(&mut file);
+        // The lock can outlive its process when the file was inherited by another
+        // one, so a lock of a dead process is taken over on a fresh file.
+        let stale = holder
+          .parse::<u32>()
+          .map(|pid| !is_process_alive(pid))
+          .unwrap_or(false);
+        if stale && is_lock_file(&file, &lock_path) {
+          rux_dbg_tell!(holder, "stale");
+          let _ = std::fs::remove_file(&lock_path);
+          file = open_single_file(&lock_path).map_err(|err| rux_dbg_bleb!(err))?;
+          continue;
+        }
+        let waited = started.elapsed();
+        if timeout
+          .map(|timeout| waited >= Duration::from_millis(timeout))
+          .unwrap_or(true)
+        {
+          return Err(rux_dbg_erro!(format!(
+            "Another instance of {} is running on the process {}",
+            key, holder
+          )));
+        }
+        if liz_exits::is_signal_pending() {
+          match lane {
+            Some(lane) => liz_exits::take_signals(lane),
+            None => liz_exits::take_abort(),
+          }
+          .map_err(|err| rux_dbg_bleb!(err))?;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+      }
+      Err(TryLockError::Error(err)) => return Err(rux_dbg_erro!(err, lock_path)),
+    }
+  }
+  let stale = get_lock_holder(&mut file);
+  if !stale.is_empty() {
+    rux_dbg_step!(stale);
+  }
+  file
+    .set_len(0)
+    .map_err(|err| rux_dbg_erro!(err, lock_path))?;
+  file.rewind().map_err(|err| rux_dbg_erro!(err, lock_path))?;
+  write!(file, "{}", std::process::id()).map_err(|err| rux_dbg_erro!(err, lock_path))?;
+  file
+    .sync_all()
+    .map_err(|err| rux_dbg_erro!(err, lock_path))?;
+  single_locks.insert(key.into(), (lock_path.clone(), file));
+  rux_dbg_reav!(Ok(lock_path));
+}
+
+fn open_single_file(lock_path: &str) -> Result<File, LizError> {
+  OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .read(true)
+    .write(true)
+    .open(lock_path)
+    .map_err(|err| rux_dbg_erro!(err, lock_path))
+}
+
+fn get_lock_holder(file: &mut File) -> String {
+  let mut result = String::new();
+  if file.rewind().is_ok() {
+    let _ = file.read_to_string(&mut result);
+  }
+  result.trim().to_string()
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+  if pid == 0 || pid > i32::MAX as u32 {
+    return false;
+  }
+  let result = unsafe { libc::kill(pid as i32, 0) };
+  result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_process_alive(_: u32) -> bool {
+  true
+}
+
+pub fn single_finish_all() {
+  rux_dbg_call!();
+  let single_locks = match SINGLE_LOCKS.lock() {
+    Ok(mut single_locks) => std::mem::take(&mut *single_locks),
+    Err(_) => return,
+  };
+  for (_, (lock_path, file)) in single_locks {
+    rux_dbg_step!(lock_path);
+    let _ = file.set_len(0);
+    let _ = file.unlock();
+  }
+}
+
+fn get_single_key(lane: Context) -> Result<String, LizError> {
+  rux_dbg_call!();
+  let liz: Table = lane
+    .globals()
+    .get("Liz")
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let race_path: Option<String> = liz.get("race_path").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(race_path) = race_path {
+    rux_dbg_reav!(Ok(race_path));
+  }
+  let rise_path: Option<String> = liz.get("rise_path").map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(rise_path.ok_or_else(|| rux_dbg_erro!("Could not get the race path")));
+}
+
+fn get_single_path(key: &str) -> Result<String, LizError> {
+  rux_dbg_call!(key);
+  let stem = Path::new(key)
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_default()
+    .chars()
+    .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+    .collect::<String>();
+  let hash = liz_hashes::hash_text(key, "crc32").map_err(|err| rux_dbg_bleb!(err))?;
+  let name = format!("liz_single_{}_{}.lock", stem, hash);
+  rux_dbg_reav!(Ok(std::env::temp_dir().join(name).display().to_string()));
+}
+
+fn put_atomic_temp(temp: &Path, target: &Path, contents: &[u8]) -> Result<(), LizError> {
+  rux_dbg_call!(temp, target);
+  let mut file = OpenOptions::new()
+    .create_new(true)
+    .write(true)
+    .open(temp)
+    .map_err(|err| rux_dbg_erro!(err, temp))?;
+  file
+    .write_all(contents)
+    .map_err(|err| rux_dbg_erro!(err, temp))?;
+  if let Ok(metadata) = std::fs::metadata(target) {
+    let _ = file.set_permissions(metadata.permissions());
+  }
+  file.sync_all().map_err(|err| rux_dbg_erro!(err, temp))?;
+  drop(file);
+  std::fs::rename(temp, target).map_err(|err| rux_dbg_erro!(err, target))?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_test_dir(name: &str) -> PathBuf {
+    let result =
+      std::env::temp_dir().join(format!("liz_files_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&result);
+    std::fs::create_dir_all(&result).unwrap();
+    result
+  }
+
+  fn get_names(dir: &Path) -> Vec<String> {
+    let mut result: Vec<String> = std::fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+      .collect();
+    result.sort();
+    result
+  }
+
+  #[test]
+  fn atomic_write_replaces_and_leaves_no_temp() {
+    let dir = get_test_dir("atomic");
+    let path = dir.join("data.txt").display().to_string();
+    write_atomic(&path, b"first").unwrap();
+    write_atomic(&path, b"second").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+    assert_eq!(get_names(&dir), vec!["data.txt"]);
+    let missing = dir.join("missing").join("data.txt").display().to_string();
+    assert!(write_atomic(&missing, b"none").is_err());
+    assert_eq!(get_names(&dir), vec!["data.txt"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn atomic_write_goes_through_symlinks() {
+    let dir = get_test_dir("symlink");
+    let target = dir.join("target.txt");
+    std::fs::write(&target, "before").unwrap();
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink("target.txt", &link).unwrap();
+    write_atomic(&link.display().to_string(), b"after").unwrap();
+    assert!(std::fs::symlink_metadata(&link)
+      .unwrap()
+      .file_type()
+      .is_symlink());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "after");
+    let dangling = dir.join("dangling.txt");
+    std::os::unix::fs::symlink("created.txt", &dangling).unwrap();
+    write_atomic(&dangling.display().to_string(), b"new").unwrap();
+    assert_eq!(
+      std::fs::read_to_string(dir.join("created.txt")).unwrap(),
+      "new"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn lock_runs_the_function_and_removes_its_file() {
+    let dir = get_test_dir("lock");
+    let path = dir.join("data.txt").display().to_string();
+    Lua::new().context(|lane| {
+      let function: Function = lane.load("function() return 42 end").eval().unwrap();
+      let result = lock(lane, &path, None, function).unwrap();
+      assert!(matches!(
+        result.into_iter().next(),
+        Some(rlua::Value::Integer(42))
+      ));
+      assert!(get_names(&dir).is_empty());
+      let holder = open_lock_file(&format!("{}.lock", path)).unwrap();
+      holder.try_lock().unwrap();
+      let function: Function = lane.load("function() end").eval().unwrap();
+      assert!(lock(lane, &path, Some(50), function.clone()).is_err());
+      holder.unlock().unwrap();
+      assert!(lock(lane, &path, Some(50), function).is_ok());
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  fn get_single_holder(key: &str, pid: &str) -> File {
+    let mut result = open_single_file(&get_single_path(key).unwrap()).unwrap();
+    result.try_lock().unwrap();
+    result.set_len(0).unwrap();
+    write!(result, "{}", pid).unwrap();
+    result
+  }
+
+  fn put_single_free(key: &str) {
+    if let Some((lock_path, file)) = SINGLE_LOCKS.lock().unwrap().remove(key) {
+      let _ = file.unlock();
+      let _ = std::fs::remove_file(lock_path);
+    }
+  }
+
+  #[test]
+  fn single_lock_records_the_process() {
+    let key = format!("liz_files_single_{}", std::process::id());
+    let lock_path = single_lock(None, &key, None).unwrap();
+    assert_eq!(single_lock(None, &key, None).unwrap(), lock_path);
+    let recorded = std::fs::read_to_string(&lock_path).unwrap();
+    assert_eq!(recorded, std::process::id().to_string());
+    put_single_free(&key);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn single_lock_takes_over_from_dead_processes() {
+    let key = format!("liz_files_stale_{}", std::process::id());
+    let holder = get_single_holder(&key, "2147483646");
+    let lock_path = single_lock(None, &key, None).unwrap();
+    let recorded = std::fs::read_to_string(&lock_path).unwrap();
+    assert_eq!(recorded, std::process::id().to_string());
+    assert!(!is_lock_file(&holder, &lock_path));
+    put_single_free(&key);
+  }
+
+  #[test]
+  fn single_lock_refuses_live_holders() {
+    let key = format!("liz_files_live_{}", std::process::id());
+    let holder = get_single_holder(&key, &std::process::id().to_string());
+    assert!(single_lock(None, &key, None).is_err());
+    assert!(single_lock(None, &key, Some(100)).is_err());
+    holder.unlock().unwrap();
+    assert!(single_lock(None, &key, Some(100)).is_ok());
+    put_single_free(&key);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn process_liveness_is_checked() {
+    assert!(is_process_alive(std::process::id()));
+    assert!(!is_process_alive(0));
+    assert!(!is_process_alive(u32::MAX));
+  }
+}
@@ -3,6 +3,8 @@ use rubx::rux_paths;
 use rubx::{self, rux_dbg_call, rux_dbg_reav, rux_dbg_step};
 use rubx::{rux_dbg_bleb, rux_dbg_erro, rux_dbg_errs, rux_dbg_warn};
 
+use std::collections::HashMap;
+use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::RwLock;
@@ -10,9 +12,29 @@ use std::thread;
 use std::time::Duration;
 
 use crate::liz_codes;
+use crate::liz_envs::{self, LaneEnvs};
+use crate::liz_exits;
 use crate::utils;
 use crate::LizError;
 
+pub fn run_envs(
+    path: &str,
+    args: &Option<Vec<String>>,
+    envs: &LaneEnvs,
+) -> Result<Vec<String>, LizError> {
+    rux_dbg_call!(path, args, envs);
+    let (rise_path, handler) = crate::rise(path, args).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_step!(rise_path);
+    handler
+        .context(|lane| liz_envs::put_lane_envs(lane, envs))
+        .map_err(|err| rux_dbg_bleb!(err))?;
+    let result = crate::race(&rise_path, &handler).map_err(|err| rux_dbg_bleb!(err));
+    let result = liz_exits::treat_exit(&handler, result);
+    rux_dbg_step!(result);
+    let finished = crate::finish(&handler).map_err(|err| rux_dbg_bleb!(err));
+    rux_dbg_reav!(result.and_then(|values| finished.map(|_| values)));
+}
+
 pub fn run_wd(relative_path: &str, args: &Option<Vec<String>>) -> Result<Vec<String>, LizError> {
     rux_dbg_call!(relative_path);
     let working_dir = rux_paths::wd().map_err(|err| rux_dbg_bleb!(err))?;
@@ -33,8 +55,13 @@ pub fn race_wd(lane: Context, relative_path: &str) -> Result<Vec<String>, LizErr
     rux_dbg_reav!(crate::race_in(lane, &full_path).map_err(|err| rux_dbg_bleb!(err)));
 }
 
-pub fn spawn(lane: Context, path: &str, args: &Option<Vec<String>>) -> Result<Spawned, LizError> {
-    rux_dbg_call!(path, args);
+pub fn spawn(
+    lane: Context,
+    path: &str,
+    args: &Option<Vec<String>>,
+    envs: Option<HashMap<String, String>>,
+) -> Result<Spawned, LizError> {
+    rux_dbg_call!(path, args, envs);
     let globals = lane.globals();
     let liz: Table = globals.get("Liz").map_err(|err| rux_dbg_erro!(err))?;
 
@@ -69,13 +96,22 @@ pub fn spawn(lane: Context, path: &str, args: &Option<Vec<String>>) -> Result<Sp
     let spawn_name = format!("spawn{}", spawn_index);
     rux_dbg_step!(spawn_name);
 
-    let spawned = Spawned::new(spawn_path, args.clone());
+    let mut spawn_envs = liz_envs::get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+    if let Some(envs) = envs {
+        for (name, value) in envs {
+            spawn_envs.insert(name, Some(value));
+        }
+    }
+    rux_dbg_step!(spawn_envs);
+
+    let spawned = Spawned::new(spawn_path, args.clone(), spawn_envs);
     let spawned_clone = spawned.clone();
 
     let builder = thread::Builder::new().name(spawn_name);
     builder
         .spawn(move || {
-            let returned = crate::run(&spawned_clone.path, &spawned_clone.args);
+            let returned =
+                run_envs(&spawned_clone.path, &spawned_clone.args, &spawned_clone.envs);
             {
                 let mut lock = spawned_clone
                     .results
@@ -90,64 +126,98 @@ pub fn spawn(lane: Context, path: &str, args: &Option<Vec<String>>) -> Result<Sp
     rux_dbg_reav!(result)
 }
 
-pub fn join(spawned: Spawned) -> Result<Vec<String>, LizError> {
+pub fn join(lane: Context, spawned: Spawned) -> Result<Vec<String>, LizError> {
     rux_dbg_call!(spawned);
-    rux_dbg_reav!(spawned.join());
+    rux_dbg_reav!(spawned.join(lane));
 }
 
-pub fn join_all(spawneds: Vec<Spawned>) -> Result<Vec<Vec<String>>, LizError> {
+pub fn join_all(lane: Context, spawneds: Vec<Spawned>) -> Result<Vec<Vec<String>>, LizError> {
     rux_dbg_call!(spawneds);
     let mut all_results: Vec<Vec<String>> = Vec::new();
     for spawned in spawneds {
-        let spawned_result = spawned.join().map_err(|err| rux_dbg_bleb!(err))?;
+        let spawned_result = spawned.join(lane).map_err(|err| rux_dbg_bleb!(err))?;
         rux_dbg_step!(spawned_result);
         all_results.push(spawned_result);
     }
     rux_dbg_reav!(Ok(all_results));
 }
 
-pub fn wait(spawned: Spawned) -> Result<(), LizError> {
+pub fn wait(lane: Context, spawned: Spawned) -> Result<(), LizError> {
     rux_dbg_call!(spawned);
-    spawned.wait()
+    spawned.wait(lane)
 }
 
-pub fn wait_all(spawneds: Vec<Spawned>) -> Result<(), LizError> {
+pub fn wait_all(lane: Context, spawneds: Vec<Spawned>) -> Result<(), LizError> {
     rux_dbg_call!(spawneds);
     for spawned in spawneds {
-        spawned.wait().map_err(|err| rux_dbg_erro!(err))?
+        spawned.wait(lane).map_err(|err| rux_dbg_erro!(err))?
     }
     Ok(())
 }
 
+pub fn cmd(
+    lane: Context,
+    name: &str,
+    args: &[String],
+    dir: Option<String>,
+    print: Option<bool>,
+    throw: Option<bool>,
+    envs: Option<HashMap<String, String>>,
+) -> Result<(i32, String), LizError> {
+    rux_dbg_call!(name, args, dir, print, throw, envs);
+    let mut command = Command::new(name);
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let mut lane_envs = liz_envs::get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+    if let Some(envs) = envs {
+        for (env_name, env_value) in envs {
+            lane_envs.insert(env_name, Some(env_value));
+        }
+    }
+    rux_dbg_step!(lane_envs);
+    liz_envs::apply_lane_envs(&mut command, &lane_envs);
+    let output = command.output().map_err(|err| rux_dbg_erro!(err, name))?;
+    let mut result = String::from_utf8_lossy(&output.stdout).to_string();
+    result.push_str(&String::from_utf8_lossy(&output.stderr));
+    rux_dbg_step!(result);
+    let code = output.status.code().unwrap_or(-1);
+    rux_dbg_step!(code);
+    if print.unwrap_or(true) {
+        print!("{}", result);
+    }
+    if throw.unwrap_or(true) && code != 0 {
+        return Err(rux_dbg_erro!(format!(
+            "The command {} returned the code {} with the output: {}",
+            name, code, result
+        )));
+    }
+    rux_dbg_reav!(Ok((code, result)));
+}
+
 static SPAWN_COUNT: AtomicUsize = AtomicUsize::new(1);
 
 #[derive(Debug, Clone)]
 pub struct Spawned {
     path: String,
     args: Option<Vec<String>>,
+    envs: LaneEnvs,
     results: Arc<RwLock<Option<Result<Vec<String>, LizError>>>>,
 }
 
 impl Spawned {
-    fn new(path: String, args: Option<Vec<String>>) -> Spawned {
+    fn new(path: String, args: Option<Vec<String>>, envs: LaneEnvs) -> Spawned {
         Spawned {
             path,
             args,
+            envs,
             results: Arc::new(RwLock::new(None)),
         }
     }
 
-    fn join(&self) -> Result<Vec<String>, LizError> {
-        let waiter = Duration::from_millis(10);
-        loop {
-            {
-                let lock = self.results.read().map_err(|err| rux_dbg_erro!(err))?;
-                if lock.is_some() {
-                    break;
-                }
-            }
-            thread::sleep(waiter);
-        }
+    fn join(&self, lane: Context) -> Result<Vec<String>, LizError> {
+        self.wait(lane).map_err(|err| rux_dbg_bleb!(err))?;
         let lock = self.results.read().map_err(|err| rux_dbg_erro!(err))?;
         if let Some(results) = &*lock {
             match results {
@@ -162,7 +232,7 @@ impl Spawned {
         }
     }
 
-    fn wait(&self) -> Result<(), LizError> {
+    fn wait(&self, lane: Context) -> Result<(), LizError> {
         loop {
             {
                 let lock = self.results.read().map_err(|err| rux_dbg_erro!(err))?;
@@ -170,6 +240,9 @@ impl Spawned {
                     break;
                 }
             }
+            if liz_exits::is_signal_pending() {
+                liz_exits::take_signals(lane).map_err(|err| rux_dbg_bleb!(err))?;
+            }
             thread::sleep(Duration::from_millis(10));
         }
         Ok(())
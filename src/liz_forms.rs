@@ -2,6 +2,8 @@ use rubx::rux_dbg_bleb;
 use rubx::rux_texts;
 use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
 
+use crate::liz_distances;
+use crate::liz_files;
 use crate::LizError;
 
 #[derive(Debug, Clone)]
@@ -87,6 +89,13 @@ pub fn kit_find_all_like(forms: &Vec<String>, part: &str) -> Vec<usize> {
   }));
 }
 
+pub fn kit_find_all_similar(forms: &Vec<String>, part: &str, threshold: f64) -> Vec<usize> {
+  rux_dbg_call!(part, threshold);
+  rux_dbg_reav!(kit_find_all_ask(forms, |form| {
+    liz_distances::is_similar(form, part, threshold)
+  }));
+}
+
 pub fn kit_find_all_ask<F: Fn(&str) -> bool>(forms: &Vec<String>, ask: F) -> Vec<usize> {
   rux_dbg_call!();
   let mut result = Vec::new();
@@ -197,9 +206,9 @@ pub fn kit_build(forms: &Vec<String>) -> String {
   rux_dbg_reav!(result);
 }
 
-pub fn kit_write(forms: &Vec<String>, path: &str) -> Result<(), LizError> {
-  rux_dbg_call!(path);
+pub fn kit_write(forms: &Vec<String>, path: &str, atomic: bool) -> Result<(), LizError> {
+  rux_dbg_call!(path, atomic);
   let contents = kit_build(forms);
   rux_dbg_step!(contents);
-  rux_texts::write(path, contents).map_err(|err| rux_dbg_bleb!(err))
+  liz_files::write(path, contents, atomic).map_err(|err| rux_dbg_bleb!(err))
 }
@@ -3,6 +3,7 @@ use rlua::UserData;
 use rubx::rux_texts;
 use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
 
+use crate::liz_distances;
 use crate::liz_forms;
 use crate::liz_logic::{self, Sense};
 use crate::LizError;
@@ -32,6 +33,18 @@ pub fn group_likely_not(term: String) -> GroupIf {
     rux_dbg_reav!(GroupIf::Likely(Sense::Swap, term));
 }
 
+pub fn group_similar(term: String, threshold: Option<f64>) -> GroupIf {
+    rux_dbg_call!(threshold);
+    let threshold = threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD);
+    rux_dbg_reav!(GroupIf::Similar(Sense::Same, term, threshold));
+}
+
+pub fn group_similar_not(term: String, threshold: Option<f64>) -> GroupIf {
+    rux_dbg_call!(threshold);
+    let threshold = threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD);
+    rux_dbg_reav!(GroupIf::Similar(Sense::Swap, term, threshold));
+}
+
 pub fn group_regex(phrase: String) -> GroupIf {
     rux_dbg_call!();
     rux_dbg_reav!(GroupIf::Regex(Sense::Same, phrase));
@@ -145,6 +158,7 @@ impl UserData for GroupPair {}
 pub enum GroupIf {
     Equals(Sense, String),
     Likely(Sense, String),
+    Similar(Sense, String, f64),
     Regex(Sense, String),
     Imply(Sense, GroupImply),
 }
@@ -156,6 +170,11 @@ impl GroupIf {
         Ok(match self {
             GroupIf::Equals(sense, equals) => Box::new(GroupEquals { sense, equals }),
             GroupIf::Likely(sense, likely) => Box::new(GroupLikely { sense, likely }),
+            GroupIf::Similar(sense, similar, threshold) => Box::new(GroupSimilar {
+                sense,
+                similar,
+                threshold,
+            }),
             GroupIf::Regex(sense, phrase) => Box::new(GroupRegex {
                 sense,
                 regex: Regex::new(phrase.as_ref())?,
@@ -193,6 +212,22 @@ impl GroupTrait for GroupLikely {
     }
 }
 
+#[derive(Debug)]
+pub struct GroupSimilar {
+    sense: Sense,
+    similar: String,
+    threshold: f64,
+}
+
+impl GroupTrait for GroupSimilar {
+    fn checks(&self, term: &str) -> bool {
+        liz_logic::sense_apply(
+            self.sense,
+            liz_distances::is_similar(term, &self.similar, self.threshold),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct GroupRegex {
     sense: Sense,
@@ -0,0 +1,361 @@
+use rlua::{Context, Table};
+use rubx::rux_paths;
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+use sha1::Digest;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::liz_paths;
+use crate::utils;
+use crate::LizError;
+
+pub enum Hasher {
+  Sha256(sha2::Sha256),
+  Sha1(sha1::Sha1),
+  Md5(md5::Md5),
+  Blake3(Box<blake3::Hasher>),
+  Crc32(crc32fast::Hasher),
+}
+
+impl Hasher {
+  pub fn new(algo: &str) -> Result<Hasher, LizError> {
+    match algo.trim().to_lowercase().replace('-', "").as_str() {
+      "sha256" => Ok(Hasher::Sha256(sha2::Sha256::new())),
+      "sha1" => Ok(Hasher::Sha1(sha1::Sha1::new())),
+      "md5" => Ok(Hasher::Md5(md5::Md5::new())),
+      "blake3" => Ok(Hasher::Blake3(Box::new(blake3::Hasher::new()))),
+      "crc32" => Ok(Hasher::Crc32(crc32fast::Hasher::new())),
+      _ => Err(rux_dbg_erro!(format!("Unknown hash algorithm: {}", algo))),
+    }
+  }
+
+  pub fn update(&mut self, data: &[u8]) {
+    match self {
+      Hasher::Sha256(hasher) => hasher.update(data),
+      Hasher::Sha1(hasher) => hasher.update(data),
+      Hasher::Md5(hasher) => hasher.update(data),
+      Hasher::Blake3(hasher) => {
+        hasher.update(data);
+      }
+      Hasher::Crc32(hasher) => hasher.update(data),
+    }
+  }
+
+  pub fn finish(self) -> String {
+    match self {
+      Hasher::Sha256(hasher) => get_hex(&hasher.finalize()),
+      Hasher::Sha1(hasher) => get_hex(&hasher.finalize()),
+      Hasher::Md5(hasher) => get_hex(&hasher.finalize()),
+      Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+      Hasher::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+    }
+  }
+}
+
+pub fn hash_text(text: &str, algo: &str) -> Result<String, LizError> {
+  rux_dbg_call!(text, algo);
+  let mut hasher = Hasher::new(algo).map_err(|err| rux_dbg_bleb!(err))?;
+  hasher.update(text.as_bytes());
+  rux_dbg_reav!(Ok(hasher.finish()));
+}
+
+pub fn hash_file(lane: Context, path: &str, algo: &str) -> Result<String, LizError> {
+  rux_dbg_call!(path, algo);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  rux_dbg_reav!(hash_path(&path, algo));
+}
+
+pub fn hash_path(path: &str, algo: &str) -> Result<String, LizError> {
+  rux_dbg_call!(path, algo);
+  let mut hasher = Hasher::new(algo).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut file = File::open(path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let mut buffer = vec![0u8; 64 * 1024];
+  loop {
+    let read = file
+      .read(&mut buffer)
+      .map_err(|err| rux_dbg_erro!(err, path))?;
+    if read == 0 {
+      break;
+    }
+    hasher.update(&buffer[..read]);
+  }
+  rux_dbg_reav!(Ok(hasher.finish()));
+}
+
+pub struct HashSpec {
+  pub algo: String,
+  pub ignore: bool,
+  pub hidden: bool,
+  pub exclude: Vec<String>,
+}
+
+pub fn hash_spec(opts: Option<Table>, algo: &str) -> Result<HashSpec, LizError> {
+  rux_dbg_call!(algo);
+  let walk = liz_paths::WalkSpec::default();
+  let mut result = HashSpec {
+    algo: algo.into(),
+    ignore: walk.ignore,
+    hidden: walk.hidden,
+    exclude: Vec::new(),
+  };
+  if let Some(opts) = opts {
+    if let Some(algo) = opts.get("algo").map_err(|err| rux_dbg_erro!(err))? {
+      result.algo = algo;
+    }
+    if let Some(ignore) = opts.get("ignore").map_err(|err| rux_dbg_erro!(err))? {
+      result.ignore = ignore;
+    }
+    if let Some(hidden) = opts.get("hidden").map_err(|err| rux_dbg_erro!(err))? {
+      result.hidden = hidden;
+    }
+    if let Some(exclude) = opts.get("exclude").map_err(|err| rux_dbg_erro!(err))? {
+      result.exclude = exclude;
+    }
+  }
+  Hasher::new(&result.algo).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn hash_dir(lane: Context, dir: &str, spec: &HashSpec) -> Result<String, LizError> {
+  rux_dbg_call!(dir);
+  let dir = utils::get_stacked_path(lane, dir).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dir);
+  let mut hasher = Hasher::new(&spec.algo).map_err(|err| rux_dbg_bleb!(err))?;
+  for (relative, path) in get_hash_files(&dir, spec, None).map_err(|err| rux_dbg_bleb!(err))? {
+    let hash = hash_path(&path, &spec.algo).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(relative, hash);
+    hasher.update(relative.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(hash.as_bytes());
+    hasher.update(b"\n");
+  }
+  rux_dbg_reav!(Ok(hasher.finish()));
+}
+
+pub fn manifest_write(
+  lane: Context,
+  dir: &str,
+  manifest: Option<String>,
+  spec: &HashSpec,
+) -> Result<String, LizError> {
+  rux_dbg_call!(dir, manifest);
+  let dir = utils::get_stacked_path(lane, dir).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dir);
+  let manifest = match manifest {
+    Some(manifest) => {
+      utils::get_stacked_path(lane, &manifest).map_err(|err| rux_dbg_bleb!(err))?
+    }
+    None => rux_paths::path_join(&dir, &get_manifest_name(&spec.algo))
+      .map_err(|err| rux_dbg_bleb!(err))?,
+  };
+  rux_dbg_step!(manifest);
+  let mut contents = String::new();
+  for (relative, path) in
+    get_hash_files(&dir, spec, Some(&manifest)).map_err(|err| rux_dbg_bleb!(err))?
+  {
+    let hash = hash_path(&path, &spec.algo).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(relative, hash);
+    contents.push_str(&format!("{}  {}\n", hash, relative));
+  }
+  std::fs::write(&manifest, contents).map_err(|err| rux_dbg_erro!(err, manifest))?;
+  rux_dbg_reav!(Ok(manifest));
+}
+
+pub fn manifest_verify<'a>(
+  lane: Context<'a>,
+  manifest: &str,
+  algo: Option<String>,
+) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(manifest, algo);
+  let manifest = utils::get_stacked_path(lane, manifest).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(manifest);
+  let algo = algo.unwrap_or_else(|| get_manifest_algo(&manifest));
+  rux_dbg_step!(algo);
+  let dir = rux_paths::path_parent(&manifest).map_err(|err| rux_dbg_bleb!(err))?;
+  let source =
+    std::fs::read_to_string(&manifest).map_err(|err| rux_dbg_erro!(err, manifest))?;
+  let mut checked = 0;
+  let mut changed: Vec<String> = Vec::new();
+  let mut missing: Vec<String> = Vec::new();
+  for (index, line) in source.lines().enumerate() {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (expected, relative) = match line.find(' ') {
+      Some(split) => (
+        &line[..split],
+        line[split + 1..].trim_start_matches([' ', '*']),
+      ),
+      None => {
+        return Err(rux_dbg_erro!(format!(
+          "Invalid manifest line {} on {}",
+          index + 1,
+          manifest
+        )))
+      }
+    };
+    if !is_inside_path(relative) {
+      return Err(rux_dbg_erro!(format!(
+        "The manifest line {} on {} has a path out of its dir: {}",
+        index + 1,
+        manifest,
+        relative
+      )));
+    }
+    let path = rux_paths::path_join(&dir, relative).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(expected, path);
+    checked += 1;
+    if !Path::new(&path).is_file() {
+      missing.push(relative.into());
+      continue;
+    }
+    let actual = hash_path(&path, &algo).map_err(|err| rux_dbg_bleb!(err))?;
+    if !actual.eq_ignore_ascii_case(expected) {
+      changed.push(relative.into());
+    }
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("ok", changed.is_empty() && missing.is_empty())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("checked", checked)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("changed", changed)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("missing", missing)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_hash_files(
+  dir: &str,
+  spec: &HashSpec,
+  skip: Option<&str>,
+) -> Result<Vec<(String, String)>, LizError> {
+  rux_dbg_call!(dir, skip);
+  let builder = liz_paths::walk_builder(dir, spec.hidden, spec.ignore);
+  let mut result: Vec<(String, String)> = Vec::new();
+  for entry in builder.build() {
+    let entry = entry.map_err(|err| rux_dbg_erro!(err))?;
+    if !entry
+      .file_type()
+      .map(|kind| kind.is_file())
+      .unwrap_or(false)
+    {
+      continue;
+    }
+    let path = entry.path();
+    if let Some(skip) = skip {
+      if path == Path::new(skip) {
+        continue;
+      }
+    }
+    let relative = path
+      .strip_prefix(dir)
+      .map_err(|err| rux_dbg_erro!(err))?
+      .components()
+      .map(|part| part.as_os_str().to_string_lossy().to_string())
+      .collect::<Vec<String>>()
+      .join("/");
+    let mut excluded = false;
+    for pattern in &spec.exclude {
+      if liz_paths::glob_match(pattern, &relative).map_err(|err| rux_dbg_bleb!(err))? {
+        excluded = true;
+        break;
+      }
+    }
+    if !excluded {
+      result.push((relative, path.display().to_string()));
+    }
+  }
+  result.sort();
+  rux_dbg_reav!(Ok(result));
+}
+
+fn is_inside_path(relative: &str) -> bool {
+  !relative.is_empty()
+    && Path::new(relative)
+      .components()
+      .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+}
+
+fn get_manifest_name(algo: &str) -> String {
+  format!("{}SUMS", algo.trim().to_uppercase().replace('-', ""))
+}
+
+fn get_manifest_algo(manifest: &str) -> String {
+  let name = rux_paths::path_name(manifest).to_uppercase();
+  for algo in ["SHA256", "SHA1", "MD5", "BLAKE3", "CRC32"] {
+    if name.starts_with(algo) {
+      return algo.to_lowercase();
+    }
+  }
+  String::from("sha256")
+}
+
+fn get_hex(data: &[u8]) -> String {
+  data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn text_hashes_match_the_known_digests() {
+    let hash = |algo: &str| hash_text("abc", algo).unwrap();
+    assert_eq!(
+      hash("sha256"),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(hash("SHA-256"), hash("sha256"));
+    assert_eq!(hash("sha1"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(hash("md5"), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(
+      hash("blake3"),
+      "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+    assert_eq!(hash("crc32"), "352441c2");
+    assert!(hash_text("abc", "sha512").is_err());
+  }
+
+  #[test]
+  fn file_hashes_match_the_text_ones() {
+    let path = std::env::temp_dir().join(format!("liz_hashes_file_{}", std::process::id()));
+    let text = "a".repeat(100 * 1024);
+    std::fs::write(&path, &text).unwrap();
+    let path = path.display().to_string();
+    assert_eq!(
+      hash_path(&path, "sha1").unwrap(),
+      hash_text(&text, "sha1").unwrap()
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(hash_path(&path, "sha1").is_err());
+  }
+
+  #[test]
+  fn manifest_paths_stay_inside_their_dir() {
+    assert!(is_inside_path("a/b.txt"));
+    assert!(is_inside_path("./a"));
+    assert!(!is_inside_path(""));
+    assert!(!is_inside_path("../a"));
+    assert!(!is_inside_path("a/../../b"));
+    assert!(!is_inside_path("/etc/passwd"));
+  }
+
+  #[test]
+  fn manifest_names_tell_their_algorithm() {
+    assert_eq!(get_manifest_name("sha-256"), "SHA256SUMS");
+    assert_eq!(get_manifest_algo("dir/MD5SUMS"), "md5");
+    assert_eq!(get_manifest_algo("blake3sums.txt"), "blake3");
+    assert_eq!(get_manifest_algo("checksums.txt"), "sha256");
+  }
+}
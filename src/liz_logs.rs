@@ -0,0 +1,391 @@
+use once_cell::sync::Lazy;
+use rlua::{Context, Table, Value as LuaValue};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+use serde_json::Value as JsonValue;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::utils;
+use crate::LizError;
+
+pub const LOG_LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+
+// The lines go to stderr by default so they do not mix with the output of the
+// script, the sink can send them to stdout or nowhere, apart from the archive.
+pub const LOG_SINKS: [&str; 3] = ["stderr", "stdout", "none"];
+
+pub const LOG_RESERVED: [&str; 4] = ["time", "level", "script", "message"];
+
+pub const ARCHIVE_NAME: &str = "archive.log";
+
+pub struct LogConfig {
+  pub level: usize,
+  pub json: bool,
+  pub sink: usize,
+  pub archive: bool,
+  pub archive_path: String,
+  pub archive_max_size: u64,
+  pub archive_max_files: usize,
+}
+
+static LOG_CONFIG: Lazy<Mutex<LogConfig>> = Lazy::new(|| Mutex::new(log_config_from_envs()));
+
+fn log_config_from_envs() -> LogConfig {
+  let mut result = LogConfig {
+    level: 1,
+    json: false,
+    sink: 0,
+    archive: false,
+    archive_path: ARCHIVE_NAME.into(),
+    archive_max_size: 10 * 1024 * 1024,
+    archive_max_files: 5,
+  };
+  if let Ok(level) = std::env::var("LIZ_LOG_LEVEL") {
+    if let Ok(level) = get_level_index(&level) {
+      result.level = level;
+    }
+  }
+  if let Ok(format) = std::env::var("LIZ_LOG_FORMAT") {
+    result.json = format.trim().eq_ignore_ascii_case("json");
+  }
+  if let Ok(sink) = std::env::var("LIZ_LOG_SINK") {
+    if let Ok(sink) = get_sink_index(&sink) {
+      result.sink = sink;
+    }
+  }
+  if let Ok(size) = std::env::var("LIZ_LOG_MAX_SIZE") {
+    if let Ok(size) = size.trim().parse::<u64>() {
+      result.archive_max_size = size;
+    }
+  }
+  if let Ok(files) = std::env::var("LIZ_LOG_MAX_FILES") {
+    if let Ok(files) = files.trim().parse::<usize>() {
+      result.archive_max_files = files;
+    }
+  }
+  result
+}
+
+pub fn set_log_level(level: &str) -> Result<(), LizError> {
+  rux_dbg_call!(level);
+  let index = get_level_index(level).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  config.level = index;
+  Ok(())
+}
+
+pub fn get_log_level() -> Result<String, LizError> {
+  rux_dbg_call!();
+  let config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(LOG_LEVELS[config.level].into()));
+}
+
+pub fn set_log_format(format: &str) -> Result<(), LizError> {
+  rux_dbg_call!(format);
+  let json = match format.trim().to_lowercase().as_str() {
+    "text" => false,
+    "json" => true,
+    _ => return Err(rux_dbg_erro!(format!("Unknown log format: {}", format))),
+  };
+  let mut config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  config.json = json;
+  Ok(())
+}
+
+pub fn set_log_sink(sink: &str) -> Result<(), LizError> {
+  rux_dbg_call!(sink);
+  let index = get_sink_index(sink).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  config.sink = index;
+  Ok(())
+}
+
+// Rubx opens its archive on the working dir of the start, so the path is fixed
+// here and a later change of the working dir does not move it.
+pub fn set_log_archive(archive: bool) -> Result<(), LizError> {
+  rux_dbg_call!(archive);
+  let mut config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  if archive && !config.archive {
+    let working_dir = std::env::current_dir().map_err(|err| rux_dbg_erro!(err))?;
+    config.archive_path = working_dir.join(ARCHIVE_NAME).display().to_string();
+    rux_dbg_step!(config.archive_path);
+  }
+  config.archive = archive;
+  Ok(())
+}
+
+pub fn set_log_rotation(max_size: u64, max_files: usize) -> Result<(), LizError> {
+  rux_dbg_call!(max_size, max_files);
+  let mut config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  config.archive_max_size = max_size;
+  config.archive_max_files = max_files;
+  Ok(())
+}
+
+pub fn log_debug(lane: Context, message: &str, fields: Option<Table>) -> Result<(), LizError> {
+  rux_dbg_call!(message);
+  rux_dbg_reav!(log(lane, 0, message, fields));
+}
+
+pub fn log_info(lane: Context, message: &str, fields: Option<Table>) -> Result<(), LizError> {
+  rux_dbg_call!(message);
+  rux_dbg_reav!(log(lane, 1, message, fields));
+}
+
+pub fn log_warn(lane: Context, message: &str, fields: Option<Table>) -> Result<(), LizError> {
+  rux_dbg_call!(message);
+  rux_dbg_reav!(log(lane, 2, message, fields));
+}
+
+pub fn log_error(lane: Context, message: &str, fields: Option<Table>) -> Result<(), LizError> {
+  rux_dbg_call!(message);
+  rux_dbg_reav!(log(lane, 3, message, fields));
+}
+
+pub fn log(
+  lane: Context,
+  level: usize,
+  message: &str,
+  fields: Option<Table>,
+) -> Result<(), LizError> {
+  rux_dbg_call!(level, message);
+  let config = LOG_CONFIG.lock().map_err(|err| rux_dbg_erro!(err))?;
+  if level < config.level {
+    return Ok(());
+  }
+  let fields = match fields {
+    Some(fields) => get_log_fields(fields).map_err(|err| rux_dbg_bleb!(err))?,
+    None => Vec::new(),
+  };
+  rux_dbg_step!(fields);
+  let script = get_log_script(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(script);
+  let now = chrono::Local::now();
+  let line = if config.json {
+    let mut line = format!(
+      "{{\"time\":{},\"level\":{},\"script\":{},\"message\":{}",
+      serde_json::to_string(&now.to_rfc3339()).map_err(|err| rux_dbg_erro!(err))?,
+      serde_json::to_string(LOG_LEVELS[level]).map_err(|err| rux_dbg_erro!(err))?,
+      serde_json::to_string(&script).map_err(|err| rux_dbg_erro!(err))?,
+      serde_json::to_string(message).map_err(|err| rux_dbg_erro!(err))?
+    );
+    for (name, value) in &fields {
+      let name = serde_json::to_string(name).map_err(|err| rux_dbg_erro!(err))?;
+      let value = serde_json::to_string(value).map_err(|err| rux_dbg_erro!(err))?;
+      line.push_str(&format!(",{}:{}", name, value));
+    }
+    line.push('}');
+    line
+  } else {
+    let mut line = format!(
+      "{} {:5} [{}] {}",
+      now.format("%Y-%m-%d %H:%M:%S%.3f"),
+      LOG_LEVELS[level].to_uppercase(),
+      script,
+      message
+    );
+    for (name, value) in &fields {
+      let value = get_text_value(value).map_err(|err| rux_dbg_bleb!(err))?;
+      line.push_str(&format!(" {}={}", name, value));
+    }
+    line
+  };
+  rux_dbg_step!(line);
+  match config.sink {
+    0 => eprintln!("{}", line),
+    1 => println!("{}", line),
+    _ => {}
+  }
+  if config.archive {
+    put_archive_line(&config, &line).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  Ok(())
+}
+
+fn get_level_index(level: &str) -> Result<usize, LizError> {
+  let level = level.trim().to_lowercase();
+  let level = if level == "warning" {
+    "warn".into()
+  } else {
+    level
+  };
+  match LOG_LEVELS.iter().position(|known| *known == level) {
+    Some(index) => Ok(index),
+    None => Err(rux_dbg_erro!(format!("Unknown log level: {}", level))),
+  }
+}
+
+fn get_sink_index(sink: &str) -> Result<usize, LizError> {
+  let sink = sink.trim().to_lowercase();
+  match LOG_SINKS.iter().position(|known| *known == sink) {
+    Some(index) => Ok(index),
+    None => Err(rux_dbg_erro!(format!("Unknown log sink: {}", sink))),
+  }
+}
+
+fn get_log_fields(fields: Table) -> Result<Vec<(String, JsonValue)>, LizError> {
+  rux_dbg_call!();
+  let mut result = Vec::new();
+  for pair in fields.pairs::<LuaValue, LuaValue>() {
+    let (name, value) = pair.map_err(|err| rux_dbg_erro!(err))?;
+    let name = match name {
+      LuaValue::String(name) => name.to_str().map_err(|err| rux_dbg_erro!(err))?.to_string(),
+      LuaValue::Integer(name) => format!("{}", name),
+      LuaValue::Number(name) => format!("{}", name),
+      _ => return Err(rux_dbg_erro!("The log fields must have string keys")),
+    };
+    if LOG_RESERVED.contains(&name.as_str()) {
+      return Err(rux_dbg_erro!(format!(
+        "The log field {} is reserved, use another name",
+        name
+      )));
+    }
+    let value = utils::to_json_value(value).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(name, value);
+    result.push((name, value));
+  }
+  result.sort_by(|first, second| first.0.cmp(&second.0));
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_log_script(lane: Context) -> Result<String, LizError> {
+  rux_dbg_call!();
+  let liz: Table = lane
+    .globals()
+    .get("Liz")
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let race_path: Option<String> = liz.get("race_path").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(race_path) = race_path {
+    rux_dbg_reav!(Ok(race_path));
+  }
+  let rise_path: Option<String> = liz.get("rise_path").map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(rise_path.unwrap_or_default()));
+}
+
+fn get_text_value(value: &JsonValue) -> Result<String, LizError> {
+  if let JsonValue::String(text) = value {
+    if !text.is_empty()
+      && !text.contains(|ch: char| ch.is_whitespace() || ch == '"' || ch == '=')
+    {
+      return Ok(text.clone());
+    }
+  }
+  serde_json::to_string(value).map_err(|err| rux_dbg_erro!(err))
+}
+
+fn put_archive_line(config: &LogConfig, line: &str) -> Result<(), LizError> {
+  rux_dbg_call!(line);
+  let path = &config.archive_path;
+  if let Ok(metadata) = std::fs::metadata(path) {
+    let size = metadata.len() + line.len() as u64 + 1;
+    if config.archive_max_size > 0 && size > config.archive_max_size {
+      rotate_archive(path, config.archive_max_files).map_err(|err| rux_dbg_bleb!(err))?;
+    }
+  }
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  writeln!(file, "{}", line).map_err(|err| rux_dbg_erro!(err))?;
+  Ok(())
+}
+
+// The archive is copied and truncated in place instead of renamed, because rubx
+// keeps it open on append and must go on writing on the same file.
+fn rotate_archive(path: &str, max_files: usize) -> Result<(), LizError> {
+  rux_dbg_call!(path, max_files);
+  if max_files > 0 {
+    let oldest = format!("{}.{}", path, max_files);
+    if std::path::Path::new(&oldest).exists() {
+      std::fs::remove_file(&oldest).map_err(|err| rux_dbg_erro!(err, oldest))?;
+    }
+    for index in (1..max_files).rev() {
+      let from = format!("{}.{}", path, index);
+      if std::path::Path::new(&from).exists() {
+        let to = format!("{}.{}", path, index + 1);
+        rux_dbg_tell!(from, to);
+        std::fs::rename(&from, &to).map_err(|err| rux_dbg_erro!(err, from))?;
+      }
+    }
+    let first = format!("{}.1", path);
+    std::fs::copy(path, &first).map_err(|err| rux_dbg_erro!(err, first))?;
+  }
+  OpenOptions::new()
+    .write(true)
+    .open(path)
+    .and_then(|file| file.set_len(0))
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  #[test]
+  fn levels_and_sinks_are_known_by_name() {
+    assert_eq!(get_level_index(" DEBUG ").unwrap(), 0);
+    assert_eq!(get_level_index("warning").unwrap(), 2);
+    assert_eq!(get_level_index("error").unwrap(), 3);
+    assert!(get_level_index("trace").is_err());
+    assert_eq!(get_sink_index("Stdout").unwrap(), 1);
+    assert_eq!(get_sink_index("none").unwrap(), 2);
+    assert!(get_sink_index("file").is_err());
+  }
+
+  #[test]
+  fn text_values_are_quoted_when_needed() {
+    let get_text = |value: JsonValue| get_text_value(&value).unwrap();
+    assert_eq!(get_text(JsonValue::from("plain")), "plain");
+    assert_eq!(get_text(JsonValue::from("two words")), "\"two words\"");
+    assert_eq!(get_text(JsonValue::from("a=b")), "\"a=b\"");
+    assert_eq!(get_text(JsonValue::from("")), "\"\"");
+    assert_eq!(get_text(JsonValue::from(3)), "3");
+    assert_eq!(get_text(serde_json::json!([1, "x"])), "[1,\"x\"]");
+  }
+
+  #[test]
+  fn fields_are_sorted_and_reserved_names_refused() {
+    Lua::new().context(|lane| {
+      let fields: Table = lane
+        .load("{ zeta = 1, alpha = 'a', list = { 1, 2 } }")
+        .eval()
+        .unwrap();
+      let fields = get_log_fields(fields).unwrap();
+      let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+      assert_eq!(names, vec!["alpha", "list", "zeta"]);
+      assert_eq!(fields[1].1, serde_json::json!([1, 2]));
+      for reserved in &LOG_RESERVED {
+        let fields: Table = lane
+          .load(&format!("{{ {} = 1 }}", reserved))
+          .eval()
+          .unwrap();
+        assert!(get_log_fields(fields).is_err());
+      }
+      let fields: Table = lane.load("{ [true] = 1 }").eval().unwrap();
+      assert!(get_log_fields(fields).is_err());
+    });
+  }
+
+  #[test]
+  fn archive_rotates_the_numbered_files() {
+    let dir = std::env::temp_dir().join(format!("liz_logs_rotate_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(ARCHIVE_NAME).display().to_string();
+    std::fs::write(&path, "third").unwrap();
+    std::fs::write(format!("{}.1", path), "second").unwrap();
+    std::fs::write(format!("{}.2", path), "first").unwrap();
+    rotate_archive(&path, 2).unwrap();
+    let read = |path: String| std::fs::read_to_string(path).unwrap();
+    assert_eq!(read(path.clone()), "");
+    assert_eq!(read(format!("{}.1", path)), "third");
+    assert_eq!(read(format!("{}.2", path)), "second");
+    assert!(!std::path::Path::new(&format!("{}.3", path)).exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,555 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ignore::WalkBuilder;
+use rlua::{Context, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::liz_paths;
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone, Default)]
+pub struct PackSpec {
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+  pub prefix: String,
+  pub gzip: Option<bool>,
+}
+
+pub fn pack_spec(opts: Option<Table>) -> Result<PackSpec, LizError> {
+  rux_dbg_call!();
+  let mut result = PackSpec::default();
+  if let Some(opts) = opts {
+    if let Some(include) = opts.get("include").map_err(|err| rux_dbg_erro!(err))? {
+      result.include = include;
+    }
+    if let Some(exclude) = opts.get("exclude").map_err(|err| rux_dbg_erro!(err))? {
+      result.exclude = exclude;
+    }
+    if let Some(prefix) = opts
+      .get::<_, Option<String>>("prefix")
+      .map_err(|err| rux_dbg_erro!(err))?
+    {
+      result.prefix = prefix.trim_matches('/').into();
+    }
+    result.gzip = opts.get("gzip").map_err(|err| rux_dbg_erro!(err))?;
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn zip(
+  lane: Context,
+  sources: Vec<String>,
+  dest: &str,
+  spec: &PackSpec,
+) -> Result<usize, LizError> {
+  rux_dbg_call!(sources, dest);
+  let dest = utils::get_stacked_path(lane, dest).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dest);
+  let entries =
+    get_pack_entries(lane, &sources, spec, &dest).map_err(|err| rux_dbg_bleb!(err))?;
+  let file = File::create(&dest).map_err(|err| rux_dbg_erro!(err, dest))?;
+  let mut writer = zip::ZipWriter::new(BufWriter::new(file));
+  for entry in &entries {
+    rux_dbg_tell!(entry.name);
+    let metadata =
+      std::fs::metadata(&entry.path).map_err(|err| rux_dbg_erro!(err, entry.name))?;
+    let options = zip::write::FileOptions::default()
+      .compression_method(zip::CompressionMethod::Deflated)
+      .unix_permissions(get_mode(&metadata))
+      .last_modified_time(get_zip_time(&metadata));
+    if entry.is_dir {
+      writer
+        .add_directory(format!("{}/", entry.name), options)
+        .map_err(|err| rux_dbg_erro!(err, entry.name))?;
+    } else {
+      writer
+        .start_file(entry.name.as_str(), options)
+        .map_err(|err| rux_dbg_erro!(err, entry.name))?;
+      let mut source = File::open(&entry.path).map_err(|err| rux_dbg_erro!(err, entry.name))?;
+      std::io::copy(&mut source, &mut writer).map_err(|err| rux_dbg_erro!(err, entry.name))?;
+    }
+  }
+  writer.finish().map_err(|err| rux_dbg_erro!(err, dest))?;
+  rux_dbg_reav!(Ok(entries.len()));
+}
+
+pub fn tar(
+  lane: Context,
+  sources: Vec<String>,
+  dest: &str,
+  spec: &PackSpec,
+) -> Result<usize, LizError> {
+  rux_dbg_call!(sources, dest);
+  let dest = utils::get_stacked_path(lane, dest).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dest);
+  let gzip = spec
+    .gzip
+    .unwrap_or_else(|| dest.ends_with(".gz") || dest.ends_with(".tgz"));
+  rux_dbg_step!(gzip);
+  let entries =
+    get_pack_entries(lane, &sources, spec, &dest).map_err(|err| rux_dbg_bleb!(err))?;
+  let file = File::create(&dest).map_err(|err| rux_dbg_erro!(err, dest))?;
+  let writer: Box<dyn Write> = if gzip {
+    Box::new(GzEncoder::new(BufWriter::new(file), Compression::default()))
+  } else {
+    Box::new(BufWriter::new(file))
+  };
+  let mut builder = tar::Builder::new(writer);
+  for entry in &entries {
+    rux_dbg_tell!(entry.name);
+    if entry.is_dir {
+      builder
+        .append_dir(&entry.name, &entry.path)
+        .map_err(|err| rux_dbg_erro!(err, entry.name))?;
+    } else {
+      builder
+        .append_path_with_name(&entry.path, &entry.name)
+        .map_err(|err| rux_dbg_erro!(err, entry.name))?;
+    }
+  }
+  let mut writer = builder
+    .into_inner()
+    .map_err(|err| rux_dbg_erro!(err, dest))?;
+  writer.flush().map_err(|err| rux_dbg_erro!(err, dest))?;
+  drop(writer);
+  rux_dbg_reav!(Ok(entries.len()));
+}
+
+pub fn unpack(
+  lane: Context,
+  archive: &str,
+  dest: &str,
+  spec: &PackSpec,
+) -> Result<usize, LizError> {
+  rux_dbg_call!(archive, dest);
+  let archive = utils::get_stacked_path(lane, archive).map_err(|err| rux_dbg_bleb!(err))?;
+  let dest = utils::get_stacked_path(lane, dest).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(archive, dest);
+  std::fs::create_dir_all(&dest).map_err(|err| rux_dbg_erro!(err, dest))?;
+  let mut count = 0;
+  match get_pack_kind(&archive).map_err(|err| rux_dbg_bleb!(err))? {
+    PackKind::Zip => {
+      let file = File::open(&archive).map_err(|err| rux_dbg_erro!(err, archive))?;
+      let mut reader = zip::ZipArchive::new(BufReader::new(file))
+        .map_err(|err| rux_dbg_erro!(err, archive))?;
+      for index in 0..reader.len() {
+        let mut entry = reader
+          .by_index(index)
+          .map_err(|err| rux_dbg_erro!(err, archive))?;
+        let name = entry
+          .enclosed_name()
+          .map(|name| name.to_path_buf())
+          .ok_or_else(|| {
+            rux_dbg_erro!(format!("Unsafe path on the archive: {}", entry.name()))
+          })?;
+        let slashed = get_slashed_name(&name);
+        if !is_pack_selected(&slashed, spec).map_err(|err| rux_dbg_bleb!(err))? {
+          continue;
+        }
+        rux_dbg_tell!(slashed);
+        let target = Path::new(&dest).join(&name);
+        if entry.is_dir() {
+          std::fs::create_dir_all(&target).map_err(|err| rux_dbg_erro!(err, slashed))?;
+        } else {
+          if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| rux_dbg_erro!(err, slashed))?;
+          }
+          let mut output = File::create(&target).map_err(|err| rux_dbg_erro!(err, slashed))?;
+          std::io::copy(&mut entry, &mut output).map_err(|err| rux_dbg_erro!(err, slashed))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+          use std::os::unix::fs::PermissionsExt;
+          std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o7777))
+            .map_err(|err| rux_dbg_erro!(err, slashed))?;
+        }
+        count += 1;
+      }
+    }
+    PackKind::Tar(gzip) => {
+      let mut reader =
+        tar::Archive::new(get_tar_reader(&archive, gzip).map_err(|err| rux_dbg_bleb!(err))?);
+      reader.set_preserve_permissions(true);
+      reader.set_preserve_mtime(true);
+      for entry in reader
+        .entries()
+        .map_err(|err| rux_dbg_erro!(err, archive))?
+      {
+        let mut entry = entry.map_err(|err| rux_dbg_erro!(err, archive))?;
+        let name = entry
+          .path()
+          .map_err(|err| rux_dbg_erro!(err, archive))?
+          .to_path_buf();
+        let slashed = get_slashed_name(&name);
+        if !is_pack_selected(&slashed, spec).map_err(|err| rux_dbg_bleb!(err))? {
+          continue;
+        }
+        rux_dbg_tell!(slashed);
+        let unpacked = entry
+          .unpack_in(&dest)
+          .map_err(|err| rux_dbg_erro!(err, slashed))?;
+        if !unpacked {
+          return Err(rux_dbg_erro!(format!(
+            "Unsafe path on the archive: {}",
+            slashed
+          )));
+        }
+        count += 1;
+      }
+    }
+  }
+  rux_dbg_reav!(Ok(count));
+}
+
+pub fn archive_list<'a>(lane: Context<'a>, archive: &str) -> Result<Vec<Table<'a>>, LizError> {
+  rux_dbg_call!(archive);
+  let archive = utils::get_stacked_path(lane, archive).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(archive);
+  let mut result = Vec::new();
+  match get_pack_kind(&archive).map_err(|err| rux_dbg_bleb!(err))? {
+    PackKind::Zip => {
+      let file = File::open(&archive).map_err(|err| rux_dbg_erro!(err, archive))?;
+      let mut reader = zip::ZipArchive::new(BufReader::new(file))
+        .map_err(|err| rux_dbg_erro!(err, archive))?;
+      for index in 0..reader.len() {
+        let entry = reader
+          .by_index(index)
+          .map_err(|err| rux_dbg_erro!(err, archive))?;
+        let kind = if entry.is_dir() { "dir" } else { "file" };
+        let path = entry.name().trim_end_matches('/').to_string();
+        result.push(
+          get_list_entry(lane, &path, kind, entry.size(), entry.unix_mode())
+            .map_err(|err| rux_dbg_bleb!(err))?,
+        );
+      }
+    }
+    PackKind::Tar(gzip) => {
+      let mut reader =
+        tar::Archive::new(get_tar_reader(&archive, gzip).map_err(|err| rux_dbg_bleb!(err))?);
+      for entry in reader
+        .entries()
+        .map_err(|err| rux_dbg_erro!(err, archive))?
+      {
+        let entry = entry.map_err(|err| rux_dbg_erro!(err, archive))?;
+        let header = entry.header();
+        let kind = match header.entry_type() {
+          tar::EntryType::Directory => "dir",
+          tar::EntryType::Symlink => "symlink",
+          _ => "file",
+        };
+        let path = entry.path().map_err(|err| rux_dbg_erro!(err, archive))?;
+        let path = get_slashed_name(&path);
+        result.push(
+          get_list_entry(
+            lane,
+            &path,
+            kind,
+            header.size().unwrap_or(0),
+            header.mode().ok(),
+          )
+          .map_err(|err| rux_dbg_bleb!(err))?,
+        );
+      }
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+struct PackEntry {
+  name: String,
+  path: PathBuf,
+  is_dir: bool,
+}
+
+enum PackKind {
+  Zip,
+  Tar(bool),
+}
+
+fn get_pack_entries(
+  lane: Context,
+  sources: &[String],
+  spec: &PackSpec,
+  dest: &str,
+) -> Result<Vec<PackEntry>, LizError> {
+  rux_dbg_call!(sources, dest);
+  let mut result: Vec<PackEntry> = Vec::new();
+  for source in sources {
+    let source = utils::get_stacked_path(lane, source).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(source);
+    let source_path = Path::new(&source);
+    if !source_path.is_dir() {
+      let name = source_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| rux_dbg_erro!(format!("Could not get the name of {}", source)))?;
+      if is_pack_selected(&name, spec).map_err(|err| rux_dbg_bleb!(err))? {
+        result.push(PackEntry {
+          name: get_prefixed(spec, &name),
+          path: source_path.to_path_buf(),
+          is_dir: false,
+        });
+      }
+      continue;
+    }
+    let mut builder = WalkBuilder::new(&source);
+    builder
+      .standard_filters(false)
+      .sort_by_file_name(|first, second| first.cmp(second));
+    let mut excluded: Vec<String> = Vec::new();
+    for entry in builder.build() {
+      let entry = entry.map_err(|err| rux_dbg_erro!(err))?;
+      if entry.depth() == 0 || entry.path() == Path::new(dest) {
+        continue;
+      }
+      let name = get_slashed_name(
+        entry
+          .path()
+          .strip_prefix(source_path)
+          .map_err(|err| rux_dbg_erro!(err))?,
+      );
+      if excluded
+        .iter()
+        .any(|parent| name.starts_with(&format!("{}/", parent)))
+      {
+        continue;
+      }
+      let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+      if is_dir && is_pack_excluded(&name, spec).map_err(|err| rux_dbg_bleb!(err))? {
+        excluded.push(name);
+        continue;
+      }
+      if is_dir && !spec.include.is_empty() {
+        continue;
+      }
+      if !is_pack_selected(&name, spec).map_err(|err| rux_dbg_bleb!(err))? {
+        continue;
+      }
+      result.push(PackEntry {
+        name: get_prefixed(spec, &name),
+        path: entry.path().to_path_buf(),
+        is_dir,
+      });
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn is_pack_excluded(name: &str, spec: &PackSpec) -> Result<bool, LizError> {
+  for pattern in &spec.exclude {
+    if liz_paths::glob_match(pattern, name)? {
+      return Ok(true);
+    }
+  }
+  Ok(false)
+}
+
+fn is_pack_selected(name: &str, spec: &PackSpec) -> Result<bool, LizError> {
+  if is_pack_excluded(name, spec)? {
+    return Ok(false);
+  }
+  if spec.include.is_empty() {
+    return Ok(true);
+  }
+  for pattern in &spec.include {
+    if liz_paths::glob_match(pattern, name)? {
+      return Ok(true);
+    }
+  }
+  Ok(false)
+}
+
+fn get_pack_kind(archive: &str) -> Result<PackKind, LizError> {
+  rux_dbg_call!(archive);
+  let mut magic = [0u8; 4];
+  let mut file = File::open(archive).map_err(|err| rux_dbg_erro!(err, archive))?;
+  let read = file
+    .read(&mut magic)
+    .map_err(|err| rux_dbg_erro!(err, archive))?;
+  if read >= 4 && &magic == b"PK\x03\x04" {
+    rux_dbg_reav!(Ok(PackKind::Zip));
+  }
+  if read >= 2 && magic[0] == 0x1f && magic[1] == 0x8b {
+    rux_dbg_reav!(Ok(PackKind::Tar(true)));
+  }
+  rux_dbg_reav!(Ok(PackKind::Tar(false)));
+}
+
+fn get_tar_reader(archive: &str, gzip: bool) -> Result<Box<dyn Read>, LizError> {
+  let file = File::open(archive).map_err(|err| rux_dbg_erro!(err, archive))?;
+  if gzip {
+    Ok(Box::new(GzDecoder::new(BufReader::new(file))))
+  } else {
+    Ok(Box::new(BufReader::new(file)))
+  }
+}
+
+fn get_list_entry<'a>(
+  lane: Context<'a>,
+  path: &str,
+  kind: &str,
+  size: u64,
+  mode: Option<u32>,
+) -> Result<Table<'a>, LizError> {
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result.set("path", path).map_err(|err| rux_dbg_erro!(err))?;
+  result.set("kind", kind).map_err(|err| rux_dbg_erro!(err))?;
+  result.set("size", size).map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(mode) = mode {
+    result
+      .set("mode", mode & 0o7777)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("perms", format!("{:o}", mode & 0o7777))
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  Ok(result)
+}
+
+fn get_prefixed(spec: &PackSpec, name: &str) -> String {
+  if spec.prefix.is_empty() {
+    name.into()
+  } else {
+    format!("{}/{}", spec.prefix, name)
+  }
+}
+
+fn get_slashed_name(path: &Path) -> String {
+  path
+    .components()
+    .map(|part| part.as_os_str().to_string_lossy().to_string())
+    .collect::<Vec<String>>()
+    .join("/")
+}
+
+fn get_zip_time(metadata: &std::fs::Metadata) -> zip::DateTime {
+  use chrono::{Datelike, Timelike};
+  let modified = match metadata.modified() {
+    Ok(modified) => chrono::DateTime::<chrono::Local>::from(modified),
+    Err(_) => return zip::DateTime::default(),
+  };
+  zip::DateTime::from_date_and_time(
+    modified.year().clamp(1980, 2107) as u16,
+    modified.month() as u8,
+    modified.day() as u8,
+    modified.hour() as u8,
+    modified.minute() as u8,
+    modified.second().min(59) as u8,
+  )
+  .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn get_mode(metadata: &std::fs::Metadata) -> u32 {
+  use std::os::unix::fs::PermissionsExt;
+  metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn get_mode(metadata: &std::fs::Metadata) -> u32 {
+  match (metadata.is_dir(), metadata.permissions().readonly()) {
+    (true, _) => 0o755,
+    (false, true) => 0o444,
+    (false, false) => 0o644,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_spec(include: &[&str], exclude: &[&str], prefix: &str) -> PackSpec {
+    PackSpec {
+      include: include.iter().map(|pattern| pattern.to_string()).collect(),
+      exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+      prefix: prefix.into(),
+      gzip: None,
+    }
+  }
+
+  fn get_test_dir(name: &str) -> PathBuf {
+    let result =
+      std::env::temp_dir().join(format!("liz_packs_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&result);
+    for file in ["a.txt", "keep/b.txt", "target/x.o", "target/deep/y.o"] {
+      let path = result.join("src").join(file);
+      std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+      std::fs::write(&path, file).unwrap();
+    }
+    result
+  }
+
+  #[test]
+  fn selection_excludes_before_including() {
+    let spec = get_spec(&["**/*.txt"], &["keep/**"], "");
+    assert!(is_pack_selected("a.txt", &spec).unwrap());
+    assert!(is_pack_selected("docs/b.txt", &spec).unwrap());
+    assert!(!is_pack_selected("keep/b.txt", &spec).unwrap());
+    assert!(!is_pack_selected("a.rs", &spec).unwrap());
+    assert!(is_pack_selected("a.rs", &get_spec(&[], &[], "")).unwrap());
+    assert_eq!(
+      get_prefixed(&get_spec(&[], &[], "pkg"), "a.txt"),
+      "pkg/a.txt"
+    );
+  }
+
+  #[test]
+  fn entries_leave_out_excluded_dirs() {
+    let dir = get_test_dir("entries");
+    let sources = [dir.join("src").display().to_string()];
+    Lua::new().context(|lane| {
+      let spec = get_spec(&[], &["target"], "pkg");
+      let entries = get_pack_entries(lane, &sources, &spec, "").unwrap();
+      let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+      assert_eq!(names, vec!["pkg/a.txt", "pkg/keep", "pkg/keep/b.txt"]);
+      let spec = get_spec(&["**/*.o"], &[], "");
+      let entries = get_pack_entries(lane, &sources, &spec, "").unwrap();
+      let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+      assert_eq!(names, vec!["target/deep/y.o", "target/x.o"]);
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn archives_round_trip() {
+    let dir = get_test_dir("round");
+    let source = dir.join("src").display().to_string();
+    Lua::new().context(|lane| {
+      let spec = get_spec(&[], &["target"], "pkg");
+      for name in ["out.zip", "out.tar.gz", "out.tar"] {
+        let archive = dir.join(name).display().to_string();
+        let packed = if name.ends_with(".zip") {
+          zip(lane, vec![source.clone()], &archive, &spec).unwrap()
+        } else {
+          tar(lane, vec![source.clone()], &archive, &spec).unwrap()
+        };
+        assert_eq!(packed, 3);
+        let kind = get_pack_kind(&archive).unwrap();
+        match name {
+          "out.zip" => assert!(matches!(kind, PackKind::Zip)),
+          "out.tar.gz" => assert!(matches!(kind, PackKind::Tar(true))),
+          _ => assert!(matches!(kind, PackKind::Tar(false))),
+        }
+        let listed = archive_list(lane, &archive).unwrap();
+        assert_eq!(listed.len(), 3);
+        let target = dir.join(format!("{}.unpacked", name));
+        let only = get_spec(&["**/*.txt"], &[], "");
+        let unpacked = unpack(lane, &archive, &target.display().to_string(), &only).unwrap();
+        assert_eq!(unpacked, 2);
+        let read = |file: &str| std::fs::read_to_string(target.join(file)).unwrap();
+        assert_eq!(read("pkg/a.txt"), "a.txt");
+        assert_eq!(read("pkg/keep/b.txt"), "keep/b.txt");
+        assert!(!target.join("pkg/target").exists());
+      }
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,919 @@
+use ignore::{Walk, WalkBuilder};
+use regex::Regex;
+use rlua::{Context, Function, Table, Value};
+use rubx::rux_paths;
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::utils;
+use crate::LizError;
+
+pub fn glob(lane: Context, patterns: Vec<String>) -> Result<Vec<String>, LizError> {
+  rux_dbg_call!(patterns);
+  let mut includes: Vec<Glob> = Vec::new();
+  let mut excludes: Vec<Glob> = Vec::new();
+  for pattern in &patterns {
+    match pattern.strip_prefix('!') {
+      Some(pattern) => {
+        excludes.push(glob_compile(lane, pattern).map_err(|err| rux_dbg_bleb!(err))?)
+      }
+      None => includes.push(glob_compile(lane, pattern).map_err(|err| rux_dbg_bleb!(err))?),
+    }
+  }
+  let mut result: Vec<String> = Vec::new();
+  for include in &includes {
+    rux_dbg_tell!(include.base);
+    glob_walk(include, &excludes, &mut result).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  result.sort();
+  result.dedup();
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn glob_files(lane: Context, patterns: Vec<String>) -> Result<Vec<String>, LizError> {
+  rux_dbg_call!(patterns);
+  let found = glob(lane, patterns).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(
+    found
+      .into_iter()
+      .filter(|path| Path::new(path).is_file())
+      .collect()
+  ));
+}
+
+pub fn glob_match(pattern: &str, path: &str) -> Result<bool, LizError> {
+  rux_dbg_call!(pattern, path);
+  let regex = format!(
+    "^{}$",
+    glob_regex(&get_slashed(pattern)).map_err(|err| rux_dbg_bleb!(err))?
+  );
+  let regex = Regex::new(&regex).map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(regex.is_match(&get_slashed(path))));
+}
+
+pub fn is_glob(path: &str) -> bool {
+  path.contains(['*', '?', '[', '{'])
+}
+
+pub fn cp(lane: Context, origin: &str, destiny: &str) -> Result<(), LizError> {
+  rux_dbg_call!(origin, destiny);
+  if !is_glob(origin) {
+    rux_dbg_reav!(rux_paths::cp(origin, destiny));
+  }
+  let destiny = utils::get_stacked_path(lane, destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(destiny);
+  rux_paths::mkdir(&destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  for found in
+    get_outer_paths(glob(lane, vec![origin.into()]).map_err(|err| rux_dbg_bleb!(err))?)
+  {
+    let found_destiny = rux_paths::path_join(&destiny, rux_paths::path_name(&found))
+      .map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(found, found_destiny);
+    rux_paths::cp(&found, &found_destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  Ok(())
+}
+
+pub fn mv(lane: Context, origin: &str, destiny: &str) -> Result<(), LizError> {
+  rux_dbg_call!(origin, destiny);
+  if !is_glob(origin) {
+    rux_dbg_reav!(rux_paths::mv(origin, destiny));
+  }
+  let destiny = utils::get_stacked_path(lane, destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(destiny);
+  rux_paths::mkdir(&destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  for found in
+    get_outer_paths(glob(lane, vec![origin.into()]).map_err(|err| rux_dbg_bleb!(err))?)
+  {
+    let found_destiny = rux_paths::path_join(&destiny, rux_paths::path_name(&found))
+      .map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_tell!(found, found_destiny);
+    rux_paths::mv(&found, &found_destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  Ok(())
+}
+
+pub fn rm(lane: Context, path: &str) -> Result<(), LizError> {
+  rux_dbg_call!(path);
+  if !is_glob(path) {
+    rux_dbg_reav!(rux_paths::rm(path));
+  }
+  for found in get_outer_paths(glob(lane, vec![path.into()]).map_err(|err| rux_dbg_bleb!(err))?)
+  {
+    rux_dbg_tell!(found);
+    rux_paths::rm(&found).map_err(|err| rux_dbg_bleb!(err))?;
+  }
+  Ok(())
+}
+
+pub fn get_globbed_files(lane: Context, paths: Vec<String>) -> Result<Vec<String>, LizError> {
+  rux_dbg_call!(paths);
+  let mut result = Vec::new();
+  for path in paths {
+    if is_glob(&path) {
+      result.extend(glob_files(lane, vec![path]).map_err(|err| rux_dbg_bleb!(err))?);
+    } else {
+      result.push(path);
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkSpec {
+  pub min_depth: usize,
+  pub max_depth: Option<usize>,
+  pub follow_links: bool,
+  pub hidden: bool,
+  pub ignore: bool,
+  pub types: Vec<String>,
+  pub exts: Vec<String>,
+  pub min_size: Option<u64>,
+  pub max_size: Option<u64>,
+  pub newer_than: Option<i64>,
+  pub older_than: Option<i64>,
+}
+
+// The hash_dir and sync walks take their hidden and ignore defaults from
+// here, so the same tree is walked the same way by all of them.
+impl Default for WalkSpec {
+  fn default() -> Self {
+    WalkSpec {
+      min_depth: 1,
+      max_depth: None,
+      follow_links: false,
+      hidden: true,
+      ignore: true,
+      types: Vec::new(),
+      exts: Vec::new(),
+      min_size: None,
+      max_size: None,
+      newer_than: None,
+      older_than: None,
+    }
+  }
+}
+
+pub fn walk_spec(opts: Option<Table>) -> Result<WalkSpec, LizError> {
+  rux_dbg_call!();
+  let mut result = WalkSpec::default();
+  if let Some(opts) = opts {
+    if let Some(min_depth) = opts.get("min_depth").map_err(|err| rux_dbg_erro!(err))? {
+      result.min_depth = min_depth;
+    }
+    result.max_depth = opts.get("max_depth").map_err(|err| rux_dbg_erro!(err))?;
+    if let Some(follow_links) = opts.get("follow_links").map_err(|err| rux_dbg_erro!(err))? {
+      result.follow_links = follow_links;
+    }
+    if let Some(hidden) = opts.get("hidden").map_err(|err| rux_dbg_erro!(err))? {
+      result.hidden = hidden;
+    }
+    if let Some(ignore) = opts.get("ignore").map_err(|err| rux_dbg_erro!(err))? {
+      result.ignore = ignore;
+    }
+    result.types = get_walk_list(&opts, "types").map_err(|err| rux_dbg_bleb!(err))?;
+    result.exts = get_walk_list(&opts, "exts").map_err(|err| rux_dbg_bleb!(err))?;
+    result.min_size = opts.get("min_size").map_err(|err| rux_dbg_erro!(err))?;
+    result.max_size = opts.get("max_size").map_err(|err| rux_dbg_erro!(err))?;
+    result.newer_than = opts.get("newer_than").map_err(|err| rux_dbg_erro!(err))?;
+    result.older_than = opts.get("older_than").map_err(|err| rux_dbg_erro!(err))?;
+  }
+  for kind in &result.types {
+    if kind != "file" && kind != "dir" && kind != "symlink" {
+      return Err(rux_dbg_erro!(format!("Unknown walk type: {}", kind)));
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn walk<'a>(
+  lane: Context<'a>,
+  dir: &str,
+  spec: WalkSpec,
+) -> Result<Function<'a>, LizError> {
+  rux_dbg_call!(dir, spec);
+  let dir = if dir.is_empty() || dir == "." {
+    utils::get_stacked_dir(lane).map_err(|err| rux_dbg_bleb!(err))?
+  } else {
+    utils::get_stacked_path(lane, dir).map_err(|err| rux_dbg_bleb!(err))?
+  };
+  rux_dbg_step!(dir);
+  let mut builder = walk_builder(&dir, spec.hidden, spec.ignore);
+  builder
+    .follow_links(spec.follow_links)
+    .max_depth(spec.max_depth);
+  let walker = Mutex::new(builder.build());
+  let result = lane
+    .create_function(move |lane, ()| {
+      let mut walker = walker
+        .lock()
+        .map_err(|err| rlua::Error::external(err.to_string()))?;
+      utils::treat_error(walk_next(lane, &mut walker, &spec))
+    })
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+// The ignore files do not list the .git dir, so it is skipped along with
+// them unless the ignore filters are turned off.
+pub fn walk_builder(dir: &str, hidden: bool, ignore: bool) -> WalkBuilder {
+  let mut result = WalkBuilder::new(dir);
+  result
+    .standard_filters(ignore)
+    .hidden(!hidden)
+    .require_git(false);
+  if ignore {
+    result.filter_entry(|entry| {
+      entry.depth() == 0
+        || entry.file_name() != ".git"
+        || !entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false)
+    });
+  }
+  result
+}
+
+fn walk_next<'a>(
+  lane: Context<'a>,
+  walker: &mut Walk,
+  spec: &WalkSpec,
+) -> Result<Value<'a>, LizError> {
+  for entry in walker {
+    let entry = entry.map_err(|err| rux_dbg_erro!(err))?;
+    if entry.depth() < spec.min_depth {
+      continue;
+    }
+    let kind = match entry.file_type() {
+      Some(kind) if kind.is_symlink() => "symlink",
+      Some(kind) if kind.is_dir() => "dir",
+      _ => "file",
+    };
+    if !spec.types.is_empty() && !spec.types.iter().any(|allowed| allowed == kind) {
+      continue;
+    }
+    let path = entry.path();
+    if !spec.exts.is_empty() {
+      let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+      if kind == "dir"
+        || !spec
+          .exts
+          .iter()
+          .any(|allowed| allowed.trim_start_matches('.') == ext)
+      {
+        continue;
+      }
+    }
+    let metadata = match entry.metadata() {
+      Ok(metadata) => metadata,
+      Err(err) => return Err(rux_dbg_erro!(err, path.display())),
+    };
+    let size = metadata.len();
+    let sized = spec.min_size.is_some() || spec.max_size.is_some();
+    if (sized && kind == "dir")
+      || spec
+        .min_size
+        .map(|min_size| size < min_size)
+        .unwrap_or(false)
+      || spec
+        .max_size
+        .map(|max_size| size > max_size)
+        .unwrap_or(false)
+    {
+      continue;
+    }
+    let modified = get_unix_time(metadata.modified()).unwrap_or(0);
+    if spec
+      .newer_than
+      .map(|newer| modified <= newer)
+      .unwrap_or(false)
+      || spec
+        .older_than
+        .map(|older| modified >= older)
+        .unwrap_or(false)
+    {
+      continue;
+    }
+    let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("path", path.display().to_string())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("name", entry.file_name().to_string_lossy().to_string())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("depth", entry.depth())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result.set("kind", kind).map_err(|err| rux_dbg_erro!(err))?;
+    result.set("size", size).map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("modified", modified)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    return Ok(Value::Table(result));
+  }
+  Ok(Value::Nil)
+}
+
+fn get_walk_list(opts: &Table, name: &str) -> Result<Vec<String>, LizError> {
+  let value: Value = opts.get(name).map_err(|err| rux_dbg_erro!(err))?;
+  match value {
+    Value::Nil => Ok(Vec::new()),
+    Value::String(value) => Ok(vec![value
+      .to_str()
+      .map_err(|err| rux_dbg_erro!(err))?
+      .into()]),
+    Value::Table(values) => values
+      .sequence_values::<String>()
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|err| rux_dbg_erro!(err)),
+    _ => Err(rux_dbg_erro!(format!(
+      "The walk {} must be a string or a list",
+      name
+    ))),
+  }
+}
+
+pub fn stat<'a>(lane: Context<'a>, path: &str) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(path);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let metadata = std::fs::symlink_metadata(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let kind = if metadata.file_type().is_symlink() {
+    "symlink"
+  } else if metadata.is_dir() {
+    "dir"
+  } else {
+    "file"
+  };
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("path", path.as_str())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result.set("kind", kind).map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("size", metadata.len())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("modified", get_unix_time(metadata.modified()))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("accessed", get_unix_time(metadata.accessed()))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("created", get_unix_time(metadata.created()))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("readonly", metadata.permissions().readonly())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::MetadataExt;
+    let mode = metadata.mode() & 0o7777;
+    result.set("mode", mode).map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("perms", format!("{:o}", mode))
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("uid", metadata.uid())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    result
+      .set("gid", metadata.gid())
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  if kind == "symlink" {
+    let target = std::fs::read_link(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+    result
+      .set("target", target.display().to_string())
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+// Lua has no octal literals, so a number mode like 755 is taken by its digits
+// as if it was the string "755", which also means tonumber("755", 8) is wrong.
+pub fn chmod(lane: Context, path: &str, mode: &str) -> Result<(), LizError> {
+  rux_dbg_call!(path, mode);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let metadata = std::fs::metadata(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let mut permissions = metadata.permissions();
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let actual = permissions.mode() & 0o7777;
+    let changed = get_chmod_mode(actual, mode).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_step!(actual, changed);
+    permissions.set_mode(changed);
+  }
+  #[cfg(not(unix))]
+  {
+    let actual = if permissions.readonly() { 0o555 } else { 0o777 };
+    let changed = get_chmod_mode(actual, mode).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_step!(actual, changed);
+    permissions.set_readonly(changed & 0o200 == 0);
+  }
+  std::fs::set_permissions(&path, permissions).map_err(|err| rux_dbg_erro!(err, path))?;
+  Ok(())
+}
+
+pub fn set_mtime(
+  lane: Context,
+  path: &str,
+  modified: f64,
+  accessed: Option<f64>,
+) -> Result<(), LizError> {
+  rux_dbg_call!(path, modified, accessed);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let file = std::fs::OpenOptions::new()
+    .read(true)
+    .write(cfg!(windows))
+    .open(&path)
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  let mut times = std::fs::FileTimes::new().set_modified(get_system_time(modified));
+  if let Some(accessed) = accessed {
+    times = times.set_accessed(get_system_time(accessed));
+  }
+  file
+    .set_times(times)
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  Ok(())
+}
+
+pub fn symlink(lane: Context, target: &str, link: &str) -> Result<(), LizError> {
+  rux_dbg_call!(target, link);
+  let link = utils::get_stacked_path(lane, link).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(link);
+  #[cfg(unix)]
+  std::os::unix::fs::symlink(target, &link).map_err(|err| rux_dbg_erro!(err, link))?;
+  #[cfg(windows)]
+  {
+    let link_dir = rux_paths::path_parent(&link).map_err(|err| rux_dbg_bleb!(err))?;
+    let target_path =
+      rux_paths::path_join_if_relative(&link_dir, target).map_err(|err| rux_dbg_bleb!(err))?;
+    if rux_paths::is_dir(&target_path) {
+      std::os::windows::fs::symlink_dir(target, &link)
+        .map_err(|err| rux_dbg_erro!(err, link))?;
+    } else {
+      std::os::windows::fs::symlink_file(target, &link)
+        .map_err(|err| rux_dbg_erro!(err, link))?;
+    }
+  }
+  Ok(())
+}
+
+pub fn hardlink(lane: Context, origin: &str, link: &str) -> Result<(), LizError> {
+  rux_dbg_call!(origin, link);
+  let origin = utils::get_stacked_path(lane, origin).map_err(|err| rux_dbg_bleb!(err))?;
+  let link = utils::get_stacked_path(lane, link).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(origin, link);
+  std::fs::hard_link(&origin, &link).map_err(|err| rux_dbg_erro!(err, origin, link))?;
+  Ok(())
+}
+
+pub fn readlink(lane: Context, path: &str) -> Result<String, LizError> {
+  rux_dbg_call!(path);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let target = std::fs::read_link(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  rux_dbg_reav!(Ok(target.display().to_string()));
+}
+
+fn get_chmod_mode(actual: u32, mode: &str) -> Result<u32, LizError> {
+  rux_dbg_call!(actual, mode);
+  let mode = mode.trim();
+  if !mode.is_empty() && mode.chars().all(|ch| ch.is_ascii_digit()) {
+    let result = match u32::from_str_radix(mode, 8) {
+      Ok(result) if result <= 0o7777 => result,
+      _ => {
+        return Err(rux_dbg_erro!(format!(
+          "Invalid chmod mode: {}, it must be up to four octal digits",
+          mode
+        )))
+      }
+    };
+    rux_dbg_reav!(Ok(result));
+  }
+  let mut result = actual;
+  for clause in mode.split(',') {
+    let operator = clause
+      .find(['+', '-', '='])
+      .ok_or_else(|| rux_dbg_erro!(format!("Invalid chmod mode: {}", mode)))?;
+    let mut who = 0;
+    for ch in clause[..operator].chars() {
+      who |= match ch {
+        'u' => 0o4700,
+        'g' => 0o2070,
+        'o' => 0o1007,
+        'a' => 0o7777,
+        _ => return Err(rux_dbg_erro!(format!("Invalid chmod mode: {}", mode))),
+      };
+    }
+    if who == 0 {
+      who = 0o7777;
+    }
+    let mut what = 0;
+    for ch in clause[operator + 1..].chars() {
+      what |= match ch {
+        'r' => 0o444,
+        'w' => 0o222,
+        'x' => 0o111,
+        's' => 0o6000,
+        't' => 0o1000,
+        _ => return Err(rux_dbg_erro!(format!("Invalid chmod mode: {}", mode))),
+      };
+    }
+    let bits = who & what;
+    match &clause[operator..operator + 1] {
+      "+" => result |= bits,
+      "-" => result &= !bits,
+      _ => result = (result & !who) | bits,
+    }
+    rux_dbg_tell!(clause, result);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_unix_time(time: std::io::Result<std::time::SystemTime>) -> Option<i64> {
+  time
+    .ok()
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|time| time.as_secs() as i64)
+}
+
+fn get_system_time(seconds: f64) -> std::time::SystemTime {
+  if seconds >= 0.0 {
+    UNIX_EPOCH + std::time::Duration::from_secs_f64(seconds)
+  } else {
+    UNIX_EPOCH - std::time::Duration::from_secs_f64(-seconds)
+  }
+}
+
+struct Glob {
+  base: String,
+  depth: Option<usize>,
+  regex: Regex,
+}
+
+fn glob_compile(lane: Context, pattern: &str) -> Result<Glob, LizError> {
+  rux_dbg_call!(pattern);
+  let pattern = get_slashed(pattern);
+  let parts: Vec<&str> = pattern.split('/').collect();
+  let literals = parts.iter().take_while(|part| !is_glob(part)).count();
+  let literals = if literals == parts.len() {
+    literals - 1
+  } else {
+    literals
+  };
+  let base = parts[..literals].join("/");
+  let base = if base.is_empty() && pattern.starts_with('/') {
+    String::from("/")
+  } else if base.is_empty() {
+    utils::get_stacked_dir(lane).map_err(|err| rux_dbg_bleb!(err))?
+  } else {
+    utils::get_stacked_path(lane, &base).map_err(|err| rux_dbg_bleb!(err))?
+  };
+  rux_dbg_step!(base);
+  let rest = &parts[literals..];
+  let depth = if rest.iter().any(|part| part.contains("**")) {
+    None
+  } else {
+    Some(rest.len())
+  };
+  let base_slashed = get_slashed(&base);
+  let base_slashed = base_slashed.trim_end_matches('/');
+  let regex = format!(
+    "^{}/{}$",
+    regex::escape(base_slashed),
+    glob_regex(&rest.join("/")).map_err(|err| rux_dbg_bleb!(err))?
+  );
+  rux_dbg_step!(regex);
+  let regex = Regex::new(&regex).map_err(|err| rux_dbg_erro!(err, pattern))?;
+  rux_dbg_reav!(Ok(Glob { base, depth, regex }));
+}
+
+fn glob_regex(pattern: &str) -> Result<String, LizError> {
+  rux_dbg_call!(pattern);
+  let chars: Vec<char> = pattern.chars().collect();
+  let mut result = String::new();
+  let mut braces = 0;
+  let mut index = 0;
+  while index < chars.len() {
+    let actual = chars[index];
+    match actual {
+      '*' => {
+        let double = index + 1 < chars.len() && chars[index + 1] == '*';
+        let at_start = index == 0 || chars[index - 1] == '/';
+        if double && at_start && (index + 2 == chars.len() || chars[index + 2] == '/') {
+          if index + 2 == chars.len() {
+            result.push_str(".*");
+            index += 2;
+          } else {
+            result.push_str("(?:.*/)?");
+            index += 3;
+          }
+          continue;
+        }
+        result.push_str("[^/]*");
+        while index < chars.len() && chars[index] == '*' {
+          index += 1;
+        }
+        continue;
+      }
+      '?' => result.push_str("[^/]"),
+      '[' => {
+        let close = chars[index + 1..]
+          .iter()
+          .enumerate()
+          .skip(1)
+          .find(|(_, ch)| **ch == ']')
+          .map(|(found, _)| index + 1 + found);
+        match close {
+          Some(close) => {
+            let mut class: String = chars[index + 1..close].iter().collect();
+            if class.starts_with('!') {
+              class.replace_range(..1, "^");
+            }
+            result.push('[');
+            result.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+            result.push(']');
+            index = close + 1;
+            continue;
+          }
+          None => result.push_str("\\["),
+        }
+      }
+      '{' => {
+        braces += 1;
+        result.push_str("(?:");
+      }
+      '}' if braces > 0 => {
+        braces -= 1;
+        result.push(')');
+      }
+      ',' if braces > 0 => result.push('|'),
+      '\\' if index + 1 < chars.len() => {
+        index += 1;
+        result.push_str(&regex::escape(&chars[index].to_string()));
+      }
+      other => result.push_str(&regex::escape(&other.to_string())),
+    }
+    index += 1;
+  }
+  if braces > 0 {
+    return Err(rux_dbg_erro!(format!(
+      "Unclosed brace on the glob: {}",
+      pattern
+    )));
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn glob_walk(glob: &Glob, excludes: &[Glob], result: &mut Vec<String>) -> Result<(), LizError> {
+  rux_dbg_call!(glob.base);
+  if !Path::new(&glob.base).is_dir() {
+    return Ok(());
+  }
+  let mut pending: Vec<(String, usize)> = vec![(glob.base.clone(), 0)];
+  while let Some((dir, level)) = pending.pop() {
+    let entries = match std::fs::read_dir(&dir) {
+      Ok(entries) => entries,
+      Err(err) => return Err(rux_dbg_erro!(err, dir)),
+    };
+    for entry in entries {
+      let entry = entry.map_err(|err| rux_dbg_erro!(err, dir))?;
+      let path = entry.path().display().to_string();
+      let slashed = get_slashed(&path);
+      if excludes
+        .iter()
+        .any(|exclude| exclude.regex.is_match(&slashed))
+      {
+        rux_dbg_tell!(path);
+        continue;
+      }
+      if glob.regex.is_match(&slashed) {
+        result.push(path.clone());
+      }
+      let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+      let deeper = match glob.depth {
+        Some(depth) => level + 1 < depth,
+        None => true,
+      };
+      if is_dir && deeper {
+        pending.push((path, level + 1));
+      }
+    }
+  }
+  Ok(())
+}
+
+fn get_outer_paths(mut paths: Vec<String>) -> Vec<String> {
+  paths.sort();
+  let mut result: Vec<String> = Vec::new();
+  for path in paths {
+    let inside = result
+      .iter()
+      .any(|outer| Path::new(&path).starts_with(Path::new(outer)));
+    if !inside {
+      result.push(path);
+    }
+  }
+  result
+}
+
+fn get_slashed(path: &str) -> String {
+  if cfg!(windows) {
+    path.replace('\\', "/")
+  } else {
+    path.into()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_match(pattern: &str, path: &str) -> bool {
+    glob_match(pattern, path).unwrap()
+  }
+
+  #[test]
+  fn glob_stars_stay_on_their_dir() {
+    assert!(is_match("*.rs", "main.rs"));
+    assert!(!is_match("*.rs", "src/main.rs"));
+    assert!(is_match("src/*/mod.rs", "src/paths/mod.rs"));
+    assert!(!is_match("src/*/mod.rs", "src/a/b/mod.rs"));
+    assert!(is_match("**/*.rs", "main.rs"));
+    assert!(is_match("**/*.rs", "src/a/main.rs"));
+    assert!(is_match("src/**", "src/a/b"));
+    assert!(!is_match("src/**", "lib/a"));
+    assert!(is_match("a/**/b", "a/b"));
+    assert!(is_match("a/**/b", "a/x/y/b"));
+  }
+
+  #[test]
+  fn glob_classes_braces_and_escapes() {
+    assert!(is_match("file?.txt", "file1.txt"));
+    assert!(!is_match("file?.txt", "file10.txt"));
+    assert!(is_match("[abc].txt", "b.txt"));
+    assert!(!is_match("[!abc].txt", "b.txt"));
+    assert!(is_match("[!abc].txt", "d.txt"));
+    assert!(is_match("[a-c]x", "bx"));
+    assert!(is_match("*.{rs,toml}", "Cargo.toml"));
+    assert!(!is_match("*.{rs,toml}", "README.md"));
+    assert!(is_match("\\*.txt", "*.txt"));
+    assert!(!is_match("\\*.txt", "a.txt"));
+    assert!(!is_match("a.b", "axb"));
+    assert!(is_match("[open", "[open"));
+    assert!(glob_match("*.{rs,toml", "a.rs").is_err());
+  }
+
+  #[test]
+  fn glob_is_told_from_plain_paths() {
+    assert!(is_glob("src/*.rs"));
+    assert!(is_glob("file?.txt"));
+    assert!(is_glob("{a,b}"));
+    assert!(!is_glob("src/main.rs"));
+  }
+
+  #[test]
+  fn outer_paths_drop_the_nested_ones() {
+    let paths = vec!["src/a.rs", "src", "srcs/b.rs", "other", "src/deep/c.rs"];
+    let paths = paths.into_iter().map(String::from).collect();
+    assert_eq!(get_outer_paths(paths), vec!["other", "src", "srcs/b.rs"]);
+  }
+
+  #[test]
+  fn chmod_reads_octal_modes() {
+    assert_eq!(get_chmod_mode(0o644, "755").unwrap(), 0o755);
+    assert_eq!(get_chmod_mode(0o644, " 04755 ").unwrap(), 0o4755);
+    assert!(get_chmod_mode(0o644, "17777").is_err());
+    assert!(get_chmod_mode(0o644, "8").is_err());
+  }
+
+  #[test]
+  fn chmod_applies_symbolic_clauses() {
+    assert_eq!(get_chmod_mode(0o644, "u+x").unwrap(), 0o744);
+    assert_eq!(get_chmod_mode(0o644, "go-r").unwrap(), 0o600);
+    assert_eq!(get_chmod_mode(0o644, "a=r").unwrap(), 0o444);
+    assert_eq!(get_chmod_mode(0o644, "+x").unwrap(), 0o755);
+    assert_eq!(get_chmod_mode(0o777, "u=rwx,g=rx,o=").unwrap(), 0o750);
+    assert_eq!(get_chmod_mode(0o644, "u+s").unwrap(), 0o4644);
+    assert_eq!(get_chmod_mode(0o644, "g+s").unwrap(), 0o2644);
+    assert_eq!(get_chmod_mode(0o644, "o+t").unwrap(), 0o1644);
+    assert!(get_chmod_mode(0o644, "u+q").is_err());
+    assert!(get_chmod_mode(0o644, "z+x").is_err());
+    assert!(get_chmod_mode(0o644, "ux").is_err());
+  }
+
+  #[test]
+  fn times_convert_around_the_epoch() {
+    let time = get_system_time(1.5);
+    assert_eq!(time.duration_since(UNIX_EPOCH).unwrap().as_millis(), 1500);
+    let time = get_system_time(-2.0);
+    assert_eq!(UNIX_EPOCH.duration_since(time).unwrap().as_secs(), 2);
+    assert_eq!(get_unix_time(Ok(get_system_time(10.0))), Some(10));
+    let missing = std::io::Error::other("no time");
+    assert_eq!(get_unix_time(Err(missing)), None);
+  }
+
+  fn put_walk_tree(name: &str) -> std::path::PathBuf {
+    let root = std::env::temp_dir().join(format!("liz_paths_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for dir in ["src", "build", ".git"] {
+      std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    std::fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+    std::fs::write(root.join(".hidden.txt"), "hidden").unwrap();
+    std::fs::write(root.join("a.rs"), "fn main(){}").unwrap();
+    std::fs::write(root.join("b.log"), "log").unwrap();
+    std::fs::write(root.join("build").join("out.rs"), "out").unwrap();
+    std::fs::write(root.join("src").join("c.rs"), "c").unwrap();
+    std::fs::write(root.join("src").join("d.txt"), "d".repeat(100)).unwrap();
+    std::fs::write(root.join(".git").join("config"), "git").unwrap();
+    root
+  }
+
+  fn get_walked(root: &std::path::Path, opts: &str) -> Vec<String> {
+    rlua::Lua::new().context(|lane| {
+      let opts: Table = lane.load(opts).eval().unwrap();
+      let spec = walk_spec(Some(opts)).unwrap();
+      let next = walk(lane, &root.display().to_string(), spec).unwrap();
+      let mut result = Vec::new();
+      while let Value::Table(entry) = next.call::<_, Value>(()).unwrap() {
+        let path: String = entry.get("path").unwrap();
+        let relative = std::path::Path::new(&path).strip_prefix(root).unwrap();
+        let relative = relative.display().to_string().replace('\\', "/");
+        let kind: String = entry.get("kind").unwrap();
+        result.push(format!("{}:{}", kind, relative));
+      }
+      result.sort();
+      result
+    })
+  }
+
+  #[test]
+  fn walk_follows_ignore_files_and_hidden() {
+    let root = put_walk_tree("ignore");
+    assert_eq!(
+      get_walked(&root, "{}"),
+      vec![
+        "dir:src",
+        "file:.gitignore",
+        "file:.hidden.txt",
+        "file:a.rs",
+        "file:src/c.rs",
+        "file:src/d.txt",
+      ]
+    );
+    assert_eq!(
+      get_walked(&root, "{hidden = false, ignore = false}"),
+      vec![
+        "dir:build",
+        "dir:src",
+        "file:a.rs",
+        "file:b.log",
+        "file:build/out.rs",
+        "file:src/c.rs",
+        "file:src/d.txt",
+      ]
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn walk_filters_types_exts_depth_and_size() {
+    let root = put_walk_tree("filters");
+    assert_eq!(
+      get_walked(&root, r#"{types = "file", exts = {".rs"}}"#),
+      vec!["file:a.rs", "file:src/c.rs"]
+    );
+    assert_eq!(
+      get_walked(&root, r#"{types = {"dir"}, max_depth = 1}"#),
+      vec!["dir:src"]
+    );
+    assert_eq!(
+      get_walked(&root, "{min_depth = 2, max_size = 10}"),
+      vec!["file:src/c.rs"]
+    );
+    assert_eq!(get_walked(&root, "{min_size = 50}"), vec!["file:src/d.txt"]);
+    assert!(get_walked(&root, "{newer_than = 4102444800}").is_empty());
+    assert_eq!(get_walked(&root, "{older_than = 4102444800}").len(), 6);
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn walk_spec_checks_the_options() {
+    rlua::Lua::new().context(|lane| {
+      let spec = walk_spec(None).unwrap();
+      assert_eq!(spec.min_depth, 1);
+      assert!(spec.hidden && spec.ignore);
+      let opts: Table = lane.load(r#"{types = "fifo"}"#).eval().unwrap();
+      assert!(walk_spec(Some(opts)).is_err());
+      let opts: Table = lane.load("{exts = 3}").eval().unwrap();
+      assert!(walk_spec(Some(opts)).is_err());
+    });
+  }
+}
@@ -0,0 +1,1313 @@
+use once_cell::sync::Lazy;
+use rlua::{
+  AnyUserData, Context, Function, RegistryKey, Table, UserData, UserDataMethods, Value,
+};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::liz_envs;
+use crate::liz_exits;
+use crate::utils;
+use crate::LizError;
+
+// A grandchild that inherits the outputs keeps them open after the child exits,
+// so the readers are only waited for this long once the child is done.
+const READERS_GRACE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone)]
+pub struct ProcSpec {
+  pub name: String,
+  pub args: Vec<String>,
+  pub dir: Option<String>,
+  pub envs: liz_envs::LaneEnvs,
+  pub stdin: Option<String>,
+  pub stdin_file: Option<String>,
+  pub stdout_file: Option<String>,
+  pub stderr_file: Option<String>,
+  pub append: bool,
+  pub timeout: Option<u64>,
+  pub print: bool,
+}
+
+pub fn proc_spec<'a>(lane: Context<'a>, spec: &Table<'a>) -> Result<ProcSpec, LizError> {
+  rux_dbg_call!();
+  let mut positional: Vec<String> = Vec::new();
+  for value in spec.clone().sequence_values::<String>() {
+    positional.push(value.map_err(|err| rux_dbg_erro!(err))?);
+  }
+  rux_dbg_step!(positional);
+  let name: Option<String> = spec.get("cmd").map_err(|err| rux_dbg_erro!(err))?;
+  let (name, mut args) = match name {
+    Some(name) => (name, positional),
+    None => {
+      if positional.is_empty() {
+        return Err(rux_dbg_erro!(
+          "The process spec must have a command to execute"
+        ));
+      }
+      let name = positional.remove(0);
+      (name, positional)
+    }
+  };
+  rux_dbg_step!(name);
+  let more_args: Option<Vec<String>> = spec.get("args").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(more_args) = more_args {
+    args.extend(more_args);
+  }
+  rux_dbg_step!(args);
+  let mut envs = liz_envs::get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let spec_envs: Option<HashMap<String, String>> =
+    spec.get("env").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(spec_envs) = spec_envs {
+    for (env_name, env_value) in spec_envs {
+      envs.insert(env_name, Some(env_value));
+    }
+  }
+  rux_dbg_step!(envs);
+  let result = ProcSpec {
+    name,
+    args,
+    dir: spec.get("dir").map_err(|err| rux_dbg_erro!(err))?,
+    envs,
+    stdin: spec.get("stdin").map_err(|err| rux_dbg_erro!(err))?,
+    stdin_file: spec.get("stdin_file").map_err(|err| rux_dbg_erro!(err))?,
+    stdout_file: spec.get("stdout_file").map_err(|err| rux_dbg_erro!(err))?,
+    stderr_file: spec.get("stderr_file").map_err(|err| rux_dbg_erro!(err))?,
+    append: spec
+      .get::<_, Option<bool>>("append")
+      .map_err(|err| rux_dbg_erro!(err))?
+      .unwrap_or(false),
+    timeout: spec.get("timeout").map_err(|err| rux_dbg_erro!(err))?,
+    print: spec
+      .get::<_, Option<bool>>("print")
+      .map_err(|err| rux_dbg_erro!(err))?
+      .unwrap_or(false),
+  };
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn proc<'a>(lane: Context<'a>, spec: Table<'a>) -> Result<Process, LizError> {
+  rux_dbg_call!();
+  let proc_spec = proc_spec(lane, &spec).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(proc_spec);
+  let mut process = proc_start(&proc_spec).map_err(|err| rux_dbg_bleb!(err))?;
+  let on_stdout: Option<Function> = spec.get("on_stdout").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(on_stdout) = on_stdout {
+    process.on_stdout = Some(
+      lane
+        .create_registry_value(on_stdout)
+        .map_err(|err| rux_dbg_erro!(err))?,
+    );
+  }
+  let on_stderr: Option<Function> = spec.get("on_stderr").map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(on_stderr) = on_stderr {
+    process.on_stderr = Some(
+      lane
+        .create_registry_value(on_stderr)
+        .map_err(|err| rux_dbg_erro!(err))?,
+    );
+  }
+  rux_dbg_reav!(Ok(process));
+}
+
+pub fn proc_command(spec: &ProcSpec) -> Command {
+  rux_dbg_call!(spec);
+  let mut command = Command::new(&spec.name);
+  command.args(&spec.args);
+  if let Some(dir) = &spec.dir {
+    command.current_dir(dir);
+  }
+  liz_envs::apply_lane_envs(&mut command, &spec.envs);
+  rux_dbg_reav!(command);
+}
+
+pub fn proc_start(spec: &ProcSpec) -> Result<Process, LizError> {
+  rux_dbg_call!(spec);
+  let mut command = proc_command(spec);
+  if let Some(stdin_file) = &spec.stdin_file {
+    let file = File::open(stdin_file).map_err(|err| rux_dbg_erro!(err, stdin_file))?;
+    command.stdin(Stdio::from(file));
+  } else {
+    command.stdin(Stdio::piped());
+  }
+  match &spec.stdout_file {
+    Some(stdout_file) => command.stdout(proc_file(stdout_file, spec.append)?),
+    None => command.stdout(Stdio::piped()),
+  };
+  match &spec.stderr_file {
+    Some(stderr_file) => command.stderr(proc_file(stderr_file, spec.append)?),
+    None => command.stderr(Stdio::piped()),
+  };
+  let mut child = command
+    .spawn()
+    .map_err(|err| rux_dbg_erro!(err, spec.name))?;
+  let pid = child.id();
+  rux_dbg_step!(pid);
+  liz_exits::track_child(pid, false);
+  let (sender, receiver) = mpsc::channel();
+  if let Some(stdout) = child.stdout.take() {
+    proc_reader(stdout, ProcStream::Stdout, sender.clone());
+  }
+  if let Some(stderr) = child.stderr.take() {
+    proc_reader(stderr, ProcStream::Stderr, sender.clone());
+  }
+  drop(sender);
+  let mut stdin = child.stdin.take();
+  if let Some(data) = &spec.stdin {
+    if let Some(mut writer) = stdin.take() {
+      let data = data.clone();
+      thread::spawn(move || {
+        let _ = writer.write_all(data.as_bytes());
+      });
+    }
+  }
+  let child = Arc::new(Mutex::new(child));
+  let timed_out = Arc::new(AtomicBool::new(false));
+  if let Some(timeout) = spec.timeout {
+    proc_watchdog(child.clone(), timed_out.clone(), timeout);
+  }
+  rux_dbg_reav!(Ok(Process {
+    name: spec.name.clone(),
+    pid,
+    child,
+    stdin,
+    lines: receiver,
+    stdout: String::new(),
+    stderr: String::new(),
+    on_stdout: None,
+    on_stderr: None,
+    print: spec.print,
+    timed_out,
+    exited: None,
+  }));
+}
+
+fn proc_file(path: &str, append: bool) -> Result<Stdio, LizError> {
+  rux_dbg_call!(path, append);
+  let file = OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(append)
+    .truncate(!append)
+    .open(path)
+    .map_err(|err| rux_dbg_erro!(err, path))?;
+  rux_dbg_reav!(Ok(Stdio::from(file)));
+}
+
+fn proc_reader<R: Read + Send + 'static, T: Copy + Send + 'static>(
+  source: R,
+  stream: T,
+  sender: Sender<(T, String)>,
+) {
+  thread::spawn(move || {
+    let mut reader = BufReader::new(source);
+    let mut buffer = Vec::new();
+    loop {
+      buffer.clear();
+      match reader.read_until(b'\n', &mut buffer) {
+        Ok(0) | Err(_) => break,
+        Ok(_) => {
+          let line = String::from_utf8_lossy(&buffer).to_string();
+          if sender.send((stream, line)).is_err() {
+            break;
+          }
+        }
+      }
+    }
+  });
+}
+
+fn is_pipe_exited(children: &[Arc<Mutex<Child>>]) -> Result<bool, LizError> {
+  for child in children {
+    let mut child = child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    if child
+      .try_wait()
+      .map_err(|err| rux_dbg_erro!(err))?
+      .is_none()
+    {
+      return Ok(false);
+    }
+  }
+  Ok(true)
+}
+
+fn proc_watchdog(child: Arc<Mutex<Child>>, timed_out: Arc<AtomicBool>, timeout: u64) {
+  let deadline = Instant::now() + Duration::from_millis(timeout);
+  thread::spawn(move || loop {
+    {
+      let mut child = match child.lock() {
+        Ok(child) => child,
+        Err(_) => break,
+      };
+      match child.try_wait() {
+        Ok(None) => {
+          if Instant::now() >= deadline {
+            timed_out.store(true, Ordering::Release);
+            let _ = child.kill();
+            break;
+          }
+        }
+        _ => break,
+      }
+    }
+    thread::sleep(Duration::from_millis(10));
+  });
+}
+
+pub fn sh<'a>(
+  lane: Context<'a>,
+  script: &str,
+  opts: Option<Table<'a>>,
+) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(script);
+  let spec = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(opts) = opts {
+    for pair in opts.pairs::<Value, Value>() {
+      let (key, value) = pair.map_err(|err| rux_dbg_erro!(err))?;
+      spec.set(key, value).map_err(|err| rux_dbg_erro!(err))?;
+    }
+  }
+  let (shell, shell_arg) = if cfg!(windows) {
+    ("cmd", "/C")
+  } else {
+    ("/bin/sh", "-c")
+  };
+  spec.set("cmd", shell).map_err(|err| rux_dbg_erro!(err))?;
+  spec
+    .set("args", vec![shell_arg, script])
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let mut process = proc(lane, spec).map_err(|err| rux_dbg_bleb!(err))?;
+  let exited = process.wait(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(exited);
+  rux_dbg_reav!(exit_table(lane, &process, &exited));
+}
+
+pub fn pipe<'a>(lane: Context<'a>, stages: Vec<Table<'a>>) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!();
+  if stages.is_empty() {
+    return Err(rux_dbg_erro!("The pipe must have at least one command"));
+  }
+  let mut specs = Vec::with_capacity(stages.len());
+  let mut on_stdouts = Vec::with_capacity(stages.len());
+  let mut on_stderrs = Vec::with_capacity(stages.len());
+  for (index, stage) in stages.iter().enumerate() {
+    let spec = proc_spec(lane, stage).map_err(|err| rux_dbg_bleb!(err))?;
+    let on_stdout: Option<Function> =
+      stage.get("on_stdout").map_err(|err| rux_dbg_erro!(err))?;
+    let on_stderr: Option<Function> =
+      stage.get("on_stderr").map_err(|err| rux_dbg_erro!(err))?;
+    if index < stages.len() - 1 && (spec.stdout_file.is_some() || on_stdout.is_some()) {
+      return Err(rux_dbg_erro!(format!(
+        "The pipe stage {} ({}) sends its stdout to the next stage, only the last one can take stdout_file or on_stdout",
+        index + 1,
+        spec.name
+      )));
+    }
+    specs.push(spec);
+    on_stdouts.push(on_stdout);
+    on_stderrs.push(on_stderr);
+  }
+  rux_dbg_step!(specs);
+  let (sender, receiver) = mpsc::channel();
+  let mut children: Vec<Arc<Mutex<Child>>> = Vec::with_capacity(specs.len());
+  let mut timeds: Vec<Arc<AtomicBool>> = Vec::with_capacity(specs.len());
+  if let Err(err) = pipe_start(&specs, sender, &mut children, &mut timeds) {
+    pipe_abort(&children);
+    return Err(rux_dbg_bleb!(err));
+  }
+  let mut stdout = String::new();
+  let mut stderr = String::new();
+  let mut exited_at: Option<Instant> = None;
+  let drained = loop {
+    if liz_exits::is_signal_pending() {
+      if let Err(err) = liz_exits::take_signals(lane) {
+        break Err(rux_dbg_bleb!(err));
+      }
+    }
+    if exited_at.is_none() {
+      match is_pipe_exited(&children) {
+        Ok(true) => exited_at = Some(Instant::now()),
+        Ok(false) => {}
+        Err(err) => break Err(rux_dbg_bleb!(err)),
+      }
+    }
+    if exited_at
+      .map(|exited_at| exited_at.elapsed() >= READERS_GRACE)
+      .unwrap_or(false)
+    {
+      break Ok(());
+    }
+    let ((index, stream), line) = match receiver.recv_timeout(Duration::from_millis(10)) {
+      Ok(received) => received,
+      Err(RecvTimeoutError::Timeout) => continue,
+      Err(RecvTimeoutError::Disconnected) => break Ok(()),
+    };
+    rux_dbg_tell!(index, stream, line);
+    let callback = match stream {
+      ProcStream::Stdout => &on_stdouts[index],
+      ProcStream::Stderr => &on_stderrs[index],
+    };
+    if let Some(callback) = callback {
+      let trimmed = line.trim_end_matches(['\n', '\r']);
+      if let Err(err) = callback.call::<_, ()>(trimmed) {
+        break Err(rux_dbg_erro!(err));
+      }
+    }
+    if specs[index].print {
+      match stream {
+        ProcStream::Stdout => print!("{}", line),
+        ProcStream::Stderr => eprint!("{}", line),
+      }
+    }
+    match stream {
+      ProcStream::Stdout => stdout.push_str(&line),
+      ProcStream::Stderr => stderr.push_str(&line),
+    }
+  };
+  if let Err(err) = drained {
+    pipe_abort(&children);
+    return Err(rux_dbg_bleb!(err));
+  }
+  let mut exits = Vec::with_capacity(children.len());
+  for child in &children {
+    let mut child = child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    let status = child.wait().map_err(|err| rux_dbg_erro!(err))?;
+    rux_dbg_tell!(status);
+    liz_exits::untrack_child(child.id());
+    exits.push(status);
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  let codes = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  for (index, status) in exits.iter().enumerate() {
+    codes
+      .set(index + 1, status.code())
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  let last = &exits[exits.len() - 1];
+  result
+    .set("code", last.code())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("signal", exit_signal(last))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("success", exits.iter().all(|status| status.success()))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set(
+      "timed_out",
+      timeds.iter().any(|timed| timed.load(Ordering::Acquire)),
+    )
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("codes", codes)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("stdout", stdout)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("stderr", stderr)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+fn pipe_start(
+  specs: &[ProcSpec],
+  sender: Sender<((usize, ProcStream), String)>,
+  children: &mut Vec<Arc<Mutex<Child>>>,
+  timeds: &mut Vec<Arc<AtomicBool>>,
+) -> Result<(), LizError> {
+  let mut previous: Option<Stdio> = None;
+  for (index, spec) in specs.iter().enumerate() {
+    let is_first = index == 0;
+    let is_last = index == specs.len() - 1;
+    let mut command = proc_command(spec);
+    match previous.take() {
+      Some(stdin) => command.stdin(stdin),
+      None => match &spec.stdin_file {
+        Some(stdin_file) => {
+          let file = File::open(stdin_file).map_err(|err| rux_dbg_erro!(err, stdin_file))?;
+          command.stdin(Stdio::from(file))
+        }
+        None if is_first && spec.stdin.is_some() => command.stdin(Stdio::piped()),
+        None => command.stdin(Stdio::null()),
+      },
+    };
+    match &spec.stdout_file {
+      Some(stdout_file) if is_last => {
+        command.stdout(proc_file(stdout_file, spec.append).map_err(|err| rux_dbg_bleb!(err))?)
+      }
+      _ => command.stdout(Stdio::piped()),
+    };
+    match &spec.stderr_file {
+      Some(stderr_file) => {
+        command.stderr(proc_file(stderr_file, spec.append).map_err(|err| rux_dbg_bleb!(err))?)
+      }
+      None => command.stderr(Stdio::piped()),
+    };
+    let mut child = command
+      .spawn()
+      .map_err(|err| rux_dbg_erro!(err, spec.name))?;
+    rux_dbg_tell!(spec.name, child.id());
+    liz_exits::track_child(child.id(), false);
+    if let (Some(data), Some(mut writer)) = (&spec.stdin, child.stdin.take()) {
+      let data = data.clone();
+      thread::spawn(move || {
+        let _ = writer.write_all(data.as_bytes());
+      });
+    }
+    if let Some(stdout) = child.stdout.take() {
+      if is_last {
+        proc_reader(stdout, (index, ProcStream::Stdout), sender.clone());
+      } else {
+        previous = Some(Stdio::from(stdout));
+      }
+    }
+    if let Some(stderr) = child.stderr.take() {
+      proc_reader(stderr, (index, ProcStream::Stderr), sender.clone());
+    }
+    let child = Arc::new(Mutex::new(child));
+    let timed_out = Arc::new(AtomicBool::new(false));
+    if let Some(timeout) = spec.timeout {
+      proc_watchdog(child.clone(), timed_out.clone(), timeout);
+    }
+    children.push(child);
+    timeds.push(timed_out);
+  }
+  Ok(())
+}
+
+fn pipe_abort(children: &[Arc<Mutex<Child>>]) {
+  rux_dbg_call!(children.len());
+  for child in children {
+    if let Ok(mut child) = child.lock() {
+      let _ = child.kill();
+      let _ = child.wait();
+      liz_exits::untrack_child(child.id());
+    }
+  }
+}
+
+pub fn shell_quote(parts: &[String]) -> String {
+  rux_dbg_call!(parts);
+  let mut result = String::new();
+  for part in parts {
+    if !result.is_empty() {
+      result.push(' ');
+    }
+    let is_safe = !part.is_empty()
+      && part
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || "_@%+=:,./-".contains(ch));
+    if is_safe {
+      result.push_str(part);
+    } else {
+      result.push('\'');
+      result.push_str(&part.replace('\'', "'\\''"));
+      result.push('\'');
+    }
+  }
+  rux_dbg_reav!(result);
+}
+
+pub fn shell_split(line: &str) -> Result<Vec<String>, LizError> {
+  rux_dbg_call!(line);
+  let mut result: Vec<String> = Vec::new();
+  let mut actual: Option<String> = None;
+  let mut chars = line.chars();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '\'' => {
+        let word = actual.get_or_insert_with(String::new);
+        loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some(inner) => word.push(inner),
+            None => return Err(rux_dbg_erro!("Could not find the closing single quote")),
+          }
+        }
+      }
+      '"' => {
+        let word = actual.get_or_insert_with(String::new);
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some('\n') => {}
+              Some(escaped) if "$`\"\\".contains(escaped) => word.push(escaped),
+              Some(escaped) => {
+                word.push('\\');
+                word.push(escaped);
+              }
+              None => return Err(rux_dbg_erro!("Could not find the closing double quote")),
+            },
+            Some(inner) => word.push(inner),
+            None => return Err(rux_dbg_erro!("Could not find the closing double quote")),
+          }
+        }
+      }
+      '\\' => match chars.next() {
+        Some('\n') => {}
+        Some(escaped) => actual.get_or_insert_with(String::new).push(escaped),
+        None => actual.get_or_insert_with(String::new).push('\\'),
+      },
+      ch if ch.is_whitespace() => {
+        if let Some(word) = actual.take() {
+          rux_dbg_tell!(word);
+          result.push(word);
+        }
+      }
+      ch => actual.get_or_insert_with(String::new).push(ch),
+    }
+  }
+  if let Some(word) = actual.take() {
+    result.push(word);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProcStream {
+  Stdout,
+  Stderr,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcExit {
+  pub code: Option<i32>,
+  pub signal: Option<i32>,
+  pub success: bool,
+  pub timed_out: bool,
+}
+
+#[derive(Debug)]
+pub struct Process {
+  name: String,
+  pid: u32,
+  child: Arc<Mutex<Child>>,
+  stdin: Option<ChildStdin>,
+  lines: Receiver<(ProcStream, String)>,
+  stdout: String,
+  stderr: String,
+  on_stdout: Option<RegistryKey>,
+  on_stderr: Option<RegistryKey>,
+  print: bool,
+  timed_out: Arc<AtomicBool>,
+  exited: Option<ProcExit>,
+}
+
+impl Process {
+  pub fn pid(&self) -> u32 {
+    self.pid
+  }
+
+  pub fn is_running(&mut self) -> Result<bool, LizError> {
+    rux_dbg_call!(self.pid);
+    if self.exited.is_some() {
+      rux_dbg_reav!(Ok(false));
+    }
+    let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    let status = child.try_wait().map_err(|err| rux_dbg_erro!(err))?;
+    rux_dbg_reav!(Ok(status.is_none()));
+  }
+
+  pub fn write(&mut self, data: &str) -> Result<(), LizError> {
+    rux_dbg_call!(self.pid, data);
+    match &mut self.stdin {
+      Some(stdin) => {
+        stdin
+          .write_all(data.as_bytes())
+          .map_err(|err| rux_dbg_erro!(err))?;
+        stdin.flush().map_err(|err| rux_dbg_erro!(err))?;
+        Ok(())
+      }
+      None => Err(rux_dbg_erro!(format!(
+        "The stdin of the process {} is already closed",
+        self.name
+      ))),
+    }
+  }
+
+  pub fn close_stdin(&mut self) {
+    rux_dbg_call!(self.pid);
+    self.stdin = None;
+  }
+
+  pub fn kill(&mut self) -> Result<(), LizError> {
+    rux_dbg_call!(self.pid);
+    let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    if child
+      .try_wait()
+      .map_err(|err| rux_dbg_erro!(err))?
+      .is_none()
+    {
+      child.kill().map_err(|err| rux_dbg_erro!(err))?;
+    }
+    Ok(())
+  }
+
+  pub fn wait(&mut self, lane: Context) -> Result<ProcExit, LizError> {
+    rux_dbg_call!(self.pid);
+    if let Some(exited) = &self.exited {
+      rux_dbg_reav!(Ok(exited.clone()));
+    }
+    self.stdin = None;
+    let mut status = None;
+    let mut exited_at: Option<Instant> = None;
+    loop {
+      match self.lines.recv_timeout(Duration::from_millis(10)) {
+        Ok((stream, line)) => {
+          rux_dbg_tell!(stream, line);
+          self.take_line(lane, stream, line)?;
+        }
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => {
+          if status.is_some() {
+            break;
+          }
+          thread::sleep(Duration::from_millis(10));
+        }
+      }
+      if liz_exits::is_signal_pending() {
+        liz_exits::take_signals(lane).map_err(|err| rux_dbg_bleb!(err))?;
+      }
+      if status.is_none() {
+        let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+        status = child.try_wait().map_err(|err| rux_dbg_erro!(err))?;
+        if status.is_some() {
+          exited_at = Some(Instant::now());
+        }
+      }
+      if exited_at
+        .map(|exited_at| exited_at.elapsed() >= READERS_GRACE)
+        .unwrap_or(false)
+      {
+        break;
+      }
+    }
+    let status = match status {
+      Some(status) => status,
+      None => return Err(rux_dbg_erro!("Could not get the process exit status")),
+    };
+    rux_dbg_step!(status);
+    liz_exits::untrack_child(self.pid);
+    let exited = ProcExit {
+      code: status.code(),
+      signal: exit_signal(&status),
+      success: status.success(),
+      timed_out: self.timed_out.load(Ordering::Acquire),
+    };
+    self.exited = Some(exited.clone());
+    rux_dbg_reav!(Ok(exited));
+  }
+
+  fn take_line(
+    &mut self,
+    lane: Context,
+    stream: ProcStream,
+    line: String,
+  ) -> Result<(), LizError> {
+    let callback = match stream {
+      ProcStream::Stdout => &self.on_stdout,
+      ProcStream::Stderr => &self.on_stderr,
+    };
+    if let Some(callback) = callback {
+      let callback: Function = lane
+        .registry_value(callback)
+        .map_err(|err| rux_dbg_erro!(err))?;
+      let trimmed = line.trim_end_matches(['\n', '\r']);
+      callback
+        .call::<_, ()>(trimmed)
+        .map_err(|err| rux_dbg_erro!(err))?;
+    }
+    if self.print {
+      match stream {
+        ProcStream::Stdout => print!("{}", line),
+        ProcStream::Stderr => eprint!("{}", line),
+      }
+    }
+    match stream {
+      ProcStream::Stdout => self.stdout.push_str(&line),
+      ProcStream::Stderr => self.stderr.push_str(&line),
+    }
+    Ok(())
+  }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+  use std::os::unix::process::ExitStatusExt;
+  status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_: &std::process::ExitStatus) -> Option<i32> {
+  None
+}
+
+fn exit_table<'a>(
+  lane: Context<'a>,
+  process: &Process,
+  exited: &ProcExit,
+) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(exited);
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("code", exited.code)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("signal", exited.signal)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("success", exited.success)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("timed_out", exited.timed_out)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("stdout", process.stdout.as_str())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("stderr", process.stderr.as_str())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+impl UserData for Process {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_method("pid", |_, slf, ()| Ok(slf.pid()));
+
+    methods.add_method_mut("is_running", |_, slf, ()| {
+      utils::treat_error(slf.is_running())
+    });
+
+    methods.add_method_mut("write", |_, slf, data: String| {
+      utils::treat_error(slf.write(&data))
+    });
+
+    methods.add_method_mut("close_stdin", |_, slf, ()| Ok(slf.close_stdin()));
+
+    methods.add_method_mut("kill", |_, slf, ()| utils::treat_error(slf.kill()));
+
+    methods.add_method_mut("wait", |lane, slf, ()| {
+      let exited = utils::treat_error(slf.wait(lane))?;
+      utils::treat_error(exit_table(lane, slf, &exited))
+    });
+  }
+}
+
+static LANE_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+static LANE_BACKS: Lazy<Mutex<HashMap<usize, Vec<Background>>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn bg<'a>(
+  lane: Context<'a>,
+  name: &str,
+  args: Vec<String>,
+  opts: Option<Table<'a>>,
+) -> Result<Background, LizError> {
+  rux_dbg_call!(name, args);
+  let mut envs = liz_envs::get_lane_envs(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut dir: Option<String> = None;
+  let mut log: Option<String> = None;
+  let mut append = false;
+  if let Some(opts) = opts {
+    dir = opts.get("dir").map_err(|err| rux_dbg_erro!(err))?;
+    log = opts.get("log").map_err(|err| rux_dbg_erro!(err))?;
+    append = opts
+      .get::<_, Option<bool>>("append")
+      .map_err(|err| rux_dbg_erro!(err))?
+      .unwrap_or(false);
+    let opts_envs: Option<HashMap<String, String>> =
+      opts.get("env").map_err(|err| rux_dbg_erro!(err))?;
+    if let Some(opts_envs) = opts_envs {
+      for (env_name, env_value) in opts_envs {
+        envs.insert(env_name, Some(env_value));
+      }
+    }
+  }
+  let lane_id = get_lane_id(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lane_id);
+  let log = match log {
+    Some(log) => log,
+    None => {
+      let log_name = format!(
+        "liz_bg_{}_{}.log",
+        std::process::id(),
+        BACK_COUNT.fetch_add(1, Ordering::SeqCst)
+      );
+      std::env::temp_dir().join(log_name).display().to_string()
+    }
+  };
+  rux_dbg_step!(log);
+  let log_file = OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(append)
+    .truncate(!append)
+    .open(&log)
+    .map_err(|err| rux_dbg_erro!(err, log))?;
+  let err_file = log_file.try_clone().map_err(|err| rux_dbg_erro!(err))?;
+  let spec = ProcSpec {
+    name: name.into(),
+    args,
+    dir,
+    envs,
+    stdin: None,
+    stdin_file: None,
+    stdout_file: None,
+    stderr_file: None,
+    append,
+    timeout: None,
+    print: false,
+  };
+  let mut command = proc_command(&spec);
+  command.stdin(Stdio::null());
+  command.stdout(Stdio::from(log_file));
+  command.stderr(Stdio::from(err_file));
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+  }
+  let child = command.spawn().map_err(|err| rux_dbg_erro!(err, name))?;
+  liz_exits::track_child(child.id(), true);
+  let background = Background {
+    name: name.into(),
+    pid: child.id(),
+    log,
+    child: Arc::new(Mutex::new(child)),
+  };
+  rux_dbg_step!(background);
+  let mut lane_backs = LANE_BACKS.lock().map_err(|err| rux_dbg_erro!(err))?;
+  lane_backs
+    .entry(lane_id)
+    .or_insert_with(Vec::new)
+    .push(background.clone());
+  rux_dbg_reav!(Ok(background));
+}
+
+pub fn bg_list(lane: Context) -> Result<Vec<Background>, LizError> {
+  rux_dbg_call!();
+  let lane_id = get_lane_id(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  let lane_backs = LANE_BACKS.lock().map_err(|err| rux_dbg_erro!(err))?;
+  let result = match lane_backs.get(&lane_id) {
+    Some(backs) => backs.clone(),
+    None => Vec::new(),
+  };
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn bg_finish(lane_id: usize) {
+  rux_dbg_call!(lane_id);
+  let backs = match LANE_BACKS.lock() {
+    Ok(mut lane_backs) => lane_backs.remove(&lane_id),
+    Err(_) => None,
+  };
+  if let Some(backs) = backs {
+    for background in backs {
+      rux_dbg_tell!(background);
+      background.terminate();
+    }
+  }
+}
+
+pub fn bg_finish_all() {
+  rux_dbg_call!();
+  let lane_ids: Vec<usize> = match LANE_BACKS.lock() {
+    Ok(lane_backs) => lane_backs.keys().cloned().collect(),
+    Err(_) => Vec::new(),
+  };
+  for lane_id in lane_ids {
+    bg_finish(lane_id);
+  }
+}
+
+pub fn get_lane_id(lane: Context) -> Result<usize, LizError> {
+  rux_dbg_call!();
+  let guard: Option<AnyUserData> = lane
+    .named_registry_value("liz_lane_guard")
+    .map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(guard) = guard {
+    let guard = guard
+      .borrow::<LaneGuard>()
+      .map_err(|err| rux_dbg_erro!(err))?;
+    rux_dbg_reav!(Ok(guard.lane_id));
+  }
+  let lane_id = LANE_COUNT.fetch_add(1, Ordering::SeqCst);
+  lane
+    .set_named_registry_value("liz_lane_guard", LaneGuard { lane_id })
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(lane_id));
+}
+
+#[derive(Debug)]
+struct LaneGuard {
+  lane_id: usize,
+}
+
+impl UserData for LaneGuard {}
+
+impl Drop for LaneGuard {
+  fn drop(&mut self) {
+    bg_finish(self.lane_id);
+  }
+}
+
+static BACK_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Clone)]
+pub struct Background {
+  name: String,
+  pid: u32,
+  log: String,
+  child: Arc<Mutex<Child>>,
+}
+
+impl Background {
+  pub fn is_running(&self) -> Result<bool, LizError> {
+    rux_dbg_call!(self.pid);
+    let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    let status = child.try_wait().map_err(|err| rux_dbg_erro!(err))?;
+    rux_dbg_reav!(Ok(status.is_none()));
+  }
+
+  pub fn wait(
+    &self,
+    lane: Context,
+    timeout: Option<u64>,
+  ) -> Result<Option<ProcExit>, LizError> {
+    rux_dbg_call!(self.pid, timeout);
+    rux_dbg_reav!(self.wait_exit(Some(lane), timeout));
+  }
+
+  fn wait_exit(
+    &self,
+    lane: Option<Context>,
+    timeout: Option<u64>,
+  ) -> Result<Option<ProcExit>, LizError> {
+    let deadline = timeout.map(|timeout| Instant::now() + Duration::from_millis(timeout));
+    loop {
+      {
+        let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+        if let Some(status) = child.try_wait().map_err(|err| rux_dbg_erro!(err))? {
+          liz_exits::untrack_child(self.pid);
+          return Ok(Some(ProcExit {
+            code: status.code(),
+            signal: exit_signal(&status),
+            success: status.success(),
+            timed_out: false,
+          }));
+        }
+      }
+      if let Some(deadline) = deadline {
+        if Instant::now() >= deadline {
+          return Ok(None);
+        }
+      }
+      if let Some(lane) = lane {
+        if liz_exits::is_signal_pending() {
+          liz_exits::take_signals(lane).map_err(|err| rux_dbg_bleb!(err))?;
+        }
+      }
+      thread::sleep(Duration::from_millis(10));
+    }
+  }
+
+  pub fn kill(&self, signal: Option<String>) -> Result<(), LizError> {
+    rux_dbg_call!(self.pid, signal);
+    let mut child = self.child.lock().map_err(|err| rux_dbg_erro!(err))?;
+    if child
+      .try_wait()
+      .map_err(|err| rux_dbg_erro!(err))?
+      .is_some()
+    {
+      return Ok(());
+    }
+    match signal {
+      Some(signal) => send_signal(self.pid, &signal).map_err(|err| rux_dbg_bleb!(err)),
+      None if cfg!(unix) => send_signal(self.pid, "KILL").map_err(|err| rux_dbg_bleb!(err)),
+      None => child.kill().map_err(|err| rux_dbg_erro!(err)),
+    }
+  }
+
+  fn terminate(&self) {
+    rux_dbg_call!(self.pid);
+    if let Ok(true) = self.is_running() {
+      if self.kill(Some("TERM".into())).is_err()
+        || self.wait_exit(None, Some(1000)).ok().flatten().is_none()
+      {
+        let _ = self.kill(None);
+        let _ = self.wait_exit(None, None);
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: &str) -> Result<(), LizError> {
+  rux_dbg_call!(pid, signal);
+  let number = liz_exits::signal_number(signal).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(number);
+  if unsafe { libc::kill(-(pid as libc::pid_t), number) } != 0 {
+    return Err(rux_dbg_erro!(std::io::Error::last_os_error()));
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_signal(pid: u32, signal: &str) -> Result<(), LizError> {
+  rux_dbg_call!(pid, signal);
+  Err(rux_dbg_erro!(format!(
+    "Sending the signal {} is only supported on unix",
+    signal
+  )))
+}
+
+fn back_exit_table<'a>(lane: Context<'a>, exited: &ProcExit) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(exited);
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("code", exited.code)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("signal", exited.signal)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("success", exited.success)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+impl UserData for Background {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_method("pid", |_, slf, ()| Ok(slf.pid));
+
+    methods.add_method("name", |_, slf, ()| Ok(slf.name.clone()));
+
+    methods.add_method("log", |_, slf, ()| Ok(slf.log.clone()));
+
+    methods.add_method("is_running", |_, slf, ()| {
+      utils::treat_error(slf.is_running())
+    });
+
+    methods.add_method("kill", |_, slf, signal: Option<String>| {
+      utils::treat_error(slf.kill(signal))
+    });
+
+    methods.add_method(
+      "wait",
+      |lane, slf, timeout: Option<u64>| match utils::treat_error(slf.wait(lane, timeout))? {
+        Some(exited) => Ok(Some(utils::treat_error(back_exit_table(lane, &exited))?)),
+        None => Ok(None),
+      },
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn get_parts(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+  }
+
+  #[test]
+  fn quote_leaves_safe_words_and_quotes_the_others() {
+    let parts = get_parts(&["echo", "a/b.c=d", "hello world", "it's", "", "$HOME"]);
+    assert_eq!(
+      shell_quote(&parts),
+      "echo a/b.c=d 'hello world' 'it'\\''s' '' '$HOME'"
+    );
+  }
+
+  #[test]
+  fn split_handles_quotes_and_escapes() {
+    assert_eq!(
+      shell_split("echo 'hello world' \"a \\\"b\\\" \\$x\" c\\ d").unwrap(),
+      get_parts(&["echo", "hello world", "a \"b\" $x", "c d"])
+    );
+    assert_eq!(
+      shell_split("\"\\n\" a''b ''").unwrap(),
+      get_parts(&["\\n", "ab", ""])
+    );
+    assert_eq!(
+      shell_split("  a \t b\\\nc  ").unwrap(),
+      get_parts(&["a", "bc"])
+    );
+    assert!(shell_split("").unwrap().is_empty());
+    assert!(shell_split("'open").is_err());
+    assert!(shell_split("\"open").is_err());
+  }
+
+  #[test]
+  fn split_reverts_quote() {
+    let parts = get_parts(&["cp", "my file's name", "", "a\"b\\c", "tab\there", "ação"]);
+    assert_eq!(shell_split(&shell_quote(&parts)).unwrap(), parts);
+  }
+
+  #[cfg(unix)]
+  fn get_proc_done<'a>(lane: Context<'a>, spec: &str) -> (Process, ProcExit) {
+    let spec: Table = lane.load(spec).eval().unwrap();
+    let mut process = proc(lane, spec).unwrap();
+    let exited = process.wait(lane).unwrap();
+    (process, exited)
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn proc_captures_outputs_and_exit_code() {
+    rlua::Lua::new().context(|lane| {
+      let (process, exited) = get_proc_done(
+        lane,
+        r#"{"sh", "-c", "read x; echo out:$x:$FOO; echo err >&2; exit 3",
+          stdin = "hi\n", env = {FOO = "bar"}}"#,
+      );
+      assert_eq!(process.stdout, "out:hi:bar\n");
+      assert_eq!(process.stderr, "err\n");
+      assert_eq!(exited.code, Some(3));
+      assert!(!exited.success);
+      assert!(!exited.timed_out);
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn proc_calls_line_callbacks() {
+    rlua::Lua::new().context(|lane| {
+      lane.load("lines = {}").exec().unwrap();
+      let (_, exited) = get_proc_done(
+        lane,
+        r#"{cmd = "sh", args = {"-c", "printf 'a\nb\n'"},
+          on_stdout = function(line) lines[#lines + 1] = line end}"#,
+      );
+      assert!(exited.success);
+      let lines: Vec<String> = lane.load("lines").eval().unwrap();
+      assert_eq!(lines, vec!["a".to_string(), "b".to_string()]);
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn proc_timeout_kills_the_child() {
+    rlua::Lua::new().context(|lane| {
+      let started = Instant::now();
+      let (_, exited) = get_proc_done(lane, r#"{"sleep", "5", timeout = 100}"#);
+      assert!(started.elapsed() < Duration::from_secs(3));
+      assert!(exited.timed_out);
+      assert!(!exited.success);
+      assert_eq!(exited.code, None);
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn proc_wait_does_not_hang_on_grandchild() {
+    rlua::Lua::new().context(|lane| {
+      let started = Instant::now();
+      let (process, exited) =
+        get_proc_done(lane, r#"{"sh", "-c", "echo hi; sleep 3 & echo bye"}"#);
+      assert!(started.elapsed() < Duration::from_secs(2));
+      assert!(exited.success);
+      assert_eq!(process.stdout, "hi\nbye\n");
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn proc_write_feeds_stdin() {
+    rlua::Lua::new().context(|lane| {
+      let spec: Table = lane.load(r#"{"cat"}"#).eval().unwrap();
+      let mut process = proc(lane, spec).unwrap();
+      assert!(process.is_running().unwrap());
+      process.write("one\n").unwrap();
+      process.close_stdin();
+      assert!(process.write("two\n").is_err());
+      let exited = process.wait(lane).unwrap();
+      assert!(exited.success);
+      assert_eq!(process.stdout, "one\n");
+      assert!(!process.is_running().unwrap());
+    });
+  }
+
+  #[test]
+  fn proc_spec_requires_a_command() {
+    rlua::Lua::new().context(|lane| {
+      let spec: Table = lane.load(r#"{dir = "."}"#).eval().unwrap();
+      assert!(proc_spec(lane, &spec).is_err());
+    });
+  }
+
+  #[cfg(unix)]
+  fn get_sh_args(script: &str) -> Vec<String> {
+    get_parts(&["-c", script])
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn bg_logs_outputs_and_exit_code() {
+    rlua::Lua::new().context(|lane| {
+      let background = bg(
+        lane,
+        "sh",
+        get_sh_args("echo out; echo err >&2; exit 4"),
+        None,
+      )
+      .unwrap();
+      let exited = background.wait(lane, Some(3000)).unwrap().unwrap();
+      assert_eq!(exited.code, Some(4));
+      assert!(!exited.success);
+      let logged = std::fs::read_to_string(&background.log).unwrap();
+      assert!(logged.contains("out\n"));
+      assert!(logged.contains("err\n"));
+      let listed = bg_list(lane).unwrap();
+      assert_eq!(listed.len(), 1);
+      assert_eq!(listed[0].pid, background.pid);
+      std::fs::remove_file(&background.log).unwrap();
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn bg_wait_times_out_and_kill_stops() {
+    rlua::Lua::new().context(|lane| {
+      let background = bg(lane, "sleep", get_parts(&["5"]), None).unwrap();
+      assert!(background.is_running().unwrap());
+      assert!(background.wait(lane, Some(50)).unwrap().is_none());
+      background.kill(Some("TERM".into())).unwrap();
+      let exited = background.wait(lane, Some(3000)).unwrap().unwrap();
+      assert_eq!(exited.signal, Some(15));
+      assert!(!background.is_running().unwrap());
+      background.kill(None).unwrap();
+      std::fs::remove_file(&background.log).unwrap();
+    });
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn bg_is_finished_with_the_lane() {
+    let lua = rlua::Lua::new();
+    let background = lua.context(|lane| {
+      let opts: Table = lane.load(r#"{env = {NAP = "5"}}"#).eval().unwrap();
+      bg(lane, "sh", get_sh_args("sleep $NAP"), Some(opts)).unwrap()
+    });
+    assert!(background.is_running().unwrap());
+    let started = Instant::now();
+    drop(lua);
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert!(!background.is_running().unwrap());
+    std::fs::remove_file(&background.log).unwrap();
+  }
+}
@@ -0,0 +1,171 @@
+use minijinja::{AutoEscape, Environment, ErrorKind, UndefinedBehavior};
+use rlua::{Context, Value};
+use rubx::rux_paths;
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
+
+use std::error::Error;
+
+use crate::liz_cases;
+use crate::utils;
+use crate::LizError;
+
+pub fn render(lane: Context, template: &str, data: Value) -> Result<String, LizError> {
+  rux_dbg_call!(template);
+  let dir = utils::get_stacked_dir(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dir);
+  rux_dbg_reav!(render_named("template", template, data, dir));
+}
+
+pub fn render_file(lane: Context, path: &str, data: Value) -> Result<String, LizError> {
+  rux_dbg_call!(path);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let template = std::fs::read_to_string(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let dir = utils::get_stacked_dir(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(dir);
+  let name = rux_paths::path_name(&path).to_string();
+  rux_dbg_reav!(render_named(&name, &template, data, dir));
+}
+
+fn render_named(
+  name: &str,
+  template: &str,
+  data: Value,
+  dir: String,
+) -> Result<String, LizError> {
+  rux_dbg_call!(name, dir);
+  let data = if let Value::Nil = data {
+    serde_json::Value::Object(serde_json::Map::new())
+  } else {
+    utils::to_json_value(data).map_err(|err| rux_dbg_bleb!(err))?
+  };
+  rux_dbg_step!(data);
+  let environment = get_environment(dir);
+  let result = environment
+    .render_named_str(name, template, data)
+    .map_err(|err| rux_dbg_erro!(get_render_error(&err)))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_environment(dir: String) -> Environment<'static> {
+  let mut result = Environment::new();
+  result.set_keep_trailing_newline(true);
+  result.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+  result.set_auto_escape_callback(|_| AutoEscape::None);
+  result.set_loader(move |name| {
+    let path = if rux_paths::is_relative(name) {
+      rux_paths::path_join(&dir, name)
+        .map_err(|err| minijinja::Error::new(ErrorKind::TemplateNotFound, err.to_string()))?
+    } else {
+      name.to_string()
+    };
+    match std::fs::read_to_string(&path) {
+      Ok(source) => Ok(Some(source)),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(
+        minijinja::Error::new(
+          ErrorKind::InvalidOperation,
+          format!("Could not read {}", path),
+        )
+        .with_source(err),
+      ),
+    }
+  });
+  result.add_filter("snake_case", |text: &str| liz_cases::to_snake(text));
+  result.add_filter("camel_case", |text: &str| liz_cases::to_camel(text));
+  result.add_filter("pascal_case", |text: &str| liz_cases::to_pascal(text));
+  result.add_filter("kebab_case", |text: &str| liz_cases::to_kebab(text));
+  result.add_filter("screaming_snake_case", |text: &str| {
+    liz_cases::to_screaming_snake(text)
+  });
+  result.add_filter("title_case", |text: &str| liz_cases::to_title(text));
+  result
+}
+
+fn get_render_error(err: &minijinja::Error) -> String {
+  let mut result = String::from("Could not render the template");
+  if let (Some(name), Some(line)) = (err.name(), err.line()) {
+    result.push_str(&format!(" {} on line {}", name, line));
+  }
+  result.push_str(&format!(
+    ": {}",
+    err.detail().unwrap_or(&err.kind().to_string())
+  ));
+  let mut source = err.source();
+  while let Some(cause) = source {
+    match cause.downcast_ref::<minijinja::Error>() {
+      Some(cause) => {
+        if let (Some(name), Some(line)) = (cause.name(), cause.line()) {
+          result.push_str(&format!("\n  from {} on line {}", name, line));
+        }
+        if let Some(detail) = cause.detail() {
+          result.push_str(&format!(": {}", detail));
+        }
+      }
+      None => result.push_str(&format!("\n  from {}", cause)),
+    }
+    source = cause.source();
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_rendered(
+    template: &str,
+    data: &str,
+    dir: &std::path::Path,
+  ) -> Result<String, LizError> {
+    Lua::new().context(|lane| {
+      let data: Value = lane.load(data).eval().unwrap();
+      render_named("test", template, data, dir.display().to_string())
+    })
+  }
+
+  #[test]
+  fn render_keeps_text_as_is() {
+    let dir = std::env::temp_dir();
+    let render =
+      |template: &str| get_rendered(template, "{ name = 'liz', html = '<b>' }", &dir);
+    assert_eq!(render("Hello {{ name }}!\n").unwrap(), "Hello liz!\n");
+    assert_eq!(render("{{ html }}").unwrap(), "<b>");
+    assert_eq!(get_rendered("plain", "nil", &dir).unwrap(), "plain");
+  }
+
+  #[test]
+  fn render_has_the_case_filters() {
+    let dir = std::env::temp_dir();
+    let render = |filter: &str| {
+      let template = format!("{{{{ text | {} }}}}", filter);
+      get_rendered(&template, "{ text = 'parseHTTP response' }", &dir).unwrap()
+    };
+    assert_eq!(render("snake_case"), "parse_http_response");
+    assert_eq!(render("camel_case"), "parseHttpResponse");
+    assert_eq!(render("pascal_case"), "ParseHttpResponse");
+    assert_eq!(render("kebab_case"), "parse-http-response");
+    assert_eq!(render("screaming_snake_case"), "PARSE_HTTP_RESPONSE");
+    assert_eq!(render("title_case"), "Parse Http Response");
+  }
+
+  #[test]
+  fn render_includes_and_reports_errors() {
+    let dir = std::env::temp_dir().join(format!("liz_renders_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("part.txt"), "[{{ name }}]").unwrap();
+    std::fs::write(dir.join("broken.txt"), "line\n{{ missing.deep }}").unwrap();
+    let render = |template: &str| get_rendered(template, "{ name = 'liz' }", &dir);
+    assert_eq!(render("{% include 'part.txt' %}").unwrap(), "[liz]");
+    let err = render("{{ missing }}").unwrap_err().to_string();
+    assert!(err.starts_with("Could not render the template test on line 1"));
+    let err = render("{% include 'broken.txt' %}")
+      .unwrap_err()
+      .to_string();
+    assert!(err.contains("broken.txt on line 2"), "{}", err);
+    assert!(render("{% include 'none.txt' %}").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,353 @@
+use regex::{Captures, Regex, RegexBuilder};
+use rlua::{Context, Function, Table, UserData, UserDataMethods, Value};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
+
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone)]
+pub struct Rex {
+  regex: Regex,
+}
+
+pub fn rex_compile(pattern: &str, opts: Option<Table>) -> Result<Rex, LizError> {
+  rux_dbg_call!(pattern);
+  let mut builder = RegexBuilder::new(pattern);
+  if let Some(opts) = opts {
+    if let Some(ignore_case) = opts.get("ignore_case").map_err(|err| rux_dbg_erro!(err))? {
+      builder.case_insensitive(ignore_case);
+    }
+    if let Some(multi_line) = opts.get("multi_line").map_err(|err| rux_dbg_erro!(err))? {
+      builder.multi_line(multi_line);
+    }
+    if let Some(dot_all) = opts.get("dot_all").map_err(|err| rux_dbg_erro!(err))? {
+      builder.dot_matches_new_line(dot_all);
+    }
+    if let Some(verbose) = opts.get("verbose").map_err(|err| rux_dbg_erro!(err))? {
+      builder.ignore_whitespace(verbose);
+    }
+  }
+  let regex = builder.build().map_err(|err| rux_dbg_erro!(err, pattern))?;
+  rux_dbg_reav!(Ok(Rex { regex }));
+}
+
+pub fn get_rex(pattern: Value) -> Result<Rex, LizError> {
+  rux_dbg_call!();
+  match pattern {
+    Value::String(pattern) => {
+      let pattern = pattern.to_str().map_err(|err| rux_dbg_erro!(err))?;
+      rux_dbg_reav!(rex_compile(pattern, None));
+    }
+    Value::UserData(data) => {
+      let rex = data.borrow::<Rex>().map_err(|err| rux_dbg_erro!(err))?;
+      rux_dbg_reav!(Ok(rex.clone()));
+    }
+    _ => Err(rux_dbg_erro!(
+      "The pattern must be a text or a compiled regex"
+    )),
+  }
+}
+
+pub fn rex_is_match(rex: &Rex, text: &str) -> bool {
+  rux_dbg_call!(text);
+  rux_dbg_reav!(rex.regex.is_match(text));
+}
+
+pub fn rex_match<'a>(lane: Context<'a>, rex: &Rex, text: &str) -> Result<Value<'a>, LizError> {
+  rux_dbg_call!(text);
+  match rex.regex.captures(text) {
+    Some(captures) => {
+      let result = get_match_table(lane, rex, &captures).map_err(|err| rux_dbg_bleb!(err))?;
+      rux_dbg_reav!(Ok(Value::Table(result)));
+    }
+    None => rux_dbg_reav!(Ok(Value::Nil)),
+  }
+}
+
+pub fn rex_find_all<'a>(
+  lane: Context<'a>,
+  rex: &Rex,
+  text: &str,
+  limit: Option<usize>,
+) -> Result<Vec<Table<'a>>, LizError> {
+  rux_dbg_call!(text, limit);
+  let mut result = Vec::new();
+  for captures in rex.regex.captures_iter(text) {
+    if is_over_limit(result.len(), limit) {
+      break;
+    }
+    result.push(get_match_table(lane, rex, &captures).map_err(|err| rux_dbg_bleb!(err))?);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn rex_replace<'a>(
+  lane: Context<'a>,
+  rex: &Rex,
+  text: &str,
+  with: Value<'a>,
+  limit: Option<usize>,
+) -> Result<(String, usize), LizError> {
+  rux_dbg_call!(text, limit);
+  let with = match with {
+    Value::String(template) => {
+      let template = template.to_str().map_err(|err| rux_dbg_erro!(err))?;
+      rux_dbg_step!(template);
+      let replaced = rex.regex.replacen(text, limit.unwrap_or(0), template);
+      let count = rex.regex.find_iter(text).count();
+      let count = limit.map(|limit| count.min(limit)).unwrap_or(count);
+      rux_dbg_reav!(Ok((replaced.to_string(), count)));
+    }
+    Value::Function(function) => function,
+    _ => {
+      return Err(rux_dbg_erro!(
+        "The replacement must be a template text or a function"
+      ))
+    }
+  };
+  let mut result = String::with_capacity(text.len());
+  let mut last = 0;
+  let mut count = 0;
+  for (index, captures) in rex.regex.captures_iter(text).enumerate() {
+    if is_over_limit(index, limit) {
+      break;
+    }
+    let whole = captures.get(0).expect("the whole match is always present");
+    result.push_str(&text[last..whole.start()]);
+    let replace =
+      get_replacement(lane, rex, &captures, &with).map_err(|err| rux_dbg_bleb!(err))?;
+    match replace {
+      Some(replace) => {
+        result.push_str(&replace);
+        count += 1;
+      }
+      None => result.push_str(whole.as_str()),
+    }
+    last = whole.end();
+  }
+  result.push_str(&text[last..]);
+  rux_dbg_reav!(Ok((result, count)));
+}
+
+pub fn rex_split(rex: &Rex, text: &str, limit: Option<usize>) -> Vec<String> {
+  rux_dbg_call!(text, limit);
+  let result = match limit {
+    Some(limit) if limit > 0 => rex.regex.splitn(text, limit).map(String::from).collect(),
+    _ => rex.regex.split(text).map(String::from).collect(),
+  };
+  rux_dbg_reav!(result);
+}
+
+fn is_over_limit(count: usize, limit: Option<usize>) -> bool {
+  match limit {
+    Some(limit) if limit > 0 => count >= limit,
+    _ => false,
+  }
+}
+
+fn get_replacement<'a>(
+  lane: Context<'a>,
+  rex: &Rex,
+  captures: &Captures,
+  with: &Function<'a>,
+) -> Result<Option<String>, LizError> {
+  let table = get_match_table(lane, rex, captures).map_err(|err| rux_dbg_bleb!(err))?;
+  let replace: Value = with.call(table).map_err(|err| rux_dbg_erro!(err))?;
+  match replace {
+    Value::Nil | Value::Boolean(false) => Ok(None),
+    Value::String(replace) => Ok(Some(
+      replace
+        .to_str()
+        .map_err(|err| rux_dbg_erro!(err))?
+        .to_string(),
+    )),
+    Value::Integer(replace) => Ok(Some(replace.to_string())),
+    Value::Number(replace) => Ok(Some(replace.to_string())),
+    _ => Err(rux_dbg_erro!(
+      "The replacement function must return a text, a number, nil or false"
+    )),
+  }
+}
+
+fn get_match_table<'a>(
+  lane: Context<'a>,
+  rex: &Rex,
+  captures: &Captures,
+) -> Result<Table<'a>, LizError> {
+  let whole = captures.get(0).expect("the whole match is always present");
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("text", whole.as_str())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("start", whole.start() + 1)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("finish", whole.end())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let groups = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  let positions = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  for (index, name) in rex.regex.capture_names().enumerate().skip(1) {
+    let group = match captures.get(index) {
+      Some(group) => group,
+      None => continue,
+    };
+    let position = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+    position
+      .set("start", group.start() + 1)
+      .map_err(|err| rux_dbg_erro!(err))?;
+    position
+      .set("finish", group.end())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    groups
+      .set(index, group.as_str())
+      .map_err(|err| rux_dbg_erro!(err))?;
+    if let Some(name) = name {
+      groups
+        .set(name, group.as_str())
+        .map_err(|err| rux_dbg_erro!(err))?;
+      positions
+        .set(name, position.clone())
+        .map_err(|err| rux_dbg_erro!(err))?;
+    }
+    positions
+      .set(index, position)
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  result
+    .set("captures", groups)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("positions", positions)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  Ok(result)
+}
+
+impl UserData for Rex {
+  fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+    methods.add_method("pattern", |_, slf, ()| Ok(slf.regex.as_str().to_string()));
+
+    methods.add_method("is_match", |_, slf, text: String| {
+      Ok(rex_is_match(slf, &text))
+    });
+
+    methods.add_method("match", |lane, slf, text: String| {
+      utils::treat_error(rex_match(lane, slf, &text))
+    });
+
+    methods.add_method(
+      "find_all",
+      |lane, slf, (text, limit): (String, Option<usize>)| {
+        utils::treat_error(rex_find_all(lane, slf, &text, limit))
+      },
+    );
+
+    methods.add_method(
+      "replace",
+      |lane, slf, (text, with, limit): (String, Value, Option<usize>)| {
+        utils::treat_error(rex_replace(lane, slf, &text, with, limit))
+      },
+    );
+
+    methods.add_method("split", |_, slf, (text, limit): (String, Option<usize>)| {
+      Ok(rex_split(slf, &text, limit))
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_compiled(pattern: &str) -> Rex {
+    rex_compile(pattern, None).unwrap()
+  }
+
+  #[test]
+  fn compile_takes_the_options() {
+    Lua::new().context(|lane| {
+      let opts: Table = lane.load("{ ignore_case = true }").eval().unwrap();
+      let rex = rex_compile("^abc$", Some(opts)).unwrap();
+      assert!(rex_is_match(&rex, "ABC"));
+      assert!(!rex_is_match(&get_compiled("^abc$"), "ABC"));
+      let opts: Table = lane.load("{ multi_line = true }").eval().unwrap();
+      assert!(rex_is_match(
+        &rex_compile("^b$", Some(opts)).unwrap(),
+        "a\nb"
+      ));
+      let opts: Table = lane.load("{ verbose = true }").eval().unwrap();
+      assert!(rex_is_match(
+        &rex_compile("a b # c", Some(opts)).unwrap(),
+        "ab"
+      ));
+      assert!(rex_compile("(", None).is_err());
+      assert!(get_rex(Value::String(lane.create_string("a+").unwrap())).is_ok());
+      let rex = lane.create_userdata(get_compiled("b")).unwrap();
+      assert!(rex_is_match(&get_rex(Value::UserData(rex)).unwrap(), "abc"));
+      assert!(get_rex(Value::Integer(1)).is_err());
+    });
+  }
+
+  #[test]
+  fn matches_have_texts_captures_and_positions() {
+    Lua::new().context(|lane| {
+      let rex = get_compiled("(?P<key>\\w+)=(\\d+)");
+      let found = match rex_match(lane, &rex, "x a=12").unwrap() {
+        Value::Table(found) => found,
+        _ => panic!("no match"),
+      };
+      assert_eq!(found.get::<_, String>("text").unwrap(), "a=12");
+      assert_eq!(found.get::<_, usize>("start").unwrap(), 3);
+      assert_eq!(found.get::<_, usize>("finish").unwrap(), 6);
+      let captures: Table = found.get("captures").unwrap();
+      assert_eq!(captures.get::<_, String>(1).unwrap(), "a");
+      assert_eq!(captures.get::<_, String>("key").unwrap(), "a");
+      assert_eq!(captures.get::<_, String>(2).unwrap(), "12");
+      let positions: Table = found.get("positions").unwrap();
+      let key: Table = positions.get("key").unwrap();
+      assert_eq!(key.get::<_, usize>("start").unwrap(), 3);
+      assert!(matches!(rex_match(lane, &rex, "none").unwrap(), Value::Nil));
+      let all = rex_find_all(lane, &rex, "a=1 b=2 c=3", None).unwrap();
+      assert_eq!(all.len(), 3);
+      assert_eq!(
+        rex_find_all(lane, &rex, "a=1 b=2 c=3", Some(2))
+          .unwrap()
+          .len(),
+        2
+      );
+    });
+  }
+
+  #[test]
+  fn replace_takes_templates_and_functions() {
+    Lua::new().context(|lane| {
+      let rex = get_compiled("a(\\d)");
+      let template = Value::String(lane.create_string("<$1>").unwrap());
+      let replaced = rex_replace(lane, &rex, "a1 a2 a3", template, Some(2)).unwrap();
+      assert_eq!(replaced, ("<1> <2> a3".into(), 2));
+      let function: Function = lane
+        .load(
+          "function(m) if m.captures[1] == '2' then return nil end \
+          return '<' .. m.text .. '>' end",
+        )
+        .eval()
+        .unwrap();
+      let function = Value::Function(function);
+      let replaced = rex_replace(lane, &rex, "a1 a2 a3", function, None).unwrap();
+      assert_eq!(replaced, ("<a1> a2 <a3>".into(), 2));
+      let function: Function = lane.load("function() return {} end").eval().unwrap();
+      assert!(rex_replace(lane, &rex, "a1", Value::Function(function), None).is_err());
+      assert!(rex_replace(lane, &rex, "a1", Value::Boolean(true), None).is_err());
+    });
+  }
+
+  #[test]
+  fn split_respects_the_limit() {
+    let rex = get_compiled(",\\s*");
+    assert_eq!(rex_split(&rex, "a, b,c", None), vec!["a", "b", "c"]);
+    assert_eq!(rex_split(&rex, "a, b,c", Some(2)), vec!["a", "b,c"]);
+    assert_eq!(rex_split(&rex, "a, b,c", Some(0)), vec!["a", "b", "c"]);
+  }
+}
@@ -0,0 +1,355 @@
+use rlua::{Context, Table};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::liz_hashes;
+use crate::liz_paths;
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone)]
+pub struct SyncSpec {
+  pub hash: bool,
+  pub delete: bool,
+  pub exclude: Vec<String>,
+  pub hidden: bool,
+  pub ignore: bool,
+  pub dry_run: bool,
+}
+
+pub fn sync_spec(opts: Option<Table>) -> Result<SyncSpec, LizError> {
+  rux_dbg_call!();
+  let walk = liz_paths::WalkSpec::default();
+  let mut result = SyncSpec {
+    hash: false,
+    delete: false,
+    exclude: Vec::new(),
+    hidden: walk.hidden,
+    ignore: walk.ignore,
+    dry_run: false,
+  };
+  if let Some(opts) = opts {
+    if let Some(compare) = opts
+      .get::<_, Option<String>>("compare")
+      .map_err(|err| rux_dbg_erro!(err))?
+    {
+      result.hash = match compare.trim().to_lowercase().as_str() {
+        "time" => false,
+        "hash" => true,
+        _ => return Err(rux_dbg_erro!(format!("Unknown sync compare: {}", compare))),
+      };
+    }
+    if let Some(delete) = opts.get("delete").map_err(|err| rux_dbg_erro!(err))? {
+      result.delete = delete;
+    }
+    if let Some(exclude) = opts.get("exclude").map_err(|err| rux_dbg_erro!(err))? {
+      result.exclude = exclude;
+    }
+    if let Some(hidden) = opts.get("hidden").map_err(|err| rux_dbg_erro!(err))? {
+      result.hidden = hidden;
+    }
+    if let Some(ignore) = opts.get("ignore").map_err(|err| rux_dbg_erro!(err))? {
+      result.ignore = ignore;
+    }
+    if let Some(dry_run) = opts.get("dry_run").map_err(|err| rux_dbg_erro!(err))? {
+      result.dry_run = dry_run;
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn sync<'a>(
+  lane: Context<'a>,
+  origin: &str,
+  destiny: &str,
+  spec: &SyncSpec,
+) -> Result<Table<'a>, LizError> {
+  rux_dbg_call!(origin, destiny);
+  let origin = utils::get_stacked_path(lane, origin).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(origin);
+  let destiny = utils::get_stacked_path(lane, destiny).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(destiny);
+  if !Path::new(&origin).is_dir() {
+    return Err(rux_dbg_erro!(format!(
+      "The sync origin must be a directory: {}",
+      origin
+    )));
+  }
+  let sources = get_sync_entries(&origin, spec).map_err(|err| rux_dbg_bleb!(err))?;
+  let targets = if Path::new(&destiny).is_dir() {
+    get_sync_entries(&destiny, spec).map_err(|err| rux_dbg_bleb!(err))?
+  } else {
+    BTreeMap::new()
+  };
+  let mut added: Vec<String> = Vec::new();
+  let mut updated: Vec<String> = Vec::new();
+  let mut removed: Vec<String> = Vec::new();
+  if !spec.dry_run {
+    std::fs::create_dir_all(&destiny).map_err(|err| rux_dbg_erro!(err, destiny))?;
+  }
+  for (relative, is_dir) in &sources {
+    let source = Path::new(&origin).join(relative);
+    let target = Path::new(&destiny).join(relative);
+    let change = match targets.get(relative) {
+      None => Some(&mut added),
+      Some(target_is_dir) if target_is_dir != is_dir => Some(&mut updated),
+      Some(_) if *is_dir => None,
+      Some(_) => {
+        if is_sync_changed(&source, &target, spec).map_err(|err| rux_dbg_bleb!(err))? {
+          Some(&mut updated)
+        } else {
+          None
+        }
+      }
+    };
+    let change = match change {
+      Some(change) => change,
+      None => continue,
+    };
+    rux_dbg_tell!(relative, is_dir);
+    change.push(relative.clone());
+    if spec.dry_run {
+      continue;
+    }
+    if let Some(target_is_dir) = targets.get(relative) {
+      if target_is_dir != is_dir {
+        remove_sync_path(&target, *target_is_dir).map_err(|err| rux_dbg_bleb!(err))?;
+      }
+    }
+    if *is_dir {
+      std::fs::create_dir_all(&target).map_err(|err| rux_dbg_erro!(err, relative))?;
+    } else {
+      copy_sync_file(&source, &target).map_err(|err| rux_dbg_bleb!(err))?;
+    }
+  }
+  if spec.delete {
+    for (relative, is_dir) in targets.iter().rev() {
+      if sources.contains_key(relative) {
+        continue;
+      }
+      if let Some(parent) = get_sync_parent(relative) {
+        if targets.contains_key(parent) && sources.get(parent) != targets.get(parent) {
+          continue;
+        }
+      }
+      rux_dbg_tell!(relative, is_dir);
+      removed.push(relative.clone());
+      if !spec.dry_run {
+        let target = Path::new(&destiny).join(relative);
+        remove_sync_path(&target, *is_dir).map_err(|err| rux_dbg_bleb!(err))?;
+      }
+    }
+    removed.sort();
+  }
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("added", added)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("updated", updated)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("removed", removed)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("dry_run", spec.dry_run)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(result));
+}
+
+fn get_sync_entries(dir: &str, spec: &SyncSpec) -> Result<BTreeMap<String, bool>, LizError> {
+  rux_dbg_call!(dir);
+  let builder = liz_paths::walk_builder(dir, spec.hidden, spec.ignore);
+  let mut excluded: Vec<String> = Vec::new();
+  let mut result = BTreeMap::new();
+  for entry in builder.build() {
+    let entry = entry.map_err(|err| rux_dbg_erro!(err))?;
+    if entry.depth() == 0 {
+      continue;
+    }
+    let relative = entry
+      .path()
+      .strip_prefix(dir)
+      .map_err(|err| rux_dbg_erro!(err))?
+      .components()
+      .map(|part| part.as_os_str().to_string_lossy().to_string())
+      .collect::<Vec<String>>()
+      .join("/");
+    if excluded
+      .iter()
+      .any(|parent| relative.starts_with(&format!("{}/", parent)))
+    {
+      continue;
+    }
+    let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+    let mut is_excluded = false;
+    for pattern in &spec.exclude {
+      if liz_paths::glob_match(pattern, &relative).map_err(|err| rux_dbg_bleb!(err))? {
+        is_excluded = true;
+        break;
+      }
+    }
+    if is_excluded {
+      if is_dir {
+        excluded.push(relative);
+      }
+      continue;
+    }
+    result.insert(relative, is_dir);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+fn is_sync_changed(source: &Path, target: &Path, spec: &SyncSpec) -> Result<bool, LizError> {
+  rux_dbg_call!(source, target);
+  let source_meta = std::fs::metadata(source).map_err(|err| rux_dbg_erro!(err, source))?;
+  let target_meta = std::fs::metadata(target).map_err(|err| rux_dbg_erro!(err, target))?;
+  if source_meta.len() != target_meta.len() {
+    rux_dbg_reav!(Ok(true));
+  }
+  if spec.hash {
+    let source = source.display().to_string();
+    let target = target.display().to_string();
+    let source_hash =
+      liz_hashes::hash_path(&source, "sha256").map_err(|err| rux_dbg_bleb!(err))?;
+    let target_hash =
+      liz_hashes::hash_path(&target, "sha256").map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_reav!(Ok(source_hash != target_hash));
+  }
+  let source_time = get_sync_seconds(source_meta.modified());
+  let target_time = get_sync_seconds(target_meta.modified());
+  rux_dbg_reav!(Ok(source_time != target_time));
+}
+
+fn copy_sync_file(source: &Path, target: &Path) -> Result<(), LizError> {
+  rux_dbg_call!(source, target);
+  if let Some(parent) = target.parent() {
+    std::fs::create_dir_all(parent).map_err(|err| rux_dbg_erro!(err, parent))?;
+  }
+  // A copy of a read-only source is read-only too, so it can not be opened for
+  // writing, neither to be copied over on the next sync nor to set its times.
+  let readonly = std::fs::symlink_metadata(target)
+    .map(|metadata| metadata.is_file() && metadata.permissions().readonly())
+    .unwrap_or(false);
+  if readonly {
+    std::fs::remove_file(target).map_err(|err| rux_dbg_erro!(err, target))?;
+  }
+  std::fs::copy(source, target).map_err(|err| rux_dbg_erro!(err, source))?;
+  let modified = std::fs::metadata(source)
+    .and_then(|metadata| metadata.modified())
+    .map_err(|err| rux_dbg_erro!(err, source))?;
+  File::options()
+    .read(true)
+    .write(cfg!(windows))
+    .open(target)
+    .and_then(|file| file.set_modified(modified))
+    .map_err(|err| rux_dbg_erro!(err, target))?;
+  Ok(())
+}
+
+fn remove_sync_path(path: &Path, is_dir: bool) -> Result<(), LizError> {
+  rux_dbg_call!(path, is_dir);
+  if is_dir {
+    std::fs::remove_dir_all(path).map_err(|err| rux_dbg_erro!(err, path))?;
+  } else {
+    std::fs::remove_file(path).map_err(|err| rux_dbg_erro!(err, path))?;
+  }
+  Ok(())
+}
+
+fn get_sync_parent(relative: &str) -> Option<&str> {
+  relative.rfind('/').map(|split| &relative[..split])
+}
+
+fn get_sync_seconds(time: std::io::Result<std::time::SystemTime>) -> Option<u64> {
+  time
+    .ok()
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map(|time| time.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+  use std::path::PathBuf;
+
+  fn get_spec(dry_run: bool) -> SyncSpec {
+    SyncSpec {
+      hash: true,
+      delete: true,
+      exclude: vec!["skip".into()],
+      hidden: true,
+      ignore: false,
+      dry_run,
+    }
+  }
+
+  fn put_files(dir: &Path, files: &[(&str, &str)]) {
+    for (file, contents) in files {
+      let path = dir.join(file);
+      std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+      std::fs::write(&path, contents).unwrap();
+    }
+  }
+
+  fn get_changes(done: &Table, name: &str) -> Vec<String> {
+    done.get(name).unwrap()
+  }
+
+  #[test]
+  fn sync_reports_then_makes_the_changes() {
+    let dir: PathBuf =
+      std::env::temp_dir().join(format!("liz_syncs_changes_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let origin = dir.join("origin");
+    let destiny = dir.join("destiny");
+    put_files(
+      &origin,
+      &[("a.txt", "BBBB"), ("sub/b.txt", "b"), ("skip/c.txt", "c")],
+    );
+    put_files(
+      &destiny,
+      &[("a.txt", "AAAA"), ("old.txt", "old"), ("oldDir/x.txt", "x")],
+    );
+    let origin = origin.display().to_string();
+    let destiny_path = destiny.display().to_string();
+    Lua::new().context(|lane| {
+      let done = sync(lane, &origin, &destiny_path, &get_spec(true)).unwrap();
+      assert_eq!(get_changes(&done, "added"), vec!["sub", "sub/b.txt"]);
+      assert_eq!(get_changes(&done, "updated"), vec!["a.txt"]);
+      assert_eq!(get_changes(&done, "removed"), vec!["old.txt", "oldDir"]);
+      assert!(destiny.join("old.txt").exists());
+      assert!(!destiny.join("sub").exists());
+      let done = sync(lane, &origin, &destiny_path, &get_spec(false)).unwrap();
+      assert_eq!(get_changes(&done, "added"), vec!["sub", "sub/b.txt"]);
+      assert_eq!(
+        std::fs::read_to_string(destiny.join("a.txt")).unwrap(),
+        "BBBB"
+      );
+      assert_eq!(
+        std::fs::read_to_string(destiny.join("sub/b.txt")).unwrap(),
+        "b"
+      );
+      assert!(!destiny.join("old.txt").exists());
+      assert!(!destiny.join("oldDir").exists());
+      assert!(!destiny.join("skip").exists());
+      let done = sync(lane, &origin, &destiny_path, &get_spec(false)).unwrap();
+      assert!(get_changes(&done, "added").is_empty());
+      assert!(get_changes(&done, "updated").is_empty());
+      assert!(get_changes(&done, "removed").is_empty());
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn sync_parents_are_the_dirs_before_the_name() {
+    assert_eq!(get_sync_parent("a/b/c.txt"), Some("a/b"));
+    assert_eq!(get_sync_parent("c.txt"), None);
+  }
+}
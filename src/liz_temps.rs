@@ -0,0 +1,270 @@
+use once_cell::sync::Lazy;
+use rlua::{AnyUserData, Context, UserData};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell, rux_dbg_warn};
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::liz_procs;
+use crate::LizError;
+
+static KEEP_TEMP: AtomicBool = AtomicBool::new(false);
+
+static TEMP_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+static LANE_TEMPS: Lazy<Mutex<HashMap<usize, Vec<Temp>>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn set_keep_temp(keep: bool) {
+  rux_dbg_call!(keep);
+  KEEP_TEMP.store(keep, Ordering::Release);
+}
+
+pub fn is_keep_temp() -> bool {
+  rux_dbg_call!();
+  rux_dbg_reav!(KEEP_TEMP.load(Ordering::Acquire));
+}
+
+pub fn temp_dir(lane: Context) -> Result<String, LizError> {
+  rux_dbg_call!();
+  let lane_id = get_temps_lane(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lane_id);
+  loop {
+    let path = temp_path("");
+    match std::fs::create_dir(&path) {
+      Ok(()) => {
+        rux_dbg_reav!(put_lane_temp(lane_id, path, true));
+      }
+      Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+        rux_dbg_tell!(path);
+      }
+      Err(err) => return Err(rux_dbg_erro!(err, path)),
+    }
+  }
+}
+
+pub fn temp_file(lane: Context, suffix: &str) -> Result<String, LizError> {
+  rux_dbg_call!(suffix);
+  if suffix.contains(['/', '\\']) || suffix.contains("..") {
+    return Err(rux_dbg_erro!(format!(
+      "The temp suffix {} can not have path separators or ..",
+      suffix
+    )));
+  }
+  let lane_id = get_temps_lane(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lane_id);
+  loop {
+    let path = temp_path(suffix);
+    match OpenOptions::new().write(true).create_new(true).open(&path) {
+      Ok(_) => {
+        rux_dbg_reav!(put_lane_temp(lane_id, path, false));
+      }
+      Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+        rux_dbg_tell!(path);
+      }
+      Err(err) => return Err(rux_dbg_erro!(err, path)),
+    }
+  }
+}
+
+pub fn temp_close(lane: Context, path: &str) -> Result<(), LizError> {
+  rux_dbg_call!(path);
+  let lane_id = get_temps_lane(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(lane_id);
+  let temp = {
+    let mut lane_temps = LANE_TEMPS.lock().map_err(|err| rux_dbg_erro!(err))?;
+    let temps = lane_temps.entry(lane_id).or_insert_with(Vec::new);
+    match temps.iter().position(|temp| temp.path == path) {
+      Some(index) => temps.remove(index),
+      None => {
+        return Err(rux_dbg_erro!(format!(
+          "The path {} is not a temp of this lane",
+          path
+        )))
+      }
+    }
+  };
+  rux_dbg_reav!(temp.close());
+}
+
+pub fn temp_finish(lane_id: usize) {
+  rux_dbg_call!(lane_id);
+  let temps = match LANE_TEMPS.lock() {
+    Ok(mut lane_temps) => lane_temps.remove(&lane_id),
+    Err(_) => None,
+  };
+  if let Some(temps) = temps {
+    for temp in temps.iter().rev() {
+      rux_dbg_tell!(temp);
+      if let Err(err) = temp.close() {
+        rux_dbg_warn!("Could not remove the temp", temp.path, err);
+      }
+    }
+  }
+}
+
+pub fn temp_finish_all() {
+  rux_dbg_call!();
+  let lane_ids: Vec<usize> = match LANE_TEMPS.lock() {
+    Ok(lane_temps) => lane_temps.keys().cloned().collect(),
+    Err(_) => Vec::new(),
+  };
+  for lane_id in lane_ids {
+    temp_finish(lane_id);
+  }
+}
+
+fn temp_path(suffix: &str) -> String {
+  let nanos = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|duration| duration.subsec_nanos())
+    .unwrap_or(0);
+  let name = format!(
+    "liz_tmp_{}_{}_{:08x}{}",
+    std::process::id(),
+    TEMP_COUNT.fetch_add(1, Ordering::SeqCst),
+    nanos,
+    suffix
+  );
+  std::env::temp_dir().join(name).display().to_string()
+}
+
+fn put_lane_temp(lane_id: usize, path: String, is_dir: bool) -> Result<String, LizError> {
+  rux_dbg_call!(lane_id, path, is_dir);
+  let mut lane_temps = LANE_TEMPS.lock().map_err(|err| rux_dbg_erro!(err))?;
+  lane_temps
+    .entry(lane_id)
+    .or_insert_with(Vec::new)
+    .push(Temp {
+      path: path.clone(),
+      is_dir,
+    });
+  rux_dbg_reav!(Ok(path));
+}
+
+fn get_temps_lane(lane: Context) -> Result<usize, LizError> {
+  rux_dbg_call!();
+  let guard: Option<AnyUserData> = lane
+    .named_registry_value("liz_temps_guard")
+    .map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(guard) = guard {
+    let guard = guard
+      .borrow::<TempsGuard>()
+      .map_err(|err| rux_dbg_erro!(err))?;
+    rux_dbg_reav!(Ok(guard.lane_id));
+  }
+  let lane_id = liz_procs::get_lane_id(lane).map_err(|err| rux_dbg_bleb!(err))?;
+  lane
+    .set_named_registry_value("liz_temps_guard", TempsGuard { lane_id })
+    .map_err(|err| rux_dbg_erro!(err))?;
+  rux_dbg_reav!(Ok(lane_id));
+}
+
+#[derive(Debug)]
+struct TempsGuard {
+  lane_id: usize,
+}
+
+impl UserData for TempsGuard {}
+
+impl Drop for TempsGuard {
+  fn drop(&mut self) {
+    temp_finish(self.lane_id);
+  }
+}
+
+#[derive(Debug)]
+struct Temp {
+  path: String,
+  is_dir: bool,
+}
+
+impl Temp {
+  fn close(&self) -> Result<(), LizError> {
+    rux_dbg_call!(self.path);
+    if is_keep_temp() {
+      rux_dbg_tell!("keeping", self.path);
+      return Ok(());
+    }
+    let removed = if self.is_dir {
+      std::fs::remove_dir_all(&self.path)
+    } else {
+      std::fs::remove_file(&self.path)
+    };
+    match removed {
+      Ok(()) => Ok(()),
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+      Err(err) => Err(rux_dbg_erro!(err, self.path)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  #[test]
+  fn temp_file_is_created_with_suffix() {
+    rlua::Lua::new().context(|lane| {
+      let path = temp_file(lane, ".txt").unwrap();
+      assert!(path.ends_with(".txt"));
+      assert!(Path::new(&path).is_file());
+      assert!(Path::new(&path).starts_with(std::env::temp_dir()));
+      let other = temp_file(lane, ".txt").unwrap();
+      assert_ne!(path, other);
+      temp_close(lane, &path).unwrap();
+      assert!(!Path::new(&path).exists());
+      assert!(Path::new(&other).exists());
+    });
+  }
+
+  #[test]
+  fn temp_file_refuses_path_suffix() {
+    rlua::Lua::new().context(|lane| {
+      assert!(temp_file(lane, "/escape").is_err());
+      assert!(temp_file(lane, "a\\b").is_err());
+      assert!(temp_file(lane, "..").is_err());
+    });
+  }
+
+  #[test]
+  fn temp_dir_is_closed_with_its_contents() {
+    rlua::Lua::new().context(|lane| {
+      let path = temp_dir(lane).unwrap();
+      assert!(Path::new(&path).is_dir());
+      std::fs::create_dir(Path::new(&path).join("inner")).unwrap();
+      std::fs::write(Path::new(&path).join("inner").join("file"), "data").unwrap();
+      temp_close(lane, &path).unwrap();
+      assert!(!Path::new(&path).exists());
+      assert!(temp_close(lane, &path).is_err());
+    });
+  }
+
+  #[test]
+  fn temp_close_is_only_for_the_lane_temps() {
+    let path = rlua::Lua::new().context(|lane| temp_file(lane, "").unwrap());
+    rlua::Lua::new().context(|lane| {
+      assert!(temp_close(lane, &path).is_err());
+    });
+  }
+
+  #[test]
+  fn temps_are_removed_with_the_lane() {
+    let lua = rlua::Lua::new();
+    let (file, dir) = lua.context(|lane| {
+      let file = temp_file(lane, ".log").unwrap();
+      let dir = temp_dir(lane).unwrap();
+      std::fs::write(Path::new(&dir).join("file"), "data").unwrap();
+      (file, dir)
+    });
+    assert!(Path::new(&file).exists());
+    assert!(Path::new(&dir).exists());
+    drop(lua);
+    assert!(!Path::new(&file).exists());
+    assert!(!Path::new(&dir).exists());
+  }
+}
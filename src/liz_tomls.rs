@@ -0,0 +1,369 @@
+use rlua::{Context, Function, Value as LuaValue};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
+use serde_json::{Map, Value as JsonValue};
+use toml_edit::{
+  Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, TableLike, Value,
+};
+
+use crate::liz_files;
+use crate::utils;
+use crate::LizError;
+
+pub fn toml_parse<'a>(lane: Context<'a>, source: &str) -> Result<LuaValue<'a>, LizError> {
+  rux_dbg_call!(source);
+  let document = get_document(source).map_err(|err| rux_dbg_bleb!(err))?;
+  let json = get_table_json(document.as_table());
+  rux_dbg_reav!(utils::from_json_value(lane, json));
+}
+
+pub fn toml_stringify(value: LuaValue) -> Result<String, LizError> {
+  rux_dbg_call!();
+  let json = utils::to_json_value(value).map_err(|err| rux_dbg_bleb!(err))?;
+  let object = get_root_object(json).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut document = DocumentMut::new();
+  for (key, item) in object {
+    let item = new_item(&item, 0, false).map_err(|err| rux_dbg_bleb!(err))?;
+    document.insert(&key, item);
+  }
+  rux_dbg_reav!(Ok(document.to_string()));
+}
+
+pub fn toml_edit<'a>(
+  lane: Context<'a>,
+  path: &str,
+  function: Function<'a>,
+) -> Result<bool, LizError> {
+  rux_dbg_call!(path);
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  let source = std::fs::read_to_string(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+  let mut document = get_document(&source).map_err(|err| rux_dbg_erro!(err, path))?;
+  let prior = get_table_json(document.as_table());
+  let data = utils::from_json_value(lane, prior.clone()).map_err(|err| rux_dbg_bleb!(err))?;
+  let returned: LuaValue = function
+    .call(data.clone())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  let edited = match returned {
+    LuaValue::Nil => data,
+    LuaValue::Table(_) => returned,
+    _ => {
+      return Err(rux_dbg_erro!(
+        "The toml edit function must return nothing or a table"
+      ))
+    }
+  };
+  let edited = utils::to_json_value(edited).map_err(|err| rux_dbg_bleb!(err))?;
+  let edited = get_root_object(edited).map_err(|err| rux_dbg_bleb!(err))?;
+  let prior = get_root_object(prior).map_err(|err| rux_dbg_bleb!(err))?;
+  put_table_edits(document.as_table_mut(), &prior, &edited, 0, false)
+    .map_err(|err| rux_dbg_bleb!(err))?;
+  let result = document.to_string();
+  if result == source {
+    rux_dbg_reav!(Ok(false));
+  }
+  liz_files::write_atomic(&path, result.as_bytes()).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(Ok(true));
+}
+
+fn get_document(source: &str) -> Result<DocumentMut, LizError> {
+  source
+    .parse::<DocumentMut>()
+    .map_err(|err| rux_dbg_erro!(format!("Could not parse the toml: {}", err)))
+}
+
+fn get_root_object(json: JsonValue) -> Result<Map<String, JsonValue>, LizError> {
+  match json {
+    JsonValue::Object(object) => Ok(object),
+    _ => Err(rux_dbg_erro!(
+      "The toml root must be a table with named keys"
+    )),
+  }
+}
+
+fn get_table_json(table: &dyn TableLike) -> JsonValue {
+  let mut result = Map::new();
+  for (key, item) in table.iter() {
+    if let Some(json) = get_item_json(item) {
+      result.insert(key.to_string(), json);
+    }
+  }
+  JsonValue::Object(result)
+}
+
+fn get_item_json(item: &Item) -> Option<JsonValue> {
+  match item {
+    Item::None => None,
+    Item::Value(value) => Some(get_value_json(value)),
+    Item::Table(table) => Some(get_table_json(table)),
+    Item::ArrayOfTables(tables) => Some(JsonValue::Array(
+      tables.iter().map(|table| get_table_json(table)).collect(),
+    )),
+  }
+}
+
+fn get_value_json(value: &Value) -> JsonValue {
+  match value {
+    Value::String(data) => JsonValue::String(data.value().clone()),
+    Value::Integer(data) => JsonValue::from(*data.value()),
+    Value::Float(data) => JsonValue::from(*data.value()),
+    Value::Boolean(data) => JsonValue::Bool(*data.value()),
+    Value::Datetime(data) => JsonValue::String(data.value().to_string()),
+    Value::Array(data) => JsonValue::Array(data.iter().map(get_value_json).collect()),
+    Value::InlineTable(data) => get_table_json(data),
+  }
+}
+
+// Only the keys whose values really changed are touched, so the comments,
+// ordering and spacing of everything else in the document are kept.
+fn put_table_edits(
+  table: &mut dyn TableLike,
+  prior: &Map<String, JsonValue>,
+  edited: &Map<String, JsonValue>,
+  depth: usize,
+  inline: bool,
+) -> Result<(), LizError> {
+  for key in prior.keys() {
+    if !edited.contains_key(key) {
+      table.remove(key);
+    }
+  }
+  for (key, value) in edited {
+    match (prior.get(key), table.get_mut(key)) {
+      (Some(before), Some(item)) => {
+        put_item_edits(item, before, value, depth, inline).map_err(|err| rux_dbg_bleb!(err))?
+      }
+      _ => {
+        let item = new_item(value, depth, inline).map_err(|err| rux_dbg_bleb!(err))?;
+        table.insert(key, item);
+      }
+    }
+  }
+  Ok(())
+}
+
+fn put_item_edits(
+  item: &mut Item,
+  prior: &JsonValue,
+  edited: &JsonValue,
+  depth: usize,
+  inline: bool,
+) -> Result<(), LizError> {
+  if is_same_json(prior, edited) {
+    return Ok(());
+  }
+  let inline = inline || item.is_inline_table() || item.is_array();
+  match (prior, edited) {
+    (JsonValue::Object(before), JsonValue::Object(after)) => {
+      if let Some(table) = item.as_table_like_mut() {
+        return put_table_edits(table, before, after, depth + 1, inline);
+      }
+    }
+    (JsonValue::Array(before), JsonValue::Array(after)) if before.len() == after.len() => {
+      match item {
+        Item::ArrayOfTables(tables) => {
+          let all_tables = after.iter().all(|value| value.is_object());
+          if all_tables {
+            for (index, table) in tables.iter_mut().enumerate() {
+              if let (JsonValue::Object(before), JsonValue::Object(after)) =
+                (&before[index], &after[index])
+              {
+                put_table_edits(table, before, after, depth + 1, false)
+                  .map_err(|err| rux_dbg_bleb!(err))?;
+              }
+            }
+            return Ok(());
+          }
+        }
+        Item::Value(Value::Array(array)) => {
+          for (index, value) in array.iter_mut().enumerate() {
+            let mut element = Item::Value(std::mem::replace(value, Value::from(false)));
+            put_item_edits(&mut element, &before[index], &after[index], depth + 1, true)
+              .map_err(|err| rux_dbg_bleb!(err))?;
+            *value = element
+              .into_value()
+              .map_err(|_| rux_dbg_erro!("Could not put the toml array value"))?;
+          }
+          return Ok(());
+        }
+        _ => {}
+      }
+    }
+    _ => {}
+  }
+  let mut replaced = if is_empty_json(edited) && item.is_array() {
+    Item::Value(Value::Array(Array::new()))
+  } else {
+    new_item(edited, depth, inline).map_err(|err| rux_dbg_bleb!(err))?
+  };
+  if let (Some(before), Some(after)) = (item.as_value(), replaced.as_value_mut()) {
+    *after.decor_mut() = before.decor().clone();
+  }
+  *item = replaced;
+  Ok(())
+}
+
+fn new_item(json: &JsonValue, depth: usize, inline: bool) -> Result<Item, LizError> {
+  let result = match json {
+    JsonValue::Null => return Err(rux_dbg_erro!("The toml format has no null value")),
+    JsonValue::Bool(data) => Item::Value(Value::from(*data)),
+    JsonValue::Number(data) => match data.as_i64() {
+      Some(data) => Item::Value(Value::from(data)),
+      None => Item::Value(Value::from(data.as_f64().unwrap_or_default())),
+    },
+    JsonValue::String(data) => Item::Value(Value::from(data.as_str())),
+    JsonValue::Array(data) => {
+      let all_tables = !data.is_empty() && data.iter().all(|value| value.is_object());
+      if all_tables && !inline && depth == 0 {
+        let mut tables = ArrayOfTables::new();
+        for value in data {
+          if let JsonValue::Object(object) = value {
+            tables.push(new_table(object, depth + 1).map_err(|err| rux_dbg_bleb!(err))?);
+          }
+        }
+        Item::ArrayOfTables(tables)
+      } else {
+        let mut array = Array::new();
+        for value in data {
+          array.push(new_value(value).map_err(|err| rux_dbg_bleb!(err))?);
+        }
+        Item::Value(Value::Array(array))
+      }
+    }
+    JsonValue::Object(data) => {
+      if !inline && depth == 0 {
+        Item::Table(new_table(data, depth + 1).map_err(|err| rux_dbg_bleb!(err))?)
+      } else {
+        let mut table = InlineTable::new();
+        for (key, value) in data {
+          table.insert(key, new_value(value).map_err(|err| rux_dbg_bleb!(err))?);
+        }
+        Item::Value(Value::InlineTable(table))
+      }
+    }
+  };
+  Ok(result)
+}
+
+fn new_table(object: &Map<String, JsonValue>, depth: usize) -> Result<Table, LizError> {
+  let mut result = Table::new();
+  for (key, value) in object {
+    let item = new_item(value, depth, false).map_err(|err| rux_dbg_bleb!(err))?;
+    result.insert(key, item);
+  }
+  Ok(result)
+}
+
+fn new_value(json: &JsonValue) -> Result<Value, LizError> {
+  new_item(json, 0, true)
+    .map_err(|err| rux_dbg_bleb!(err))?
+    .into_value()
+    .map_err(|_| rux_dbg_erro!("Could not make the toml value"))
+}
+
+fn is_same_json(prior: &JsonValue, edited: &JsonValue) -> bool {
+  prior == edited || (is_empty_json(prior) && is_empty_json(edited))
+}
+
+// Lua has no distinction between an empty array and an empty table.
+fn is_empty_json(json: &JsonValue) -> bool {
+  match json {
+    JsonValue::Array(data) => data.is_empty(),
+    JsonValue::Object(data) => data.is_empty(),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const SOURCE: &str = r#"# top comment
+name = "liz" # trailing
+version = "1.0.0"
+tags = []
+
+[deps]
+# dep comment
+serde = "1"
+rlua = { version = "0.19", features = ["a"] }
+
+[[bin]]
+name = "liz"
+path = "src/main.rs"
+"#;
+
+  fn get_edited(source: &str, edit: impl FnOnce(&mut Map<String, JsonValue>)) -> String {
+    let mut document = get_document(source).unwrap();
+    let prior = get_root_object(get_table_json(document.as_table())).unwrap();
+    let mut edited = prior.clone();
+    edit(&mut edited);
+    put_table_edits(document.as_table_mut(), &prior, &edited, 0, false).unwrap();
+    document.to_string()
+  }
+
+  #[test]
+  fn edit_without_changes_keeps_the_source() {
+    assert_eq!(get_edited(SOURCE, |_| {}), SOURCE);
+    let kept = get_edited(SOURCE, |edited| {
+      edited.insert("tags".into(), json!({}));
+    });
+    assert_eq!(kept, SOURCE);
+  }
+
+  #[test]
+  fn edit_changes_only_the_edited_values() {
+    let result = get_edited(SOURCE, |edited| {
+      edited.insert("version".into(), json!("1.1.0"));
+      edited["deps"]["rlua"]["version"] = json!("0.20");
+      edited["bin"][0]["path"] = json!("src/cli.rs");
+    });
+    let expected = SOURCE
+      .replace("\"1.0.0\"", "\"1.1.0\"")
+      .replace("\"0.19\"", "\"0.20\"")
+      .replace("src/main.rs", "src/cli.rs");
+    assert_eq!(result, expected);
+  }
+
+  #[test]
+  fn edit_removes_and_adds_keys() {
+    let result = get_edited(SOURCE, |edited| {
+      let deps = edited["deps"].as_object_mut().unwrap();
+      deps.remove("serde");
+      deps.insert("toml".into(), json!("0.8"));
+    });
+    let expected = SOURCE
+      .replace("# dep comment\nserde = \"1\"\n", "")
+      .replace(
+        "features = [\"a\"] }\n",
+        "features = [\"a\"] }\ntoml = \"0.8\"\n",
+      );
+    assert_eq!(result, expected);
+  }
+
+  #[test]
+  fn edit_changes_array_values_in_place() {
+    let result = get_edited(SOURCE, |edited| {
+      edited["deps"]["rlua"]["features"] = json!(["a", "b"]);
+      edited.insert("tags".into(), json!(["cli"]));
+    });
+    assert!(result.contains("features = [\"a\", \"b\"] }"));
+    assert!(result.contains("tags = [\"cli\"]\n"));
+    assert!(result.starts_with("# top comment\nname = \"liz\" # trailing\n"));
+  }
+
+  #[test]
+  fn new_items_reject_null_and_make_tables() {
+    assert!(new_item(&JsonValue::Null, 0, false).is_err());
+    assert!(new_item(&json!({"a": 1}), 0, false).unwrap().is_table());
+    assert!(new_item(&json!({"a": 1}), 1, false)
+      .unwrap()
+      .is_inline_table());
+    assert!(new_item(&json!([{"a": 1}]), 0, false)
+      .unwrap()
+      .is_array_of_tables());
+    assert!(get_document("a = ").is_err());
+    assert!(get_root_object(json!([1])).is_err());
+  }
+}
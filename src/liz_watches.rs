@@ -0,0 +1,417 @@
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{RecursiveMode, Watcher};
+use rlua::{Context, Function, Table, Value};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step, rux_dbg_tell};
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::liz_exits;
+use crate::liz_paths;
+use crate::utils;
+use crate::LizError;
+
+#[derive(Debug, Clone)]
+pub struct WatchSpec {
+  pub recursive: bool,
+  pub debounce: u64,
+  pub max_wait: u64,
+  pub timeout: Option<u64>,
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+}
+
+pub fn watch_spec(opts: Option<Table>) -> Result<WatchSpec, LizError> {
+  rux_dbg_call!();
+  let mut result = WatchSpec {
+    recursive: true,
+    debounce: 200,
+    max_wait: 2000,
+    timeout: None,
+    include: Vec::new(),
+    exclude: Vec::new(),
+  };
+  if let Some(opts) = opts {
+    if let Some(recursive) = opts.get("recursive").map_err(|err| rux_dbg_erro!(err))? {
+      result.recursive = recursive;
+    }
+    if let Some(debounce) = opts.get("debounce").map_err(|err| rux_dbg_erro!(err))? {
+      result.debounce = debounce;
+    }
+    if let Some(max_wait) = opts.get("max_wait").map_err(|err| rux_dbg_erro!(err))? {
+      result.max_wait = max_wait;
+    }
+    result.timeout = opts.get("timeout").map_err(|err| rux_dbg_erro!(err))?;
+    if let Some(include) = opts.get("include").map_err(|err| rux_dbg_erro!(err))? {
+      result.include = include;
+    }
+    if let Some(exclude) = opts.get("exclude").map_err(|err| rux_dbg_erro!(err))? {
+      result.exclude = exclude;
+    }
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct WatchEvent {
+  kind: &'static str,
+  path: PathBuf,
+  from: Option<PathBuf>,
+}
+
+pub fn watch<'a>(
+  lane: Context<'a>,
+  paths: Vec<String>,
+  spec: &WatchSpec,
+  callback: Function<'a>,
+) -> Result<usize, LizError> {
+  rux_dbg_call!(paths);
+  let mut roots: Vec<PathBuf> = Vec::new();
+  for path in &paths {
+    let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+    rux_dbg_step!(path);
+    let path = std::fs::canonicalize(&path).map_err(|err| rux_dbg_erro!(err, path))?;
+    roots.push(path);
+  }
+  let (sender, receiver) = mpsc::channel();
+  let mut watcher = notify::recommended_watcher(sender).map_err(|err| rux_dbg_erro!(err))?;
+  let mode = if spec.recursive {
+    RecursiveMode::Recursive
+  } else {
+    RecursiveMode::NonRecursive
+  };
+  for root in &roots {
+    watcher
+      .watch(root, mode)
+      .map_err(|err| rux_dbg_erro!(err, root))?;
+  }
+  let deadline = spec
+    .timeout
+    .map(|timeout| Instant::now() + Duration::from_millis(timeout));
+  let debounce = Duration::from_millis(spec.debounce);
+  let max_wait = Duration::from_millis(spec.max_wait);
+  let mut pending: Vec<WatchEvent> = Vec::new();
+  let mut settled = Instant::now();
+  let mut gathered = Instant::now();
+  let mut delivered = 0;
+  loop {
+    if liz_exits::is_signal_pending() {
+      liz_exits::take_signals(lane).map_err(|err| rux_dbg_bleb!(err))?;
+    }
+    let now = Instant::now();
+    let expired = deadline.map(|deadline| now >= deadline).unwrap_or(false);
+    // Events that never settle are still delivered once they waited the max wait,
+    // and the pending ones are flushed before stopping on the deadline.
+    if !pending.is_empty()
+      && (expired || now >= settled + debounce || now >= gathered + max_wait)
+    {
+      let mut stopped = false;
+      for mut event in pending.drain(..) {
+        event.kind = match get_event_kind(event.kind) {
+          Some(kind) => kind,
+          None => continue,
+        };
+        let table = get_event_table(lane, &roots, &event).map_err(|err| rux_dbg_bleb!(err))?;
+        rux_dbg_tell!(event);
+        delivered += 1;
+        let keep: Value = callback.call(table).map_err(|err| rux_dbg_erro!(err))?;
+        if let Value::Boolean(false) = keep {
+          stopped = true;
+          break;
+        }
+      }
+      if stopped || expired {
+        break;
+      }
+      continue;
+    }
+    if expired {
+      break;
+    }
+    match receiver.recv_timeout(Duration::from_millis(10)) {
+      Ok(Ok(event)) => {
+        for event in get_watch_events(event) {
+          if is_watch_wanted(&roots, &event.path, spec).map_err(|err| rux_dbg_bleb!(err))? {
+            if pending.is_empty() {
+              gathered = Instant::now();
+            }
+            put_watch_event(&mut pending, event);
+            settled = Instant::now();
+          }
+        }
+      }
+      Ok(Err(err)) => return Err(rux_dbg_erro!(err)),
+      Err(RecvTimeoutError::Timeout) => continue,
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+  }
+  rux_dbg_reav!(Ok(delivered));
+}
+
+fn get_watch_events(event: notify::Event) -> Vec<WatchEvent> {
+  let kind = match event.kind {
+    EventKind::Create(_) => "create",
+    EventKind::Remove(_) => "remove",
+    EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+      return vec![WatchEvent {
+        kind: "rename",
+        path: event.paths[1].clone(),
+        from: Some(event.paths[0].clone()),
+      }];
+    }
+    EventKind::Modify(ModifyKind::Name(RenameMode::From)) => "moved_from",
+    EventKind::Modify(ModifyKind::Name(RenameMode::To)) => "moved_to",
+    EventKind::Modify(_) => "modify",
+    _ => return Vec::new(),
+  };
+  event
+    .paths
+    .into_iter()
+    .map(|path| WatchEvent {
+      kind,
+      path,
+      from: None,
+    })
+    .collect()
+}
+
+// The inotify backend reports a rename as its two halves and also as a joined
+// event, so the halves are kept as moved kinds until the joined one arrives.
+fn put_watch_event(pending: &mut Vec<WatchEvent>, mut event: WatchEvent) {
+  if let Some(from) = &event.from {
+    if let Some(index) = pending.iter().position(|prior| &prior.path == from) {
+      if pending[index].kind == "create" || pending[index].kind == "created_moved" {
+        event.kind = "create";
+        event.from = None;
+      }
+      pending.remove(index);
+    }
+  }
+  let index = match pending.iter().position(|prior| prior.path == event.path) {
+    Some(index) => index,
+    None => {
+      pending.push(event);
+      return;
+    }
+  };
+  match (pending[index].kind, event.kind) {
+    ("create", "modify") | ("create", "moved_to") => {}
+    ("rename", "modify") | ("rename", "moved_to") => {}
+    ("create", "remove") => {
+      pending.remove(index);
+    }
+    ("create", "moved_from") => pending[index].kind = "created_moved",
+    ("remove", "create") | ("remove", "moved_to") => pending[index].kind = "modify",
+    (_, _) => {
+      pending.remove(index);
+      pending.push(event);
+    }
+  }
+}
+
+fn get_event_kind(kind: &'static str) -> Option<&'static str> {
+  match kind {
+    "created_moved" => None,
+    "moved_from" => Some("remove"),
+    "moved_to" => Some("create"),
+    _ => Some(kind),
+  }
+}
+
+fn is_watch_wanted(roots: &[PathBuf], path: &Path, spec: &WatchSpec) -> Result<bool, LizError> {
+  if spec.include.is_empty() && spec.exclude.is_empty() {
+    return Ok(true);
+  }
+  let relative = get_watch_relative(roots, path);
+  for pattern in &spec.exclude {
+    if liz_paths::glob_match(pattern, &relative).map_err(|err| rux_dbg_bleb!(err))? {
+      return Ok(false);
+    }
+  }
+  if spec.include.is_empty() {
+    return Ok(true);
+  }
+  for pattern in &spec.include {
+    if liz_paths::glob_match(pattern, &relative).map_err(|err| rux_dbg_bleb!(err))? {
+      return Ok(true);
+    }
+  }
+  Ok(false)
+}
+
+fn get_watch_relative(roots: &[PathBuf], path: &Path) -> String {
+  for root in roots {
+    let base = if root.is_file() {
+      root.parent().unwrap_or(root)
+    } else {
+      root.as_path()
+    };
+    if let Ok(relative) = path.strip_prefix(base) {
+      return relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/");
+    }
+  }
+  path.display().to_string()
+}
+
+fn get_event_table<'a>(
+  lane: Context<'a>,
+  roots: &[PathBuf],
+  event: &WatchEvent,
+) -> Result<Table<'a>, LizError> {
+  let result = lane.create_table().map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("kind", event.kind)
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("path", event.path.display().to_string())
+    .map_err(|err| rux_dbg_erro!(err))?;
+  result
+    .set("relative", get_watch_relative(roots, &event.path))
+    .map_err(|err| rux_dbg_erro!(err))?;
+  if let Some(from) = &event.from {
+    result
+      .set("from", from.display().to_string())
+      .map_err(|err| rux_dbg_erro!(err))?;
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+
+  fn get_event(kind: &'static str, path: &str) -> WatchEvent {
+    WatchEvent {
+      kind,
+      path: PathBuf::from(path),
+      from: None,
+    }
+  }
+
+  fn get_kinds(pending: &[WatchEvent]) -> Vec<(&'static str, String)> {
+    pending
+      .iter()
+      .map(|event| (event.kind, event.path.display().to_string()))
+      .collect()
+  }
+
+  fn get_spec(debounce: u64, max_wait: u64, timeout: u64) -> WatchSpec {
+    WatchSpec {
+      recursive: true,
+      debounce,
+      max_wait,
+      timeout: Some(timeout),
+      include: Vec::new(),
+      exclude: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn pending_events_are_merged_by_path() {
+    let mut pending = Vec::new();
+    put_watch_event(&mut pending, get_event("create", "/w/a"));
+    put_watch_event(&mut pending, get_event("modify", "/w/a"));
+    put_watch_event(&mut pending, get_event("modify", "/w/b"));
+    put_watch_event(&mut pending, get_event("remove", "/w/b"));
+    assert_eq!(
+      get_kinds(&pending),
+      vec![("create", "/w/a".into()), ("remove", "/w/b".into())]
+    );
+    put_watch_event(&mut pending, get_event("remove", "/w/a"));
+    put_watch_event(&mut pending, get_event("create", "/w/b"));
+    assert_eq!(get_kinds(&pending), vec![("modify", "/w/b".into())]);
+  }
+
+  #[test]
+  fn renames_join_their_halves() {
+    let mut pending = Vec::new();
+    put_watch_event(&mut pending, get_event("moved_from", "/w/old"));
+    put_watch_event(&mut pending, get_event("moved_to", "/w/new"));
+    put_watch_event(
+      &mut pending,
+      WatchEvent {
+        kind: "rename",
+        path: PathBuf::from("/w/new"),
+        from: Some(PathBuf::from("/w/old")),
+      },
+    );
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].kind, "rename");
+    assert_eq!(pending[0].from, Some(PathBuf::from("/w/old")));
+    let mut pending = Vec::new();
+    put_watch_event(&mut pending, get_event("create", "/w/temp"));
+    put_watch_event(
+      &mut pending,
+      WatchEvent {
+        kind: "rename",
+        path: PathBuf::from("/w/final"),
+        from: Some(PathBuf::from("/w/temp")),
+      },
+    );
+    assert_eq!(get_kinds(&pending), vec![("create", "/w/final".into())]);
+    assert_eq!(get_event_kind("created_moved"), None);
+    assert_eq!(get_event_kind("moved_from"), Some("remove"));
+    assert_eq!(get_event_kind("moved_to"), Some("create"));
+  }
+
+  #[test]
+  fn wanted_paths_follow_the_patterns() {
+    let roots = vec![PathBuf::from("/w")];
+    let mut spec = get_spec(0, 0, 0);
+    assert!(is_watch_wanted(&roots, Path::new("/w/a.rs"), &spec).unwrap());
+    spec.include = vec!["**/*.rs".into()];
+    spec.exclude = vec!["target/**".into()];
+    assert!(is_watch_wanted(&roots, Path::new("/w/src/a.rs"), &spec).unwrap());
+    assert!(!is_watch_wanted(&roots, Path::new("/w/target/a.rs"), &spec).unwrap());
+    assert!(!is_watch_wanted(&roots, Path::new("/w/a.md"), &spec).unwrap());
+    assert_eq!(
+      get_watch_relative(&roots, Path::new("/w/src/a.rs")),
+      "src/a.rs"
+    );
+  }
+
+  #[test]
+  fn watch_stops_on_the_deadline() {
+    let dir = std::env::temp_dir().join(format!("liz_watches_idle_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    Lua::new().context(|lane| {
+      let callback: Function = lane.load("function() end").eval().unwrap();
+      let started = Instant::now();
+      let paths = vec![dir.display().to_string()];
+      let delivered = watch(lane, paths, &get_spec(100, 1000, 200), callback).unwrap();
+      assert_eq!(delivered, 0);
+      assert!(started.elapsed() < Duration::from_secs(2));
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn watch_delivers_unsettled_events_after_the_max_wait() {
+    let dir = std::env::temp_dir().join(format!("liz_watches_busy_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("busy.txt");
+    let writer = std::thread::spawn(move || {
+      for index in 0..75 {
+        std::fs::write(&file, index.to_string()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+      }
+    });
+    Lua::new().context(|lane| {
+      let callback: Function = lane.load("function() end").eval().unwrap();
+      let started = Instant::now();
+      let paths = vec![dir.display().to_string()];
+      let delivered = watch(lane, paths, &get_spec(500, 200, 1000), callback).unwrap();
+      assert!(delivered >= 3, "delivered only {}", delivered);
+      assert!(started.elapsed() < Duration::from_millis(1400));
+    });
+    writer.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,213 @@
+use rlua::{Context, Value as LuaValue};
+use rubx::{rux_dbg_bleb, rux_dbg_erro};
+use rubx::{rux_dbg_call, rux_dbg_reav, rux_dbg_step};
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+use serde_yaml_ng::Value as YamlValue;
+
+use crate::liz_files;
+use crate::utils;
+use crate::LizError;
+
+pub fn yaml_parse<'a>(lane: Context<'a>, source: &str) -> Result<LuaValue<'a>, LizError> {
+  rux_dbg_call!(source);
+  let mut documents = get_documents(source).map_err(|err| rux_dbg_bleb!(err))?;
+  if documents.len() > 1 {
+    return Err(rux_dbg_erro!(format!(
+      "The yaml has {} documents, use yaml_parse_all to get them all",
+      documents.len()
+    )));
+  }
+  let json = documents.pop().unwrap_or(JsonValue::Null);
+  rux_dbg_reav!(utils::from_json_value(lane, json));
+}
+
+pub fn yaml_parse_all<'a>(
+  lane: Context<'a>,
+  source: &str,
+) -> Result<Vec<LuaValue<'a>>, LizError> {
+  rux_dbg_call!(source);
+  let documents = get_documents(source).map_err(|err| rux_dbg_bleb!(err))?;
+  let mut result = Vec::with_capacity(documents.len());
+  for json in documents {
+    result.push(utils::from_json_value(lane, json).map_err(|err| rux_dbg_bleb!(err))?);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn yaml_stringify(value: LuaValue) -> Result<String, LizError> {
+  rux_dbg_call!();
+  let json = utils::to_json_value(value).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(serde_yaml_ng::to_string(&json).map_err(|err| rux_dbg_erro!(err)));
+}
+
+pub fn yaml_stringify_all(values: Vec<LuaValue>) -> Result<String, LizError> {
+  rux_dbg_call!(values.len());
+  let mut result = String::new();
+  for value in values {
+    let json = utils::to_json_value(value).map_err(|err| rux_dbg_bleb!(err))?;
+    let document = serde_yaml_ng::to_string(&json).map_err(|err| rux_dbg_erro!(err))?;
+    result.push_str("---\n");
+    result.push_str(&document);
+  }
+  rux_dbg_reav!(Ok(result));
+}
+
+pub fn yaml_read<'a>(lane: Context<'a>, path: &str) -> Result<LuaValue<'a>, LizError> {
+  rux_dbg_call!(path);
+  let source = get_yaml_source(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(yaml_parse(lane, &source).map_err(|err| rux_dbg_erro!(err, path)));
+}
+
+pub fn yaml_read_all<'a>(lane: Context<'a>, path: &str) -> Result<Vec<LuaValue<'a>>, LizError> {
+  rux_dbg_call!(path);
+  let source = get_yaml_source(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_reav!(yaml_parse_all(lane, &source).map_err(|err| rux_dbg_erro!(err, path)));
+}
+
+pub fn yaml_write(
+  lane: Context,
+  path: &str,
+  value: LuaValue,
+  atomic: bool,
+) -> Result<(), LizError> {
+  rux_dbg_call!(path, atomic);
+  let contents = yaml_stringify(value).map_err(|err| rux_dbg_bleb!(err))?;
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  rux_dbg_reav!(liz_files::write(&path, contents, atomic));
+}
+
+pub fn yaml_write_all(
+  lane: Context,
+  path: &str,
+  values: Vec<LuaValue>,
+  atomic: bool,
+) -> Result<(), LizError> {
+  rux_dbg_call!(path, atomic);
+  let contents = yaml_stringify_all(values).map_err(|err| rux_dbg_bleb!(err))?;
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  rux_dbg_reav!(liz_files::write(&path, contents, atomic));
+}
+
+fn get_yaml_source(lane: Context, path: &str) -> Result<String, LizError> {
+  let path = utils::get_stacked_path(lane, path).map_err(|err| rux_dbg_bleb!(err))?;
+  rux_dbg_step!(path);
+  std::fs::read_to_string(&path).map_err(|err| rux_dbg_erro!(err, path))
+}
+
+fn get_documents(source: &str) -> Result<Vec<JsonValue>, LizError> {
+  let mut result = Vec::new();
+  for (index, document) in serde_yaml_ng::Deserializer::from_str(source).enumerate() {
+    let mut yaml = YamlValue::deserialize(document).map_err(|err| {
+      rux_dbg_erro!(format!(
+        "Could not parse the yaml document {}: {}",
+        index + 1,
+        err
+      ))
+    })?;
+    yaml.apply_merge().map_err(|err| rux_dbg_erro!(err))?;
+    result.push(get_yaml_json(yaml).map_err(|err| rux_dbg_bleb!(err))?);
+  }
+  Ok(result)
+}
+
+fn get_yaml_json(yaml: YamlValue) -> Result<JsonValue, LizError> {
+  let result = match yaml {
+    YamlValue::Null => JsonValue::Null,
+    YamlValue::Bool(data) => JsonValue::Bool(data),
+    YamlValue::Number(data) => {
+      if let Some(data) = data.as_i64() {
+        JsonValue::from(data)
+      } else if let Some(data) = data.as_u64() {
+        JsonValue::from(data)
+      } else {
+        JsonValue::from(data.as_f64().unwrap_or_default())
+      }
+    }
+    YamlValue::String(data) => JsonValue::String(data),
+    YamlValue::Sequence(data) => {
+      let mut items = Vec::with_capacity(data.len());
+      for item in data {
+        items.push(get_yaml_json(item).map_err(|err| rux_dbg_bleb!(err))?);
+      }
+      JsonValue::Array(items)
+    }
+    YamlValue::Mapping(data) => {
+      let mut items = Map::new();
+      for (key, item) in data {
+        let key = get_yaml_key(key).map_err(|err| rux_dbg_bleb!(err))?;
+        items.insert(key, get_yaml_json(item).map_err(|err| rux_dbg_bleb!(err))?);
+      }
+      JsonValue::Object(items)
+    }
+    YamlValue::Tagged(data) => get_yaml_json(data.value).map_err(|err| rux_dbg_bleb!(err))?,
+  };
+  Ok(result)
+}
+
+fn get_yaml_key(key: YamlValue) -> Result<String, LizError> {
+  match key {
+    YamlValue::String(key) => Ok(key),
+    YamlValue::Number(key) => Ok(key.to_string()),
+    YamlValue::Bool(key) => Ok(key.to_string()),
+    YamlValue::Null => Ok("null".into()),
+    YamlValue::Tagged(key) => get_yaml_key(key.value),
+    _ => Err(rux_dbg_erro!(
+      "The yaml keys must be strings, numbers or booleans"
+    )),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rlua::Lua;
+  use serde_json::json;
+
+  #[test]
+  fn documents_are_split_in_order() {
+    let source = "a: 1\n---\n- x\n- y\n---\nnull\n";
+    let documents = get_documents(source).unwrap();
+    assert_eq!(
+      documents,
+      vec![json!({"a": 1}), json!(["x", "y"]), JsonValue::Null]
+    );
+  }
+
+  #[test]
+  fn documents_merge_and_untag() {
+    let source = "base: &base {x: 1}\nchild:\n  <<: *base\n  y: 2\nsize: !custom 5\n";
+    let documents = get_documents(source).unwrap();
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0]["child"], json!({"x": 1, "y": 2}));
+    assert_eq!(documents[0]["size"], json!(5));
+  }
+
+  #[test]
+  fn documents_stringify_plain_keys() {
+    let documents = get_documents("1: a\ntrue: b\n~: c\n").unwrap();
+    assert_eq!(documents[0], json!({"1": "a", "true": "b", "null": "c"}));
+    assert!(get_documents("[1, 2]: a\n").is_err());
+  }
+
+  #[test]
+  fn documents_report_the_broken_one() {
+    let err = get_documents("a: 1\n---\na: [\n").unwrap_err();
+    assert!(err.to_string().contains("document 2"));
+  }
+
+  #[test]
+  fn multiple_documents_round_trip() {
+    Lua::new().context(|lane| {
+      let source = "name: liz\n---\n- 1\n- 2\n";
+      assert!(yaml_parse(lane, source).is_err());
+      let values = yaml_parse_all(lane, source).unwrap();
+      assert_eq!(values.len(), 2);
+      let result = yaml_stringify_all(values).unwrap();
+      assert_eq!(result, "---\nname: liz\n---\n- 1\n- 2\n");
+      assert_eq!(get_documents(&result).unwrap().len(), 2);
+    });
+  }
+}
@@ -6,6 +6,7 @@ fn main() -> Result<(), LizError> {
     let mut rise_args: Option<Vec<String>> = None;
     let mut first_arg = true;
     let mut script_args = false;
+    let mut single = false;
     for arg in std::env::args() {
         if !script_args {
             if arg == "-h" || arg == "--help" {
@@ -16,8 +17,14 @@ fn main() -> Result<(), LizError> {
                 return Ok(());
             } else if arg == "-v" || arg == "--verbose" {
                 rubx::rux_debug::set_verbose(true);
+                liz::liz_logs::set_log_level("debug")?;
             } else if arg == "-a" || arg == "--archive" {
                 rubx::rux_debug::set_archive(true);
+                liz::liz_logs::set_log_archive(true)?;
+            } else if arg == "-kt" || arg == "--keep-temp" {
+                liz::liz_temps::set_keep_temp(true);
+            } else if arg == "-s" || arg == "--single" {
+                single = true;
             } else if arg == "-lu" || arg == "--lizs-update" {
                 liz::liz_codes::set_lizs_update(true);
             } else if arg == "-dc" || arg == "--debug-calls" {
@@ -51,15 +58,47 @@ fn main() -> Result<(), LizError> {
     if race_paths.is_empty() {
         race_paths.push(format!("start"));
     }
+    liz::liz_exits::trap_signals();
     let first_path = &race_paths[0];
     let (rise_path, handler) =
         liz::rise(first_path, &rise_args).map_err(|err| rux_dbg_bleb!(err))?;
     race_paths[0] = rise_path;
+    let mut result: Result<(), LizError> = if single {
+        liz::liz_files::single_lock(None, &race_paths[0], None)
+            .map(|_| ())
+            .map_err(|err| rux_dbg_bleb!(err))
+    } else {
+        Ok(())
+    };
     for race_path in race_paths {
-        let results = liz::race(&race_path, &handler).map_err(|err| rux_dbg_bleb!(err))?;
-        rux_dbg_info!("Race finished", race_path, results);
+        if result.is_err() {
+            break;
+        }
+        match liz::race(&race_path, &handler) {
+            Ok(results) => {
+                rux_dbg_info!("Race finished", race_path, results);
+            }
+            Err(err) => {
+                result = Err(rux_dbg_bleb!(err));
+                break;
+            }
+        }
+    }
+    let finished = liz::finish(&handler).map_err(|err| rux_dbg_bleb!(err));
+    let result = result.and(finished);
+    let status = liz::liz_exits::get_exit_status(&handler, &result);
+    let quiet = liz::liz_exits::is_exit_quiet(&handler);
+    drop(handler);
+    liz::liz_exits::abort_children();
+    liz::liz_procs::bg_finish_all();
+    liz::liz_temps::temp_finish_all();
+    liz::liz_files::single_finish_all();
+    if let Err(err) = result {
+        if !quiet {
+            eprintln!("Error: {:?}", err);
+        }
     }
-    Ok(())
+    std::process::exit(status);
 }
 
 fn print_help() {
@@ -74,8 +113,10 @@ USAGE:
 
 FLAGS:
     -V, --version       Prints version information.
-    -v, --verbose       Prints verbose information.
-    -a, --archive       Saves the archive log on a file.
+    -v, --verbose       Prints verbose information and the debug logs.
+    -a, --archive       Saves the archive log and the script logs on a file.
+    -kt, --keep-temp    Keeps the temp files and dirs for debugging.
+    -s, --single        Fails if another instance of the script is running.
     -lu, --lizs-update  Updates the lizs scrips on each call.
     -dc, --debug-calls  If has debug symbols, is debuged the functions calls.
     -dr, --debug-reavs  If has debug symbols, is debuged the functions returns.
@@ -1,5 +1,6 @@
 use rlua::{Context, MultiValue, Table, Value as LuaValue};
-use rubx::{rux_dbg_erro, rux_dbg_step};
+use rubx::rux_paths;
+use rubx::{rux_dbg_bleb, rux_dbg_erro, rux_dbg_step};
 use serde_json::Value as JsonValue;
 
 use crate::LizError;
@@ -119,6 +120,58 @@ pub fn to_json(value: LuaValue) -> Result<String, LizError> {
     Ok(result)
 }
 
+pub fn to_json_value(value: LuaValue) -> Result<JsonValue, LizError> {
+    rux_dbg_step!(value);
+    let result = match value {
+        LuaValue::Nil => JsonValue::Null,
+        LuaValue::Boolean(data) => JsonValue::Bool(data),
+        LuaValue::Integer(data) => JsonValue::from(data),
+        LuaValue::Number(data) => JsonValue::from(data),
+        LuaValue::String(data) => {
+            JsonValue::String(data.to_str().map_err(|err| rux_dbg_erro!(err))?.to_string())
+        }
+        LuaValue::Table(data) => {
+            let length = data.raw_len();
+            let mut count = 0;
+            for pair in data.clone().pairs::<LuaValue, LuaValue>() {
+                pair.map_err(|err| rux_dbg_erro!(err))?;
+                count += 1;
+            }
+            if length > 0 && count == length {
+                let mut items = Vec::new();
+                for item in data.sequence_values::<LuaValue>() {
+                    let item = item.map_err(|err| rux_dbg_erro!(err))?;
+                    items.push(to_json_value(item).map_err(|err| rux_dbg_erro!(err))?);
+                }
+                JsonValue::Array(items)
+            } else {
+                let mut items = serde_json::Map::new();
+                for pair in data.pairs::<LuaValue, LuaValue>() {
+                    let (key, item) = pair.map_err(|err| rux_dbg_erro!(err))?;
+                    let key = match key {
+                        LuaValue::String(key) => {
+                            key.to_str().map_err(|err| rux_dbg_erro!(err))?.to_string()
+                        }
+                        LuaValue::Integer(key) => format!("{}", key),
+                        LuaValue::Number(key) => format!("{}", key),
+                        _ => return Err(rux_dbg_erro!("The table keys must be strings or numbers")),
+                    };
+                    items.insert(key, to_json_value(item).map_err(|err| rux_dbg_erro!(err))?);
+                }
+                JsonValue::Object(items)
+            }
+        }
+        LuaValue::Function(data) => JsonValue::String(format!("|LizFunction|[{:?}]", data)),
+        LuaValue::LightUserData(data) => {
+            JsonValue::String(format!("|LizLightUserData|[{:?}]", data))
+        }
+        LuaValue::UserData(data) => JsonValue::String(format!("|LizUserData|[{:?}]", data)),
+        LuaValue::Thread(data) => JsonValue::String(format!("|LizThread|[{:?}]", data)),
+        LuaValue::Error(data) => JsonValue::String(format!("|LizError|[{:?}]", data)),
+    };
+    Ok(result)
+}
+
 pub fn from_json<'a>(lane: Context<'a>, source: String) -> Result<LuaValue<'a>, LizError> {
     rux_dbg_step!(source);
     if source.trim().is_empty() {
@@ -128,7 +181,7 @@ pub fn from_json<'a>(lane: Context<'a>, source: String) -> Result<LuaValue<'a>,
     from_json_value(lane, json)
 }
 
-fn from_json_value<'a>(lane: Context<'a>, value: JsonValue) -> Result<LuaValue<'a>, LizError> {
+pub fn from_json_value<'a>(lane: Context<'a>, value: JsonValue) -> Result<LuaValue<'a>, LizError> {
     rux_dbg_step!(value);
     let result = match value {
         JsonValue::Null => LuaValue::Nil,
@@ -167,3 +220,13 @@ fn from_json_value<'a>(lane: Context<'a>, value: JsonValue) -> Result<LuaValue<'
     };
     Ok(result)
 }
+
+pub fn get_stacked_path(lane: Context, path: &str) -> Result<String, LizError> {
+    rux_dbg_step!(path);
+    if rux_paths::is_relative(path) {
+        let stack_dir = get_stacked_dir(lane).map_err(|err| rux_dbg_bleb!(err))?;
+        rux_paths::path_join(&stack_dir, path).map_err(|err| rux_dbg_bleb!(err))
+    } else {
+        Ok(String::from(path))
+    }
+}
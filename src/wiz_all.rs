@@ -3,15 +3,33 @@ use rubx::rux_paths;
 use rubx::{rux_dbg_bleb, rux_dbg_erro, rux_dbg_step};
 
 use crate::wiz_codes;
+use crate::wiz_diffs;
+use crate::wiz_distances;
+use crate::wiz_edits;
+use crate::wiz_encodes;
+use crate::wiz_envs;
+use crate::wiz_exits;
+use crate::wiz_files;
 use crate::wiz_fires;
 use crate::wiz_forms;
 use crate::wiz_parse;
 use crate::wiz_group;
+use crate::wiz_hashes;
 use crate::wiz_logic;
+use crate::wiz_logs;
+use crate::wiz_packs;
 use crate::wiz_paths;
+use crate::wiz_procs;
+use crate::wiz_renders;
+use crate::wiz_rexes;
+use crate::wiz_syncs;
+use crate::wiz_temps;
 use crate::wiz_texts;
 use crate::wiz_times;
+use crate::wiz_tomls;
+use crate::wiz_watches;
 use crate::wiz_winds;
+use crate::wiz_yamls;
 
 use crate::liz_codes;
 use crate::utils;
@@ -75,15 +93,33 @@ pub fn inject_all(
     liz.set("from_json", from_json)?;
 
     wiz_codes::inject_codes(lane, &liz)?;
+    wiz_diffs::inject_diffs(lane, &liz)?;
+    wiz_distances::inject_distances(lane, &liz)?;
+    wiz_edits::inject_edits(lane, &liz)?;
+    wiz_encodes::inject_encodes(lane, &liz)?;
+    wiz_envs::inject_envs(lane, &liz)?;
+    wiz_exits::inject_exits(lane, &liz)?;
+    wiz_files::inject_files(lane, &liz)?;
     wiz_fires::inject_execs(lane, &liz)?;
     wiz_forms::inject_forms(lane, &liz)?;
     wiz_group::inject_group(lane, &liz)?;
+    wiz_hashes::inject_hashes(lane, &liz)?;
     wiz_logic::inject_logic(lane, &liz)?;
+    wiz_logs::inject_logs(lane, &liz)?;
+    wiz_packs::inject_packs(lane, &liz)?;
     wiz_parse::inject_parse(lane, &liz)?;
     wiz_paths::inject_paths(lane, &liz)?;
+    wiz_procs::inject_procs(lane, &liz)?;
+    wiz_renders::inject_renders(lane, &liz)?;
+    wiz_rexes::inject_rexes(lane, &liz)?;
+    wiz_syncs::inject_syncs(lane, &liz)?;
+    wiz_temps::inject_temps(lane, &liz)?;
     wiz_texts::inject_texts(lane, &liz)?;
     wiz_times::inject_times(lane, &liz)?;
+    wiz_tomls::inject_tomls(lane, &liz)?;
+    wiz_watches::inject_watches(lane, &liz)?;
     wiz_winds::inject_winds(lane, &liz)?;
+    wiz_yamls::inject_yamls(lane, &liz)?;
 
     let globals = lane.globals();
     globals.set("Liz", liz)?;
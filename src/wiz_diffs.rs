@@ -0,0 +1,40 @@
+use rlua::{Context, Table};
+
+use crate::liz_diffs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_diffs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let diff = lane.create_function(
+    |_, (old_text, new_text, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_diffs::diff_spec(opts, "old", "new")
+          .map(|spec| liz_diffs::diff(&old_text, &new_text, &spec)),
+      )
+    },
+  )?;
+
+  let diff_files = lane.create_function(
+    |lane, (old_path, new_path, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_diffs::diff_spec(opts, &old_path, &new_path)
+          .and_then(|spec| liz_diffs::diff_files(lane, &old_path, &new_path, &spec)),
+      )
+    },
+  )?;
+
+  let patch = lane.create_function(
+    |lane, (path, diff_text, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_diffs::patch_spec(opts)
+          .and_then(|spec| liz_diffs::patch(lane, &path, &diff_text, &spec)),
+      )
+    },
+  )?;
+
+  liz.set("diff", diff)?;
+  liz.set("diff_files", diff_files)?;
+  liz.set("patch", patch)?;
+
+  Ok(())
+}
@@ -0,0 +1,64 @@
+use rlua::{Context, Table, Value};
+
+use crate::liz_distances::{self, Distance};
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_distances<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let text_distance =
+    lane.create_function(|_, (text, with, algo): (String, String, Option<String>)| {
+      let distance = utils::treat_error(liz_distances::text_distance(
+        &text,
+        &with,
+        &algo.unwrap_or("levenshtein".into()),
+      ))?;
+      Ok(match distance {
+        Distance::Edits(edits) => Value::Integer(edits as i64),
+        Distance::Ratio(ratio) => Value::Number(ratio),
+      })
+    })?;
+
+  let text_similarity =
+    lane.create_function(|_, (text, with, algo): (String, String, Option<String>)| {
+      utils::treat_error(liz_distances::text_similarity(
+        &text,
+        &with,
+        &algo.unwrap_or("jaro_winkler".into()),
+      ))
+    })?;
+
+  let is_similar = lane.create_function(
+    |_, (text, with, threshold): (String, String, Option<f64>)| {
+      Ok(liz_distances::is_similar(
+        &text,
+        &with,
+        threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD),
+      ))
+    },
+  )?;
+
+  let fuzzy_find = lane.create_function(
+    |lane,
+     (query, candidates, limit, threshold): (
+      String,
+      Vec<String>,
+      Option<usize>,
+      Option<f64>,
+    )| {
+      utils::treat_error(liz_distances::fuzzy_find(
+        lane,
+        &query,
+        candidates,
+        limit,
+        threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD),
+      ))
+    },
+  )?;
+
+  liz.set("text_distance", text_distance)?;
+  liz.set("text_similarity", text_similarity)?;
+  liz.set("is_similar", is_similar)?;
+  liz.set("fuzzy_find", fuzzy_find)?;
+
+  Ok(())
+}
@@ -0,0 +1,22 @@
+use rlua::{Context, Table};
+
+use crate::liz_edits;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_edits<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let edit_file = lane.create_function(|lane, (path, ops): (String, Vec<Table>)| {
+    utils::treat_error(
+      liz_edits::edit_ops(ops).and_then(|ops| liz_edits::edit_file(lane, &path, &ops)),
+    )
+  })?;
+
+  let edit_text = lane.create_function(|_, (text, ops): (String, Vec<Table>)| {
+    utils::treat_error(liz_edits::edit_ops(ops).map(|ops| liz_edits::edit_text(&text, &ops)))
+  })?;
+
+  liz.set("edit_file", edit_file)?;
+  liz.set("edit_text", edit_text)?;
+
+  Ok(())
+}
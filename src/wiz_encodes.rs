@@ -0,0 +1,104 @@
+use rlua::{Context, String as LuaString, Table};
+
+use crate::liz_encodes;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_encodes<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let encode_base64 = lane.create_function(|_, (data, opts): (LuaString, Option<Table>)| {
+    utils::treat_error(
+      liz_encodes::encode_spec(opts)
+        .map(|spec| liz_encodes::encode_base64(data.as_bytes(), &spec)),
+    )
+  })?;
+
+  let decode_base64 = lane.create_function(|lane, text: String| {
+    let data = utils::treat_error(liz_encodes::decode_base64(&text))?;
+    lane.create_string(&data)
+  })?;
+
+  let encode_base64_url =
+    lane.create_function(|_, (data, opts): (LuaString, Option<Table>)| {
+      utils::treat_error(
+        liz_encodes::encode_spec(opts)
+          .map(|spec| liz_encodes::encode_base64_url(data.as_bytes(), &spec)),
+      )
+    })?;
+
+  let decode_base64_url = lane.create_function(|lane, text: String| {
+    let data = utils::treat_error(liz_encodes::decode_base64_url(&text))?;
+    lane.create_string(&data)
+  })?;
+
+  let encode_base32 = lane.create_function(|_, (data, opts): (LuaString, Option<Table>)| {
+    utils::treat_error(
+      liz_encodes::encode_spec(opts)
+        .map(|spec| liz_encodes::encode_base32(data.as_bytes(), &spec)),
+    )
+  })?;
+
+  let decode_base32 = lane.create_function(|lane, text: String| {
+    let data = utils::treat_error(liz_encodes::decode_base32(&text))?;
+    lane.create_string(&data)
+  })?;
+
+  let encode_hex = lane.create_function(|_, (data, opts): (LuaString, Option<Table>)| {
+    utils::treat_error(
+      liz_encodes::encode_spec(opts)
+        .map(|spec| liz_encodes::encode_hex(data.as_bytes(), &spec)),
+    )
+  })?;
+
+  let decode_hex = lane.create_function(|lane, text: String| {
+    let data = utils::treat_error(liz_encodes::decode_hex(&text))?;
+    lane.create_string(&data)
+  })?;
+
+  let encode_percent =
+    lane.create_function(|_, (data, opts): (LuaString, Option<Table>)| {
+      utils::treat_error(
+        liz_encodes::encode_spec(opts)
+          .map(|spec| liz_encodes::encode_percent(data.as_bytes(), &spec)),
+      )
+    })?;
+
+  let decode_percent =
+    lane.create_function(|lane, (text, opts): (String, Option<Table>)| {
+      let data = utils::treat_error(
+        liz_encodes::encode_spec(opts)
+          .and_then(|spec| liz_encodes::decode_percent(&text, &spec)),
+      )?;
+      lane.create_string(&data)
+    })?;
+
+  let encode_html =
+    lane.create_function(|_, text: String| Ok(liz_encodes::encode_html(&text)))?;
+
+  let decode_html = lane
+    .create_function(|_, text: String| utils::treat_error(liz_encodes::decode_html(&text)))?;
+
+  let encode_json_string = lane.create_function(|_, text: String| {
+    utils::treat_error(liz_encodes::encode_json_string(&text))
+  })?;
+
+  let decode_json_string = lane.create_function(|_, text: String| {
+    utils::treat_error(liz_encodes::decode_json_string(&text))
+  })?;
+
+  liz.set("encode_base64", encode_base64)?;
+  liz.set("decode_base64", decode_base64)?;
+  liz.set("encode_base64_url", encode_base64_url)?;
+  liz.set("decode_base64_url", decode_base64_url)?;
+  liz.set("encode_base32", encode_base32)?;
+  liz.set("decode_base32", decode_base32)?;
+  liz.set("encode_hex", encode_hex)?;
+  liz.set("decode_hex", decode_hex)?;
+  liz.set("encode_percent", encode_percent)?;
+  liz.set("decode_percent", decode_percent)?;
+  liz.set("encode_html", encode_html)?;
+  liz.set("decode_html", decode_html)?;
+  liz.set("encode_json_string", encode_json_string)?;
+  liz.set("decode_json_string", decode_json_string)?;
+
+  Ok(())
+}
@@ -0,0 +1,31 @@
+use rlua::{Context, Table};
+
+use crate::liz_envs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_envs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let env_get = lane
+    .create_function(|lane, name: String| utils::treat_error(liz_envs::env_get(lane, &name)))?;
+
+  let env_set = lane.create_function(|lane, (name, value): (String, String)| {
+    utils::treat_error(liz_envs::env_set(lane, &name, &value))
+  })?;
+
+  let env_unset = lane
+    .create_function(|lane, name: String| utils::treat_error(liz_envs::env_unset(lane, &name)))?;
+
+  let env_all = lane.create_function(|lane, ()| utils::treat_error(liz_envs::env_all(lane)))?;
+
+  let env_load = lane.create_function(|lane, (path, overwrite): (String, Option<bool>)| {
+    utils::treat_error(liz_envs::env_load(lane, &path, overwrite.unwrap_or(false)))
+  })?;
+
+  liz.set("env_get", env_get)?;
+  liz.set("env_set", env_set)?;
+  liz.set("env_unset", env_unset)?;
+  liz.set("env_all", env_all)?;
+  liz.set("env_load", env_load)?;
+
+  Ok(())
+}
@@ -0,0 +1,27 @@
+use rlua::{Context, Function, Table};
+
+use crate::liz_exits;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_exits<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let exit = lane.create_function(|lane, code: Option<i32>| {
+    utils::treat_error(liz_exits::exit(lane, code.unwrap_or(0)))
+  })?;
+
+  let on_exit = lane.create_function(|lane, handler: Function| {
+    utils::treat_error(liz_exits::on_exit(lane, handler))
+  })?;
+
+  let on_signal = lane.create_function(|lane, (name, handler): (String, Function)| {
+    utils::treat_error(liz_exits::on_signal(lane, &name, handler))
+  })?;
+
+  liz.set("exit", exit)?;
+  liz.set("on_exit", on_exit)?;
+  liz.set("on_signal", on_signal)?;
+
+  liz_exits::guard_catches(lane)?;
+
+  Ok(())
+}
@@ -0,0 +1,28 @@
+use rlua::{Context, Function, Table};
+
+use crate::liz_files;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_files<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let write_atomic = lane.create_function(|_, (path, contents): (String, String)| {
+    utils::treat_error(liz_files::write_atomic(&path, contents.as_bytes()))
+  })?;
+
+  let lock = lane.create_function(
+    |lane, (path, function, timeout): (String, Function, Option<u64>)| {
+      utils::treat_error(liz_files::lock(lane, &path, timeout, function))
+    },
+  )?;
+
+  let single_instance =
+    lane.create_function(|lane, (name, timeout): (Option<String>, Option<u64>)| {
+      utils::treat_error(liz_files::single_instance(lane, name, timeout))
+    })?;
+
+  liz.set("write_atomic", write_atomic)?;
+  liz.set("lock", lock)?;
+  liz.set("single_instance", single_instance)?;
+
+  Ok(())
+}
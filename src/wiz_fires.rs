@@ -3,7 +3,10 @@ use rubx::rux_fires;
 use rubx::rux_paths;
 use rubx::{rux_dbg_bleb, rux_dbg_call, rux_dbg_reav, rux_dbg_step};
 
+use std::collections::HashMap;
+
 use crate::liz_codes;
+use crate::liz_envs;
 use crate::liz_fires::{self, Spawned};
 use crate::utils;
 use crate::LizError;
@@ -28,7 +31,11 @@ pub fn inject_execs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
             Err(err) => return Err(err),
         };
         rux_dbg_step!(lane_path);
-        utils::treat_error(crate::run(&lane_path, &args))
+        let lane_envs = match utils::treat_error(liz_envs::get_lane_envs(lane)) {
+            Ok(lane_envs) => lane_envs,
+            Err(err) => return Err(err),
+        };
+        utils::treat_error(liz_fires::run_envs(&lane_path, &args, &lane_envs))
     })?;
 
     let eval = lane
@@ -46,34 +53,50 @@ pub fn inject_execs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
         utils::treat_error(liz_fires::race_wd(lane, &relative_path))
     })?;
 
-    let spawn = lane.create_function(|lane, (path, args): (String, Option<Vec<String>>)| {
-        utils::treat_error(liz_fires::spawn(lane, &path, &args))
-    })?;
+    let spawn = lane.create_function(
+        |lane,
+         (path, args, envs): (
+            String,
+            Option<Vec<String>>,
+            Option<HashMap<String, String>>,
+        )| { utils::treat_error(liz_fires::spawn(lane, &path, &args, envs)) },
+    )?;
 
-    let join =
-        lane.create_function(|_, spawned: Spawned| utils::treat_error(liz_fires::join(spawned)))?;
+    let join = lane.create_function(|lane, spawned: Spawned| {
+        utils::treat_error(liz_fires::join(lane, spawned))
+    })?;
 
-    let join_all = lane.create_function(|_, spawneds: Vec<Spawned>| {
-        utils::treat_error(liz_fires::join_all(spawneds))
+    let join_all = lane.create_function(|lane, spawneds: Vec<Spawned>| {
+        utils::treat_error(liz_fires::join_all(lane, spawneds))
     })?;
 
-    let wait =
-        lane.create_function(|_, spawned: Spawned| utils::treat_error(liz_fires::wait(spawned)))?;
+    let wait = lane.create_function(|lane, spawned: Spawned| {
+        utils::treat_error(liz_fires::wait(lane, spawned))
+    })?;
 
-    let wait_all = lane.create_function(|_, spawneds: Vec<Spawned>| {
-        utils::treat_error(liz_fires::wait_all(spawneds))
+    let wait_all = lane.create_function(|lane, spawneds: Vec<Spawned>| {
+        utils::treat_error(liz_fires::wait_all(lane, spawneds))
     })?;
 
     let cmd = lane.create_function(
-        |_,
-         (name, args, dir, print, throw): (
+        |lane,
+         (name, args, dir, print, throw, envs): (
             String,
             Vec<String>,
             Option<String>,
             Option<bool>,
             Option<bool>,
+            Option<HashMap<String, String>>,
         )| {
-            utils::treat_error(rux_fires::cmd(&name, args.as_slice(), dir, print, throw))
+            utils::treat_error(liz_fires::cmd(
+                lane,
+                &name,
+                args.as_slice(),
+                dir,
+                print,
+                throw,
+                envs,
+            ))
         },
     )?;
 
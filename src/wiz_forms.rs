@@ -1,5 +1,7 @@
 use rlua::{Context, Table};
 
+use crate::liz_distances;
+use crate::liz_files;
 use crate::liz_forms;
 use crate::LizError;
 use crate::utils;
@@ -58,6 +60,16 @@ pub fn inject_forms<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
         Ok(liz_forms::kit_find_all_like(&forms, &part))
     })?;
 
+    let kit_find_all_similar = lane.create_function(
+        |_, (forms, part, threshold): (Vec<String>, String, Option<f64>)| {
+            Ok(liz_forms::kit_find_all_similar(
+                &forms,
+                &part,
+                threshold.unwrap_or(liz_distances::SIMILAR_THRESHOLD),
+            ))
+        },
+    )?;
+
     let kit_first_some = lane.create_function(|_, forms: Vec<String>| {
         Ok(liz_forms::kit_first_some(&forms))
     })?;
@@ -99,8 +111,11 @@ pub fn inject_forms<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     )?;
 
     let kit_write = lane.create_function(
-        |_, (forms, path): (Vec<String>, String)| {
-            utils::treat_error(liz_forms::kit_write(&forms, &path))
+        |_, (forms, path, opts): (Vec<String>, String, Option<Table>)| {
+            utils::treat_error(
+                liz_files::is_atomic(opts, true)
+                    .and_then(|atomic| liz_forms::kit_write(&forms, &path, atomic)),
+            )
         },
     )?;
 
@@ -117,6 +132,7 @@ pub fn inject_forms<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     liz.set("kit_pop", kit_pop)?;
     liz.set("kit_find_all", kit_find_all)?;
     liz.set("kit_find_all_like", kit_find_all_like)?;
+    liz.set("kit_find_all_similar", kit_find_all_similar)?;
     liz.set("kit_first_some", kit_first_some)?;
     liz.set("kit_prior_some", kit_prior_some)?;
     liz.set("kit_next_some", kit_next_some)?;
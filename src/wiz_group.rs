@@ -19,6 +19,16 @@ pub fn inject_group<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     let group_likely_not =
         lane.create_function(|_, term: String| Ok(liz_group::group_likely_not(term)))?;
 
+    let group_similar =
+        lane.create_function(|_, (term, threshold): (String, Option<f64>)| {
+            Ok(liz_group::group_similar(term, threshold))
+        })?;
+
+    let group_similar_not =
+        lane.create_function(|_, (term, threshold): (String, Option<f64>)| {
+            Ok(liz_group::group_similar_not(term, threshold))
+        })?;
+
     let group_regex =
         lane.create_function(|_, phrase: String| Ok(liz_group::group_regex(phrase)))?;
 
@@ -79,6 +89,8 @@ pub fn inject_group<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     liz.set("group_equals_not", group_equals_not)?;
     liz.set("group_likely", group_likely)?;
     liz.set("group_likely_not", group_likely_not)?;
+    liz.set("group_similar", group_similar)?;
+    liz.set("group_similar_not", group_similar_not)?;
     liz.set("group_regex", group_regex)?;
     liz.set("group_regex_not", group_regex_not)?;
     liz.set("group_any", group_any)?;
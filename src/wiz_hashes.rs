@@ -0,0 +1,51 @@
+use rlua::{Context, Table};
+
+use crate::liz_hashes;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_hashes<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let hash_text = lane.create_function(|_, (text, algo): (String, Option<String>)| {
+    utils::treat_error(liz_hashes::hash_text(
+      &text,
+      &algo.unwrap_or("sha256".into()),
+    ))
+  })?;
+
+  let hash_file = lane.create_function(|lane, (path, algo): (String, Option<String>)| {
+    utils::treat_error(liz_hashes::hash_file(
+      lane,
+      &path,
+      &algo.unwrap_or("sha256".into()),
+    ))
+  })?;
+
+  let hash_dir = lane.create_function(|lane, (dir, opts): (String, Option<Table>)| {
+    utils::treat_error(
+      liz_hashes::hash_spec(opts, "sha256")
+        .and_then(|spec| liz_hashes::hash_dir(lane, &dir, &spec)),
+    )
+  })?;
+
+  let manifest_write = lane.create_function(
+    |lane, (dir, manifest, opts): (String, Option<String>, Option<Table>)| {
+      utils::treat_error(
+        liz_hashes::hash_spec(opts, "sha256")
+          .and_then(|spec| liz_hashes::manifest_write(lane, &dir, manifest, &spec)),
+      )
+    },
+  )?;
+
+  let manifest_verify =
+    lane.create_function(|lane, (manifest, algo): (String, Option<String>)| {
+      utils::treat_error(liz_hashes::manifest_verify(lane, &manifest, algo))
+    })?;
+
+  liz.set("hash_text", hash_text)?;
+  liz.set("hash_file", hash_file)?;
+  liz.set("hash_dir", hash_dir)?;
+  liz.set("manifest_write", manifest_write)?;
+  liz.set("manifest_verify", manifest_verify)?;
+
+  Ok(())
+}
@@ -0,0 +1,54 @@
+use rlua::{Context, Table};
+
+use crate::liz_logs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_logs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let log_debug =
+    lane.create_function(|lane, (message, fields): (String, Option<Table>)| {
+      utils::treat_error(liz_logs::log_debug(lane, &message, fields))
+    })?;
+
+  let log_info = lane.create_function(|lane, (message, fields): (String, Option<Table>)| {
+    utils::treat_error(liz_logs::log_info(lane, &message, fields))
+  })?;
+
+  let log_warn = lane.create_function(|lane, (message, fields): (String, Option<Table>)| {
+    utils::treat_error(liz_logs::log_warn(lane, &message, fields))
+  })?;
+
+  let log_error =
+    lane.create_function(|lane, (message, fields): (String, Option<Table>)| {
+      utils::treat_error(liz_logs::log_error(lane, &message, fields))
+    })?;
+
+  let log_set_level = lane
+    .create_function(|_, level: String| utils::treat_error(liz_logs::set_log_level(&level)))?;
+
+  let log_get_level =
+    lane.create_function(|_, ()| utils::treat_error(liz_logs::get_log_level()))?;
+
+  let log_set_format = lane.create_function(|_, format: String| {
+    utils::treat_error(liz_logs::set_log_format(&format))
+  })?;
+
+  let log_set_sink = lane
+    .create_function(|_, sink: String| utils::treat_error(liz_logs::set_log_sink(&sink)))?;
+
+  let log_set_rotation = lane.create_function(|_, (max_size, max_files): (u64, usize)| {
+    utils::treat_error(liz_logs::set_log_rotation(max_size, max_files))
+  })?;
+
+  liz.set("log_debug", log_debug)?;
+  liz.set("log_info", log_info)?;
+  liz.set("log_warn", log_warn)?;
+  liz.set("log_error", log_error)?;
+  liz.set("log_set_level", log_set_level)?;
+  liz.set("log_get_level", log_get_level)?;
+  liz.set("log_set_format", log_set_format)?;
+  liz.set("log_set_sink", log_set_sink)?;
+  liz.set("log_set_rotation", log_set_rotation)?;
+
+  Ok(())
+}
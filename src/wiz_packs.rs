@@ -0,0 +1,55 @@
+use rlua::{Context, Table, Value};
+
+use crate::liz_packs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_packs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let zip = lane.create_function(
+    |lane, (sources, dest, opts): (Value, String, Option<Table>)| {
+      let sources = get_sources(sources)?;
+      utils::treat_error(
+        liz_packs::pack_spec(opts).and_then(|spec| liz_packs::zip(lane, sources, &dest, &spec)),
+      )
+    },
+  )?;
+
+  let tar = lane.create_function(
+    |lane, (sources, dest, opts): (Value, String, Option<Table>)| {
+      let sources = get_sources(sources)?;
+      utils::treat_error(
+        liz_packs::pack_spec(opts).and_then(|spec| liz_packs::tar(lane, sources, &dest, &spec)),
+      )
+    },
+  )?;
+
+  let unpack = lane.create_function(
+    |lane, (archive, dest, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_packs::pack_spec(opts)
+          .and_then(|spec| liz_packs::unpack(lane, &archive, &dest, &spec)),
+      )
+    },
+  )?;
+
+  let archive_list = lane.create_function(|lane, archive: String| {
+    utils::treat_error(liz_packs::archive_list(lane, &archive))
+  })?;
+
+  liz.set("zip", zip)?;
+  liz.set("tar", tar)?;
+  liz.set("unpack", unpack)?;
+  liz.set("archive_list", archive_list)?;
+
+  Ok(())
+}
+
+fn get_sources(sources: Value) -> Result<Vec<String>, rlua::Error> {
+  match sources {
+    Value::String(source) => Ok(vec![source.to_str()?.to_string()]),
+    Value::Table(sources) => sources.sequence_values::<String>().collect(),
+    _ => Err(rlua::Error::external(
+      "The sources must be a path or a table of paths",
+    )),
+  }
+}
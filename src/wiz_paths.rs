@@ -1,6 +1,7 @@
-use rlua::{Context, Table};
+use rlua::{Context, Table, Value};
 use rubx::rux_paths;
 
+use crate::liz_paths;
 use crate::utils;
 
 use crate::LizError;
@@ -26,19 +27,20 @@ pub fn inject_paths<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
         utils::treat_error(rux_paths::rn(&origin, &destiny))
     })?;
 
-    let cp = lane.create_function(|_, (origin, destiny): (String, String)| {
-        utils::treat_error(rux_paths::cp(&origin, &destiny))
+    let cp = lane.create_function(|lane, (origin, destiny): (String, String)| {
+        utils::treat_error(liz_paths::cp(lane, &origin, &destiny))
     })?;
 
     let cp_tmp = lane.create_function(|_, (origin, destiny): (String, String)| {
         utils::treat_error(rux_paths::cp_tmp(&origin, &destiny))
     })?;
 
-    let mv = lane.create_function(|_, (origin, destiny): (String, String)| {
-        utils::treat_error(rux_paths::mv(&origin, &destiny))
+    let mv = lane.create_function(|lane, (origin, destiny): (String, String)| {
+        utils::treat_error(liz_paths::mv(lane, &origin, &destiny))
     })?;
 
-    let rm = lane.create_function(|_, path: String| utils::treat_error(rux_paths::rm(&path)))?;
+    let rm =
+        lane.create_function(|lane, path: String| utils::treat_error(liz_paths::rm(lane, &path)))?;
 
     let mkdir =
         lane.create_function(|_, path: String| utils::treat_error(rux_paths::mkdir(&path)))?;
@@ -158,6 +160,55 @@ pub fn inject_paths<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
             ))
         })?;
 
+    let glob = lane.create_function(|lane, patterns: Value| {
+        utils::treat_error(liz_paths::glob(lane, get_patterns(patterns)?))
+    })?;
+
+    let glob_files = lane.create_function(|lane, patterns: Value| {
+        utils::treat_error(liz_paths::glob_files(lane, get_patterns(patterns)?))
+    })?;
+
+    let glob_match = lane.create_function(|_, (pattern, path): (String, String)| {
+        utils::treat_error(liz_paths::glob_match(&pattern, &path))
+    })?;
+
+    let walk = lane.create_function(|lane, (dir, opts): (String, Option<Table>)| {
+        utils::treat_error(
+            liz_paths::walk_spec(opts).and_then(|spec| liz_paths::walk(lane, &dir, spec)),
+        )
+    })?;
+
+    let stat = lane
+        .create_function(|lane, path: String| utils::treat_error(liz_paths::stat(lane, &path)))?;
+
+    let chmod = lane.create_function(|lane, (path, mode): (String, Value)| {
+        let mode = match mode {
+            Value::Integer(mode) => mode.to_string(),
+            Value::Number(mode) if mode.fract() == 0.0 => (mode as i64).to_string(),
+            Value::String(mode) => mode.to_str()?.to_string(),
+            _ => return Err(rlua::Error::external("The chmod mode must be octal digits or a string")),
+        };
+        utils::treat_error(liz_paths::chmod(lane, &path, &mode))
+    })?;
+
+    let set_mtime = lane.create_function(
+        |lane, (path, modified, accessed): (String, f64, Option<f64>)| {
+            utils::treat_error(liz_paths::set_mtime(lane, &path, modified, accessed))
+        },
+    )?;
+
+    let symlink = lane.create_function(|lane, (target, link): (String, String)| {
+        utils::treat_error(liz_paths::symlink(lane, &target, &link))
+    })?;
+
+    let hardlink = lane.create_function(|lane, (origin, link): (String, String)| {
+        utils::treat_error(liz_paths::hardlink(lane, &origin, &link))
+    })?;
+
+    let readlink = lane.create_function(|lane, path: String| {
+        utils::treat_error(liz_paths::readlink(lane, &path))
+    })?;
+
     let path_env = lane.create_function(|_, ()| utils::treat_error(rux_paths::path_env()))?;
 
     let path_env_dirs =
@@ -169,6 +220,16 @@ pub fn inject_paths<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     liz.set("is_absolute", is_absolute)?;
     liz.set("is_relative", is_relative)?;
     liz.set("is_symlink", is_symlink)?;
+    liz.set("glob", glob)?;
+    liz.set("glob_files", glob_files)?;
+    liz.set("glob_match", glob_match)?;
+    liz.set("walk", walk)?;
+    liz.set("stat", stat)?;
+    liz.set("chmod", chmod)?;
+    liz.set("set_mtime", set_mtime)?;
+    liz.set("symlink", symlink)?;
+    liz.set("hardlink", hardlink)?;
+    liz.set("readlink", readlink)?;
     liz.set("cd", cd)?;
     liz.set("wd", wd)?;
     liz.set("rn", rn)?;
@@ -209,3 +270,13 @@ pub fn inject_paths<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
 
     Ok(())
 }
+
+fn get_patterns(patterns: Value) -> Result<Vec<String>, rlua::Error> {
+    match patterns {
+        Value::String(pattern) => Ok(vec![pattern.to_str()?.to_string()]),
+        Value::Table(patterns) => patterns.sequence_values::<String>().collect(),
+        _ => Err(rlua::Error::external(
+            "The glob patterns must be a string or a table of strings",
+        )),
+    }
+}
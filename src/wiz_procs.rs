@@ -0,0 +1,46 @@
+use rlua::{Context, Table, Variadic};
+
+use crate::liz_procs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_procs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let proc = lane
+    .create_function(|lane, spec: Table| utils::treat_error(liz_procs::proc(lane, spec)))?;
+
+  let sh = lane.create_function(|lane, (script, opts): (String, Option<Table>)| {
+    utils::treat_error(liz_procs::sh(lane, &script, opts))
+  })?;
+
+  let pipe = lane.create_function(|lane, stages: Variadic<Table>| {
+    utils::treat_error(liz_procs::pipe(lane, stages.into_iter().collect()))
+  })?;
+
+  let shell_quote = lane.create_function(|_, parts: Variadic<String>| {
+    Ok(liz_procs::shell_quote(
+      &parts.into_iter().collect::<Vec<String>>(),
+    ))
+  })?;
+
+  let shell_split = lane
+    .create_function(|_, line: String| utils::treat_error(liz_procs::shell_split(&line)))?;
+
+  let bg = lane.create_function(
+    |lane, (name, args, opts): (String, Option<Vec<String>>, Option<Table>)| {
+      utils::treat_error(liz_procs::bg(lane, &name, args.unwrap_or_default(), opts))
+    },
+  )?;
+
+  let bg_list =
+    lane.create_function(|lane, ()| utils::treat_error(liz_procs::bg_list(lane)))?;
+
+  liz.set("proc", proc)?;
+  liz.set("sh", sh)?;
+  liz.set("pipe", pipe)?;
+  liz.set("shell_quote", shell_quote)?;
+  liz.set("shell_split", shell_split)?;
+  liz.set("bg", bg)?;
+  liz.set("bg_list", bg_list)?;
+
+  Ok(())
+}
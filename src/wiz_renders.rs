@@ -0,0 +1,20 @@
+use rlua::{Context, Table, Value};
+
+use crate::liz_renders;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_renders<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let render = lane.create_function(|lane, (template, data): (String, Value)| {
+    utils::treat_error(liz_renders::render(lane, &template, data))
+  })?;
+
+  let render_file = lane.create_function(|lane, (path, data): (String, Value)| {
+    utils::treat_error(liz_renders::render_file(lane, &path, data))
+  })?;
+
+  liz.set("render", render)?;
+  liz.set("render_file", render_file)?;
+
+  Ok(())
+}
@@ -0,0 +1,58 @@
+use rlua::{Context, Table, Value};
+
+use crate::liz_rexes;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_rexes<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let rex_compile = lane.create_function(|_, (pattern, opts): (String, Option<Table>)| {
+    utils::treat_error(liz_rexes::rex_compile(&pattern, opts))
+  })?;
+
+  let rex_is_match = lane.create_function(|_, (text, pattern): (String, Value)| {
+    utils::treat_error(
+      liz_rexes::get_rex(pattern).map(|rex| liz_rexes::rex_is_match(&rex, &text)),
+    )
+  })?;
+
+  let rex_match = lane.create_function(|lane, (text, pattern): (String, Value)| {
+    utils::treat_error(
+      liz_rexes::get_rex(pattern).and_then(|rex| liz_rexes::rex_match(lane, &rex, &text)),
+    )
+  })?;
+
+  let rex_find_all = lane.create_function(
+    |lane, (text, pattern, limit): (String, Value, Option<usize>)| {
+      utils::treat_error(
+        liz_rexes::get_rex(pattern)
+          .and_then(|rex| liz_rexes::rex_find_all(lane, &rex, &text, limit)),
+      )
+    },
+  )?;
+
+  let rex_replace = lane.create_function(
+    |lane, (text, pattern, with, limit): (String, Value, Value, Option<usize>)| {
+      utils::treat_error(
+        liz_rexes::get_rex(pattern)
+          .and_then(|rex| liz_rexes::rex_replace(lane, &rex, &text, with, limit)),
+      )
+    },
+  )?;
+
+  let rex_split = lane.create_function(
+    |_, (text, pattern, limit): (String, Value, Option<usize>)| {
+      utils::treat_error(
+        liz_rexes::get_rex(pattern).map(|rex| liz_rexes::rex_split(&rex, &text, limit)),
+      )
+    },
+  )?;
+
+  liz.set("rex_compile", rex_compile)?;
+  liz.set("rex_is_match", rex_is_match)?;
+  liz.set("rex_match", rex_match)?;
+  liz.set("rex_find_all", rex_find_all)?;
+  liz.set("rex_replace", rex_replace)?;
+  liz.set("rex_split", rex_split)?;
+
+  Ok(())
+}
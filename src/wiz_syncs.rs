@@ -0,0 +1,20 @@
+use rlua::{Context, Table};
+
+use crate::liz_syncs;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_syncs<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let sync = lane.create_function(
+    |lane, (origin, destiny, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_syncs::sync_spec(opts)
+          .and_then(|spec| liz_syncs::sync(lane, &origin, &destiny, &spec)),
+      )
+    },
+  )?;
+
+  liz.set("sync", sync)?;
+
+  Ok(())
+}
@@ -0,0 +1,24 @@
+use rlua::{Context, Table};
+
+use crate::liz_temps;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_temps<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let temp_dir =
+    lane.create_function(|lane, ()| utils::treat_error(liz_temps::temp_dir(lane)))?;
+
+  let temp_file = lane.create_function(|lane, suffix: Option<String>| {
+    utils::treat_error(liz_temps::temp_file(lane, &suffix.unwrap_or_default()))
+  })?;
+
+  let temp_close = lane.create_function(|lane, path: String| {
+    utils::treat_error(liz_temps::temp_close(lane, &path))
+  })?;
+
+  liz.set("temp_dir", temp_dir)?;
+  liz.set("temp_file", temp_file)?;
+  liz.set("temp_close", temp_close)?;
+
+  Ok(())
+}
@@ -1,6 +1,9 @@
 use rlua::{Context, Table};
 use rubx::rux_texts;
 
+use crate::liz_cases;
+use crate::liz_files;
+use crate::liz_paths;
 use crate::utils;
 
 use crate::LizError;
@@ -67,6 +70,21 @@ pub fn inject_texts<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
 
   let tocapital = lane.create_function(|_, text: String| Ok(rux_texts::tocapital(&text)))?;
 
+  let to_snake = lane.create_function(|_, text: String| Ok(liz_cases::to_snake(&text)))?;
+
+  let to_camel = lane.create_function(|_, text: String| Ok(liz_cases::to_camel(&text)))?;
+
+  let to_pascal = lane.create_function(|_, text: String| Ok(liz_cases::to_pascal(&text)))?;
+
+  let to_kebab = lane.create_function(|_, text: String| Ok(liz_cases::to_kebab(&text)))?;
+
+  let to_screaming_snake =
+    lane.create_function(|_, text: String| Ok(liz_cases::to_screaming_snake(&text)))?;
+
+  let to_title = lane.create_function(|_, text: String| Ok(liz_cases::to_title(&text)))?;
+
+  let words = lane.create_function(|_, text: String| Ok(liz_cases::words(&text)))?;
+
   let contains = lane.create_function(|_, (text, part): (String, String)| {
     Ok(rux_texts::contains(&text, &part))
   })?;
@@ -102,13 +120,19 @@ pub fn inject_texts<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
     })?;
 
   let text_files_find =
-    lane.create_function(|_, (paths, contents): (Vec<String>, String)| {
-      utils::treat_error(rux_texts::text_files_find(paths, contents))
+    lane.create_function(|lane, (paths, contents): (Vec<String>, String)| {
+      utils::treat_error(
+        liz_paths::get_globbed_files(lane, paths)
+          .and_then(|paths| rux_texts::text_files_find(paths, contents)),
+      )
     })?;
 
   let text_files_find_any =
-    lane.create_function(|_, (paths, contents): (Vec<String>, Vec<String>)| {
-      utils::treat_error(rux_texts::text_files_find_any(paths, contents))
+    lane.create_function(|lane, (paths, contents): (Vec<String>, Vec<String>)| {
+      utils::treat_error(
+        liz_paths::get_globbed_files(lane, paths)
+          .and_then(|paths| rux_texts::text_files_find_any(paths, contents)),
+      )
     })?;
 
   let text_file_founds =
@@ -117,12 +141,17 @@ pub fn inject_texts<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
   let read =
     lane.create_function(|_, path: String| utils::treat_error(rux_texts::read(&path)))?;
 
-  let write = lane.create_function(|_, (path, contents): (String, String)| {
-    utils::treat_error(rux_texts::write(&path, contents))
-  })?;
+  let write = lane.create_function(
+    |_, (path, contents, opts): (String, String, Option<Table>)| {
+      utils::treat_error(
+        liz_files::is_atomic(opts, false)
+          .and_then(|atomic| liz_files::write(&path, contents, atomic)),
+      )
+    },
+  )?;
 
   let append = lane.create_function(|_, (path, contents): (String, String)| {
-    utils::treat_error(rux_texts::append(&path, contents))
+    utils::treat_error(liz_files::append(&path, contents))
   })?;
 
   let write_lines = lane.create_function(|_, (path, lines): (String, Vec<String>)| {
@@ -173,6 +202,13 @@ pub fn inject_texts<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizErr
   liz.set("tolower", tolower)?;
   liz.set("toupper", toupper)?;
   liz.set("tocapital", tocapital)?;
+  liz.set("to_snake", to_snake)?;
+  liz.set("to_camel", to_camel)?;
+  liz.set("to_pascal", to_pascal)?;
+  liz.set("to_kebab", to_kebab)?;
+  liz.set("to_screaming_snake", to_screaming_snake)?;
+  liz.set("to_title", to_title)?;
+  liz.set("words", words)?;
   liz.set("contains", contains)?;
   liz.set("find", find)?;
   liz.set("rfind", rfind)?;
@@ -0,0 +1,24 @@
+use rlua::{Context, Function, Table, Value};
+
+use crate::liz_tomls;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_tomls<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let toml_parse = lane.create_function(|lane, source: String| {
+    utils::treat_error(liz_tomls::toml_parse(lane, &source))
+  })?;
+
+  let toml_stringify = lane
+    .create_function(|_, value: Value| utils::treat_error(liz_tomls::toml_stringify(value)))?;
+
+  let toml_edit = lane.create_function(|lane, (path, function): (String, Function)| {
+    utils::treat_error(liz_tomls::toml_edit(lane, &path, function))
+  })?;
+
+  liz.set("toml_parse", toml_parse)?;
+  liz.set("toml_stringify", toml_stringify)?;
+  liz.set("toml_edit", toml_edit)?;
+
+  Ok(())
+}
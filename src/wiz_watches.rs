@@ -0,0 +1,31 @@
+use rlua::{Context, Function, Table, Value};
+
+use crate::liz_watches;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_watches<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let watch = lane.create_function(
+    |lane, (paths, opts, callback): (Value, Option<Table>, Function)| {
+      let paths = get_paths(paths)?;
+      utils::treat_error(
+        liz_watches::watch_spec(opts)
+          .and_then(|spec| liz_watches::watch(lane, paths, &spec, callback)),
+      )
+    },
+  )?;
+
+  liz.set("watch", watch)?;
+
+  Ok(())
+}
+
+fn get_paths(paths: Value) -> Result<Vec<String>, rlua::Error> {
+  match paths {
+    Value::String(path) => Ok(vec![path.to_str()?.to_string()]),
+    Value::Table(paths) => paths.sequence_values::<String>().collect(),
+    _ => Err(rlua::Error::external(
+      "The watch paths must be a path or a table of paths",
+    )),
+  }
+}
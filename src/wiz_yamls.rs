@@ -0,0 +1,60 @@
+use rlua::{Context, Table, Value};
+
+use crate::liz_files;
+use crate::liz_yamls;
+use crate::utils;
+use crate::LizError;
+
+pub fn inject_yamls<'a>(lane: Context<'a>, liz: &Table<'a>) -> Result<(), LizError> {
+  let yaml_parse = lane.create_function(|lane, source: String| {
+    utils::treat_error(liz_yamls::yaml_parse(lane, &source))
+  })?;
+
+  let yaml_parse_all = lane.create_function(|lane, source: String| {
+    utils::treat_error(liz_yamls::yaml_parse_all(lane, &source))
+  })?;
+
+  let yaml_stringify = lane
+    .create_function(|_, value: Value| utils::treat_error(liz_yamls::yaml_stringify(value)))?;
+
+  let yaml_stringify_all = lane.create_function(|_, values: Vec<Value>| {
+    utils::treat_error(liz_yamls::yaml_stringify_all(values))
+  })?;
+
+  let yaml_read = lane.create_function(|lane, path: String| {
+    utils::treat_error(liz_yamls::yaml_read(lane, &path))
+  })?;
+
+  let yaml_read_all = lane.create_function(|lane, path: String| {
+    utils::treat_error(liz_yamls::yaml_read_all(lane, &path))
+  })?;
+
+  let yaml_write = lane.create_function(
+    |lane, (path, value, opts): (String, Value, Option<Table>)| {
+      utils::treat_error(
+        liz_files::is_atomic(opts, true)
+          .and_then(|atomic| liz_yamls::yaml_write(lane, &path, value, atomic)),
+      )
+    },
+  )?;
+
+  let yaml_write_all = lane.create_function(
+    |lane, (path, values, opts): (String, Vec<Value>, Option<Table>)| {
+      utils::treat_error(
+        liz_files::is_atomic(opts, true)
+          .and_then(|atomic| liz_yamls::yaml_write_all(lane, &path, values, atomic)),
+      )
+    },
+  )?;
+
+  liz.set("yaml_parse", yaml_parse)?;
+  liz.set("yaml_parse_all", yaml_parse_all)?;
+  liz.set("yaml_stringify", yaml_stringify)?;
+  liz.set("yaml_stringify_all", yaml_stringify_all)?;
+  liz.set("yaml_read", yaml_read)?;
+  liz.set("yaml_read_all", yaml_read_all)?;
+  liz.set("yaml_write", yaml_write)?;
+  liz.set("yaml_write_all", yaml_write_all)?;
+
+  Ok(())
+}